@@ -0,0 +1,315 @@
+use crate::{
+    error::{Result, TexProError},
+    node::embed::{EmbeddedSlotData, EmbeddedSlotDataId},
+    node_graph::{resolve_path, NodeGraph, SlotId},
+    slot_data::AlphaMode,
+    slot_image::{Buffer, SlotImage},
+    transient_buffer::{TransientBuffer, TransientBufferContainer},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+const MAGIC: &[u8; 8] = b"VISMUTBN";
+const VERSION: u32 = 1;
+/// The directory inside a bundle that referenced files are stored in.
+const FILE_DIR: &str = "images";
+
+#[derive(Deserialize, Serialize)]
+struct Manifest {
+    node_graph: NodeGraph,
+    embedded: Vec<EmbeddedEntry>,
+    files: Vec<FileEntry>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct EmbeddedEntry {
+    slot_data_id: EmbeddedSlotDataId,
+    slot_id: SlotId,
+    width: u32,
+    height: u32,
+    rgba: bool,
+    /// Missing in bundles written before it was stored, which only had straight alpha data.
+    #[serde(default)]
+    alpha_mode: AlphaMode,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FileEntry {
+    name: String,
+    len: u64,
+}
+
+/// A file stored in a `Bundle`. The `name` is a relative path, and the `Image` nodes in the
+/// bundled `NodeGraph` refer to the file by it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BundledFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// A single file containing a `NodeGraph` along with its embedded `SlotData`s and the image
+/// files it references, so a graph can be handed over as one file.
+///
+/// The file starts with a magic number and a version, followed by a length prefixed JSON
+/// manifest, and then the data of each embedded `SlotData` and file in manifest order. Files
+/// are stored byte for byte and embedded images as little endian `f32`s, so nothing is lost.
+#[derive(Clone, Debug)]
+pub struct Bundle {
+    pub node_graph: NodeGraph,
+    pub embedded_slot_datas: Vec<EmbeddedSlotData>,
+    pub files: Vec<BundledFile>,
+}
+
+impl Bundle {
+    /// Creates a `Bundle` by reading all files referenced by the `NodeGraph`. Each file is
+    /// stored once even if several nodes read it. Paths are resolved against `search_paths`
+    /// like when the images are processed, see `node_graph::resolve_path()`.
+    pub fn new(
+        node_graph: &NodeGraph,
+        embedded_slot_datas: &[Arc<EmbeddedSlotData>],
+        search_paths: &[PathBuf],
+    ) -> Result<Self> {
+        let mut node_graph = node_graph.clone();
+        let mut sources: Vec<PathBuf> = Vec::new();
+        let mut files: Vec<BundledFile> = Vec::new();
+
+        for node in &mut node_graph.nodes {
+            for image_path in node.node_type.image_paths_mut() {
+                let index = match sources.iter().position(|source| source == image_path) {
+                    Some(index) => index,
+                    None => {
+                        let resolved = resolve_path(image_path, search_paths);
+                        let data =
+                            fs::read(resolved.as_ref().unwrap_or(image_path)).map_err(|e| {
+                                TexProError::ReadImage(image_path.clone(), Box::new(e.into()))
+                            })?;
+                        let file_name = image_path
+                            .file_name()
+                            .map(|file_name| file_name.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+
+                        files.push(BundledFile {
+                            name: format!("{}/{}_{}", FILE_DIR, files.len(), file_name),
+                            data,
+                        });
+                        sources.push(image_path.clone());
+                        files.len() - 1
+                    }
+                };
+
+                *image_path = PathBuf::from(&files[index].name);
+            }
+        }
+
+        Ok(Self {
+            node_graph,
+            embedded_slot_datas: embedded_slot_datas
+                .iter()
+                .map(|embedded_slot_data| (**embedded_slot_data).clone())
+                .collect(),
+            files,
+        })
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut embedded = Vec::with_capacity(self.embedded_slot_datas.len());
+        for embedded_slot_data in &self.embedded_slot_datas {
+            let size = embedded_slot_data.image.size()?;
+            embedded.push(EmbeddedEntry {
+                slot_data_id: embedded_slot_data.slot_data_id,
+                slot_id: embedded_slot_data.slot_id,
+                width: size.width,
+                height: size.height,
+                rgba: embedded_slot_data.image.is_rgba(),
+                alpha_mode: embedded_slot_data.alpha_mode,
+            });
+        }
+
+        let manifest = Manifest {
+            node_graph: self.node_graph.clone(),
+            embedded,
+            files: self
+                .files
+                .iter()
+                .map(|file| FileEntry {
+                    name: file.name.clone(),
+                    len: file.data.len() as u64,
+                })
+                .collect(),
+        };
+        let manifest = serde_json::to_vec(&manifest).map_err(io::Error::from)?;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(manifest.len() as u64).to_le_bytes())?;
+        writer.write_all(&manifest)?;
+
+        for embedded_slot_data in &self.embedded_slot_datas {
+            for buf in embedded_slot_data.image.bufs() {
+                for value in buf.transient_buffer().buffer().iter() {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+
+        for file in &self.files {
+            writer.write_all(&file.data)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u32(reader)? != VERSION {
+            return Err(TexProError::InvalidBundle);
+        }
+
+        let manifest_len = read_u64(reader)?;
+        let mut manifest = Vec::new();
+        reader.take(manifest_len).read_to_end(&mut manifest)?;
+        let manifest: Manifest =
+            serde_json::from_slice(&manifest).map_err(|_| TexProError::InvalidBundle)?;
+
+        let mut embedded_slot_datas = Vec::with_capacity(manifest.embedded.len());
+        for entry in manifest.embedded {
+            let channel_count = if entry.rgba { 4 } else { 1 };
+
+            // The data is read before allocating the buffers, so a manifest claiming a huge
+            // size fails on the missing data instead of allocating memory for it.
+            let channel_len = u64::from(entry.width)
+                .checked_mul(u64::from(entry.height))
+                .and_then(|pixel_count| pixel_count.checked_mul(4))
+                .filter(|&channel_len| channel_len > 0)
+                .ok_or(TexProError::InvalidBundle)?;
+            let byte_len = channel_len
+                .checked_mul(channel_count)
+                .ok_or(TexProError::InvalidBundle)?;
+            let mut bytes = Vec::new();
+            reader.take(byte_len).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != byte_len {
+                return Err(TexProError::InvalidBundle);
+            }
+
+            let mut buffers = bytes
+                .chunks_exact(channel_len as usize)
+                .map(|channel| {
+                    let values = channel
+                        .chunks_exact(4)
+                        .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                        .collect();
+                    Buffer::from_raw(entry.width, entry.height, values)
+                        .ok_or(TexProError::InvalidBundle)
+                })
+                .collect::<Result<Vec<Buffer>>>()?;
+
+            let image = if entry.rgba {
+                SlotImage::from_buffers_rgba(&mut buffers)?
+            } else {
+                SlotImage::Gray(Arc::new(TransientBufferContainer::new(Arc::new(
+                    RwLock::new(TransientBuffer::new(Box::new(buffers.remove(0)))),
+                ))))
+            };
+
+            embedded_slot_datas.push(EmbeddedSlotData {
+                slot_data_id: entry.slot_data_id,
+                slot_id: entry.slot_id,
+                image,
+                alpha_mode: entry.alpha_mode,
+            });
+        }
+
+        let mut files = Vec::with_capacity(manifest.files.len());
+        for entry in manifest.files {
+            if !is_relative_name(&entry.name) {
+                return Err(TexProError::InvalidBundle);
+            }
+
+            let mut data = Vec::new();
+            reader.take(entry.len).read_to_end(&mut data)?;
+            if data.len() as u64 != entry.len {
+                return Err(TexProError::InvalidBundle);
+            }
+
+            files.push(BundledFile {
+                name: entry.name,
+                data,
+            });
+        }
+
+        let mut node_graph = manifest.node_graph;
+        node_graph.update_node_id_counter();
+
+        Ok(Self {
+            node_graph,
+            embedded_slot_datas,
+            files,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the bundled files to `dir` and returns the `NodeGraph` with its image paths
+    /// pointing to the written files, along with the embedded `SlotData`s.
+    pub fn extract<P: AsRef<Path>>(self, dir: P) -> Result<(NodeGraph, Vec<EmbeddedSlotData>)> {
+        let dir = dir.as_ref();
+
+        for file in &self.files {
+            let path = dir.join(&file.name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, &file.data)?;
+        }
+
+        let mut node_graph = self.node_graph;
+        for node in &mut node_graph.nodes {
+            for image_path in node.node_type.image_paths_mut() {
+                if self
+                    .files
+                    .iter()
+                    .any(|file| Path::new(&file.name) == image_path)
+                {
+                    *image_path = dir.join(&image_path);
+                }
+            }
+        }
+
+        Ok((node_graph, self.embedded_slot_datas))
+    }
+}
+
+/// Checks that a bundled file name is a relative path that stays inside the directory it's
+/// extracted to, since the names come from the bundle and can't be trusted.
+fn is_relative_name(name: &str) -> bool {
+    let mut components = Path::new(name).components().peekable();
+    components.peek().is_some()
+        && components.all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
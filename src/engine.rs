@@ -8,7 +8,7 @@ use crate::{
     edge::Edge,
     error::{Result, TexProError},
     live_graph::{LiveGraph, NodeState},
-    node::{embed::EmbeddedSlotData, node_type::process_node},
+    node::{embed::EmbeddedSlotData, image, node_type::process_node},
     node_graph::NodeId,
     process_pack::ProcessPack,
     slot_data::SlotData,
@@ -43,7 +43,17 @@ pub(crate) fn process_loop(tex_pro: Arc<TextureProcessor>) {
 
                 let node_id = message.node_id;
 
-                match message.slot_datas {
+                let slot_datas = match message.slot_datas {
+                    Err(TexProError::ReadImage(..)) if live_graph.missing_image_placeholder => {
+                        Ok(image::placeholder(node_id))
+                    }
+                    slot_datas => slot_datas,
+                };
+
+                match slot_datas {
+                    // An ancestor failed while the node was being processed, so the result is
+                    // thrown away.
+                    Ok(_) if live_graph.node_state(node_id) == Ok(NodeState::Failed) => (),
                     Ok(slot_datas) => {
                         for slot_data in &slot_datas {
                             TransientBufferQueue::add_slot_data(
@@ -109,13 +119,18 @@ pub(crate) fn process_loop(tex_pro: Arc<TextureProcessor>) {
                             }
                         }
                         _ => {
-                            tex_pro.shutdown.store(true, Ordering::Relaxed);
-                            panic!(
-                                "Error when processing '{:?}' node with id '{}': {}",
-                                live_graph.node_graph.node(node_id).unwrap().node_type,
-                                node_id,
-                                e
-                            );
+                            // If the node was changed while it was processed it gets another
+                            // try, otherwise the error is stored on the node.
+                            if let Ok(node) = live_graph.node(node_id) {
+                                if node.cancel.swap(false, Ordering::SeqCst)
+                                    || live_graph.node_state(node_id)
+                                        == Ok(NodeState::ProcessingDirty)
+                                {
+                                    let _ = live_graph.force_state(node_id, NodeState::Dirty);
+                                } else {
+                                    let _ = live_graph.fail_node(node_id, e);
+                                }
+                            }
                         }
                     },
                 }
@@ -125,8 +140,13 @@ pub(crate) fn process_loop(tex_pro: Arc<TextureProcessor>) {
         let mut process_packs: Vec<ProcessPack> = Vec::new();
         LiveGraph::drop_unused_live_graphs(&mut tex_pro.live_graphs.write().unwrap());
 
+        let search_paths = tex_pro.search_paths().unwrap();
         for live_graph in tex_pro.live_graph().read().unwrap().iter() {
             let mut live_graph_write = live_graph.write().unwrap();
+            if let Err(e) = live_graph_write.poll_file_changes(&search_paths) {
+                println!("Unexpected error: {}", e);
+            }
+            live_graph_write.propagate_failures();
 
             let closest_processable = {
                 // Get requested nodes
@@ -140,6 +160,7 @@ pub(crate) fn process_loop(tex_pro: Arc<TextureProcessor>) {
                                 NodeState::Processing
                                     | NodeState::ProcessingDirty
                                     | NodeState::Clean
+                                    | NodeState::Failed
                             )
                         })
                         .map(|(node_id, _)| *node_id)
@@ -245,6 +266,7 @@ pub(crate) fn process_loop(tex_pro: Arc<TextureProcessor>) {
             }
 
             let node = live_graph.node_graph.node(node_id).unwrap();
+            let gray_conversion = live_graph.node_graph.gray_conversion;
 
             let embedded_node_datas: Vec<Arc<EmbeddedSlotData>> = live_graph
                 .embedded_slot_datas()
@@ -292,6 +314,7 @@ pub(crate) fn process_loop(tex_pro: Arc<TextureProcessor>) {
                     &embedded_node_datas,
                     &input_node_datas,
                     &edges,
+                    gray_conversion,
                     tex_pro,
                 );
 
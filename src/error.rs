@@ -1,4 +1,4 @@
-use std::{error::Error, fmt, io, result};
+use std::{error::Error, fmt, io, path::PathBuf, result};
 
 pub type Result<T> = result::Result<T, TexProError>;
 
@@ -24,6 +24,12 @@ pub enum TexProError {
     Io(io::Error),
     // RwLockWriteGuard(),
     InvalidName,
+    UnsupportedExport,
+    ReadImage(PathBuf, Box<TexProError>),
+    NodeFailed,
+    InvalidBundle,
+    InvalidExpression(String),
+    UnknownNodeType(String),
 }
 
 impl PartialEq for TexProError {
@@ -59,6 +65,20 @@ impl fmt::Display for TexProError {
             Self::InvalidName => f.write_str(
                 "Invalid name, can only contain lowercase letters, numbers and underscores",
             ),
+            Self::UnsupportedExport => {
+                f.write_str("The export options are not supported by the export format")
+            }
+            Self::ReadImage(ref path, ref cause) => {
+                write!(f, "Could not read image \"{}\": {}", path.display(), cause)
+            }
+            Self::NodeFailed => f.write_str(
+                "The node or one of its inputs failed to process, see `LiveGraph::node_error()`",
+            ),
+            Self::InvalidBundle => f.write_str("The file is not a valid bundle"),
+            Self::InvalidExpression(ref message) => write!(f, "Invalid expression: {}", message),
+            Self::UnknownNodeType(ref type_id) => {
+                write!(f, "No node type with the id \"{}\" is registered", type_id)
+            }
         }
     }
 }
@@ -0,0 +1,601 @@
+//! Block compression encoders for the BC1, BC3, BC4, BC5 and BC7 formats.
+//!
+//! All formats compress blocks of 4x4 pixels. BC7 blocks are always encoded in mode 6, which
+//! stores one pair of RGBA endpoints per block.
+
+use crate::{
+    error::{Result, TexProError},
+    slot_data::Size,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BcFormat {
+    /// RGB, 4 bits per pixel.
+    Bc1,
+    /// RGBA, 8 bits per pixel.
+    Bc3,
+    /// One channel, 4 bits per pixel.
+    Bc4,
+    /// Two channels, 8 bits per pixel.
+    Bc5,
+    /// RGBA, 8 bits per pixel, higher quality than BC1 and BC3.
+    Bc7,
+}
+
+impl Default for BcFormat {
+    fn default() -> Self {
+        Self::Bc7
+    }
+}
+
+impl fmt::Display for BcFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bc1 => write!(f, "BC1"),
+            Self::Bc3 => write!(f, "BC3"),
+            Self::Bc4 => write!(f, "BC4"),
+            Self::Bc5 => write!(f, "BC5"),
+            Self::Bc7 => write!(f, "BC7"),
+        }
+    }
+}
+
+impl BcFormat {
+    /// The number of bytes in one compressed 4x4 block.
+    pub fn block_bytes(self) -> usize {
+        match self {
+            Self::Bc1 | Self::Bc4 => 8,
+            Self::Bc3 | Self::Bc5 | Self::Bc7 => 16,
+        }
+    }
+
+    /// If the format can store sRGB encoded colors.
+    pub fn supports_srgb(self) -> bool {
+        matches!(self, Self::Bc1 | Self::Bc3 | Self::Bc7)
+    }
+}
+
+type Rgba8 = [u8; 4];
+
+/// Compresses interleaved 8 bit RGBA pixels. Images with sizes that are not a multiple of 4 are
+/// padded by repeating the edge pixels.
+pub(crate) fn compress(pixels: &[u8], size: Size, format: BcFormat) -> Result<Vec<u8>> {
+    if pixels.len() != size.pixel_count() * 4 {
+        return Err(TexProError::InvalidBufferCount);
+    }
+
+    let blocks_x = size.width.div_ceil(4);
+    let blocks_y = size.height.div_ceil(4);
+    let mut output = Vec::with_capacity((blocks_x * blocks_y) as usize * format.block_bytes());
+
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let mut block = [[0; 4]; 16];
+
+            for (i, pixel) in block.iter_mut().enumerate() {
+                let x = (block_x * 4 + i as u32 % 4).min(size.width - 1);
+                let y = (block_y * 4 + i as u32 / 4).min(size.height - 1);
+                let index = (y * size.width + x) as usize * 4;
+                pixel.copy_from_slice(&pixels[index..index + 4]);
+            }
+
+            match format {
+                BcFormat::Bc1 => output.extend_from_slice(&encode_bc1(&block)),
+                BcFormat::Bc3 => {
+                    output.extend_from_slice(&encode_bc4(&channel(&block, 3)));
+                    output.extend_from_slice(&encode_bc1(&block));
+                }
+                BcFormat::Bc4 => output.extend_from_slice(&encode_bc4(&channel(&block, 0))),
+                BcFormat::Bc5 => {
+                    output.extend_from_slice(&encode_bc4(&channel(&block, 0)));
+                    output.extend_from_slice(&encode_bc4(&channel(&block, 1)));
+                }
+                BcFormat::Bc7 => output.extend_from_slice(&encode_bc7(&block)),
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+fn channel(block: &[Rgba8; 16], channel: usize) -> [u8; 16] {
+    let mut output = [0; 16];
+    for (value, pixel) in output.iter_mut().zip(block.iter()) {
+        *value = pixel[channel];
+    }
+    output
+}
+
+/// Finds the two points at the extremes of the principal axis of the given points.
+fn principal_endpoints<const N: usize>(points: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    let count = points.len() as f32;
+    let mut mean = [0.0; N];
+    for point in points {
+        for c in 0..N {
+            mean[c] += point[c] / count;
+        }
+    }
+
+    let mut covariance = [[0.0; N]; N];
+    for point in points {
+        for a in 0..N {
+            for b in 0..N {
+                covariance[a][b] += (point[a] - mean[a]) * (point[b] - mean[b]);
+            }
+        }
+    }
+
+    // Power iteration to find the eigenvector with the largest eigenvalue, starting from the
+    // channel with the largest variance.
+    let start = (0..N)
+        .max_by(|a, b| covariance[*a][*a].total_cmp(&covariance[*b][*b]))
+        .unwrap_or(0);
+    let mut axis = [0.0; N];
+    axis[start] = 1.0;
+    for _ in 0..8 {
+        let mut next = [0.0; N];
+        for a in 0..N {
+            for b in 0..N {
+                next[a] += covariance[a][b] * axis[b];
+            }
+        }
+
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < f32::EPSILON {
+            return (mean, mean);
+        }
+        for c in 0..N {
+            axis[c] = next[c] / length;
+        }
+    }
+
+    let (mut min, mut max) = (f32::MAX, f32::MIN);
+    for point in points {
+        let t = (0..N).map(|c| (point[c] - mean[c]) * axis[c]).sum::<f32>();
+        min = min.min(t);
+        max = max.max(t);
+    }
+
+    let mut low = [0.0; N];
+    let mut high = [0.0; N];
+    for c in 0..N {
+        low[c] = (mean[c] + axis[c] * min).clamp(0.0, 255.0);
+        high[c] = (mean[c] + axis[c] * max).clamp(0.0, 255.0);
+    }
+
+    (high, low)
+}
+
+fn distance_squared(a: &[u8], b: &[u8]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| {
+            let difference = *a as i32 - *b as i32;
+            (difference * difference) as u32
+        })
+        .sum()
+}
+
+fn nearest_index(value: &[u8], palette: &[&[u8]]) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance_squared(value, entry))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn pack_565(color: [f32; 3]) -> u16 {
+    let r = (color[0] / 255. * 31.).round() as u16;
+    let g = (color[1] / 255. * 63.).round() as u16;
+    let b = (color[2] / 255. * 31.).round() as u16;
+    (r << 11) | (g << 5) | b
+}
+
+pub(crate) fn unpack_565(color: u16) -> [u8; 3] {
+    let r = ((color >> 11) & 31) as u8;
+    let g = ((color >> 5) & 63) as u8;
+    let b = (color & 31) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// The four colors of a BC1 block in four color mode.
+pub(crate) fn bc1_palette(color_0: u16, color_1: u16) -> [[u8; 3]; 4] {
+    let (c0, c1) = (unpack_565(color_0), unpack_565(color_1));
+    let mut palette = [c0, c1, [0; 3], [0; 3]];
+
+    for c in 0..3 {
+        palette[2][c] = ((2 * c0[c] as u16 + c1[c] as u16 + 1) / 3) as u8;
+        palette[3][c] = ((c0[c] as u16 + 2 * c1[c] as u16 + 1) / 3) as u8;
+    }
+
+    palette
+}
+
+/// Encodes the color of a block. The block is always in four color mode, so it can also be used
+/// as the color part of BC3.
+fn encode_bc1(block: &[Rgba8; 16]) -> [u8; 8] {
+    let points = block
+        .iter()
+        .map(|pixel| [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32])
+        .collect::<Vec<[f32; 3]>>();
+    let (high, low) = principal_endpoints(&points);
+
+    let (mut color_0, mut color_1) = (pack_565(high), pack_565(low));
+    if color_0 < color_1 {
+        std::mem::swap(&mut color_0, &mut color_1);
+    }
+
+    let mut indices: u32 = 0;
+    if color_0 != color_1 {
+        let palette = bc1_palette(color_0, color_1);
+        let palette = palette.iter().map(|c| &c[..]).collect::<Vec<&[u8]>>();
+
+        for (i, pixel) in block.iter().enumerate() {
+            indices |= (nearest_index(&pixel[0..3], &palette) as u32) << (i * 2);
+        }
+    }
+
+    let mut output = [0; 8];
+    output[0..2].copy_from_slice(&color_0.to_le_bytes());
+    output[2..4].copy_from_slice(&color_1.to_le_bytes());
+    output[4..8].copy_from_slice(&indices.to_le_bytes());
+    output
+}
+
+/// The eight values of a BC4 block where the first endpoint is larger than the second.
+pub(crate) fn bc4_palette(value_0: u8, value_1: u8) -> [u8; 8] {
+    let (v0, v1) = (value_0 as u16, value_1 as u16);
+    let mut palette = [value_0, value_1, 0, 0, 0, 0, 0, 0];
+
+    for (i, value) in palette.iter_mut().enumerate().skip(2) {
+        let i = i as u16;
+        *value = (((8 - i) * v0 + (i - 1) * v1 + 3) / 7) as u8;
+    }
+
+    palette
+}
+
+fn encode_bc4(values: &[u8; 16]) -> [u8; 8] {
+    let max = *values.iter().max().unwrap();
+    let min = *values.iter().min().unwrap();
+
+    let mut indices: u64 = 0;
+    if max != min {
+        let palette = bc4_palette(max, min);
+        let palette = palette
+            .iter()
+            .map(std::slice::from_ref)
+            .collect::<Vec<&[u8]>>();
+
+        for (i, value) in values.iter().enumerate() {
+            indices |= (nearest_index(&[*value], &palette) as u64) << (i * 3);
+        }
+    }
+
+    let mut output = [0; 8];
+    output[0] = max;
+    output[1] = min;
+    output[2..8].copy_from_slice(&indices.to_le_bytes()[0..6]);
+    output
+}
+
+pub(crate) const BC7_WEIGHTS_4: [u16; 16] =
+    [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Quantizes an endpoint to 7 bits per channel plus a shared p-bit, picking the p-bit that gives
+/// the smallest error.
+fn quantize_bc7_mode_6_endpoint(endpoint: [f32; 4]) -> ([u8; 4], u8) {
+    let mut best = ([0; 4], 0, f32::MAX);
+
+    for p_bit in 0..2 {
+        let mut quantized = [0; 4];
+        let mut error = 0.0;
+
+        for c in 0..4 {
+            let value = ((endpoint[c] - p_bit as f32) / 2.)
+                .round()
+                .clamp(0.0, 127.0) as u8;
+            let difference = (((value << 1) | p_bit) as f32) - endpoint[c];
+            quantized[c] = value;
+            error += difference * difference;
+        }
+
+        if error < best.2 {
+            best = (quantized, p_bit, error);
+        }
+    }
+
+    (best.0, best.1)
+}
+
+pub(crate) fn bc7_interpolate(e0: u8, e1: u8, weight: u16) -> u8 {
+    (((64 - weight) * e0 as u16 + weight * e1 as u16 + 32) >> 6) as u8
+}
+
+fn encode_bc7(block: &[Rgba8; 16]) -> [u8; 16] {
+    let points = block
+        .iter()
+        .map(|pixel| {
+            [
+                pixel[0] as f32,
+                pixel[1] as f32,
+                pixel[2] as f32,
+                pixel[3] as f32,
+            ]
+        })
+        .collect::<Vec<[f32; 4]>>();
+    let (high, low) = principal_endpoints(&points);
+
+    let mut endpoints = [
+        quantize_bc7_mode_6_endpoint(high),
+        quantize_bc7_mode_6_endpoint(low),
+    ];
+
+    let expand = |(endpoint, p_bit): ([u8; 4], u8)| {
+        let mut output = [0; 4];
+        for c in 0..4 {
+            output[c] = (endpoint[c] << 1) | p_bit;
+        }
+        output
+    };
+    let (e0, e1) = (expand(endpoints[0]), expand(endpoints[1]));
+
+    let palette = BC7_WEIGHTS_4
+        .iter()
+        .map(|weight| {
+            let mut color = [0; 4];
+            for c in 0..4 {
+                color[c] = bc7_interpolate(e0[c], e1[c], *weight);
+            }
+            color
+        })
+        .collect::<Vec<Rgba8>>();
+    let palette = palette.iter().map(|c| &c[..]).collect::<Vec<&[u8]>>();
+
+    let mut indices = block
+        .iter()
+        .map(|pixel| nearest_index(pixel, &palette) as u8)
+        .collect::<Vec<u8>>();
+
+    // The most significant bit of the first index is implicitly 0, so the endpoints are swapped
+    // if the first index needs it.
+    if indices[0] >= 8 {
+        endpoints.swap(0, 1);
+        for index in indices.iter_mut() {
+            *index = 15 - *index;
+        }
+    }
+
+    let mut bits: u128 = 1 << 6;
+    let mut position = 7;
+    let mut write = |value: u128, count: u32| {
+        bits |= value << position;
+        position += count;
+    };
+
+    for c in 0..4 {
+        write(endpoints[0].0[c] as u128, 7);
+        write(endpoints[1].0[c] as u128, 7);
+    }
+    write(endpoints[0].1 as u128, 1);
+    write(endpoints[1].1 as u128, 1);
+    write(indices[0] as u128, 3);
+    for index in &indices[1..] {
+        write(*index as u128, 4);
+    }
+
+    bits.to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{
+        codecs::dxt::{DxtDecoder, DxtVariant},
+        ImageDecoder,
+    };
+
+    /// Decodes one block with the DXT decoder of the `image` crate, so the encoders are checked
+    /// against a decoder that doesn't share any code with them. DXT1 is BC1 and DXT5 is BC3.
+    fn decode_dxt(block: &[u8], variant: DxtVariant) -> Vec<u8> {
+        let decoder = DxtDecoder::new(block, 4, 4, variant).unwrap();
+        let mut output = vec![0; decoder.total_bytes() as usize];
+        decoder.read_image(&mut output).unwrap();
+        output
+    }
+
+    fn decode_bc1(block: &[u8]) -> Vec<[u8; 3]> {
+        decode_dxt(block, DxtVariant::DXT1)
+            .chunks_exact(3)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect()
+    }
+
+    /// BC4 blocks are stored like the alpha of BC3 blocks, so they are decoded as the alpha of a
+    /// BC3 block.
+    fn decode_bc4(block: &[u8]) -> Vec<u8> {
+        let mut bc3_block = [0; 16];
+        bc3_block[0..8].copy_from_slice(block);
+
+        decode_dxt(&bc3_block, DxtVariant::DXT5)
+            .chunks_exact(4)
+            .map(|pixel| pixel[3])
+            .collect()
+    }
+
+    /// A BC7 mode 6 block with the endpoints 0 and 255 in all channels, where pixel `i` uses
+    /// index `i`.
+    const BC7_MODE_6_RAMP: [u8; 16] = [
+        0x40, 0xc0, 0x1f, 0xf0, 0x07, 0xfc, 0x01, 0x7f, 0x11, 0x32, 0x54, 0x76, 0x98, 0xba, 0xdc,
+        0xfe,
+    ];
+    /// The values of `BC7_MODE_6_RAMP` from the BC7 specification.
+    const BC7_MODE_6_RAMP_VALUES: [u8; 16] = [
+        0, 16, 36, 52, 68, 84, 104, 120, 135, 151, 171, 187, 203, 219, 239, 255,
+    ];
+
+    /// Decodes a BC7 mode 6 block following the specification, without using the encoder's
+    /// helpers. It's checked against `BC7_MODE_6_RAMP`.
+    fn decode_bc7_mode_6(block: &[u8]) -> [Rgba8; 16] {
+        const WEIGHTS: [u16; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(block);
+        let bits = u128::from_le_bytes(bytes);
+        assert_eq!(bits & 0x7f, 1 << 6, "Only mode 6 is used");
+
+        let read = |position: u32, count: u32| ((bits >> position) & ((1 << count) - 1)) as u16;
+        let p_bits = [read(63, 1), read(64, 1)];
+        let mut endpoints = [[0; 4]; 2];
+        for c in 0..4 {
+            for (e, endpoint) in endpoints.iter_mut().enumerate() {
+                endpoint[c] = (read(7 + (c as u32 * 2 + e as u32) * 7, 7) << 1) | p_bits[e];
+            }
+        }
+
+        let mut output = [[0; 4]; 16];
+        for (i, pixel) in output.iter_mut().enumerate() {
+            let index = if i == 0 {
+                read(65, 3)
+            } else {
+                read(68 + (i as u32 - 1) * 4, 4)
+            };
+            let weight = WEIGHTS[index as usize];
+
+            for c in 0..4 {
+                pixel[c] =
+                    (((64 - weight) * endpoints[0][c] + weight * endpoints[1][c] + 32) >> 6) as u8;
+            }
+        }
+        output
+    }
+
+    fn gradient_block() -> [Rgba8; 16] {
+        let mut block = [[0; 4]; 16];
+        for (i, pixel) in block.iter_mut().enumerate() {
+            let i = i as u8;
+            *pixel = [i * 16, 255 - i * 16, 128, 255 - i * 8];
+        }
+        block
+    }
+
+    fn max_error(a: &[u8], b: &[u8]) -> u8 {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| (*a as i16 - *b as i16).abs() as u8)
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn bc1_solid() {
+        let block = [[255, 0, 0, 255]; 16];
+        let encoded = encode_bc1(&block);
+
+        // Pure red is exactly representable in 5:6:5.
+        assert_eq!(&encoded[0..4], &[0x00, 0xf8, 0x00, 0xf8]);
+        for pixel in decode_bc1(&encoded).iter() {
+            assert_eq!(pixel, &[255, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn bc1_gradient() {
+        let block = gradient_block();
+        let decoded = decode_bc1(&encode_bc1(&block));
+
+        // Sixteen steps are spread over four colors, so each pixel can be off by up to half the
+        // distance between two colors.
+        for (pixel, decoded) in block.iter().zip(decoded.iter()) {
+            assert!(max_error(&pixel[0..3], decoded) <= 44);
+        }
+    }
+
+    #[test]
+    fn bc3_gradient() {
+        let block = gradient_block();
+        let compressed = compress(&block.concat(), Size::new(4, 4), BcFormat::Bc3).unwrap();
+        let decoded = decode_dxt(&compressed, DxtVariant::DXT5);
+
+        for (pixel, decoded) in block.iter().zip(decoded.chunks_exact(4)) {
+            assert!(max_error(&pixel[0..3], &decoded[0..3]) <= 44);
+            // Alpha only has half the range of the other channels, and gets eight values.
+            assert!(max_error(&pixel[3..4], &decoded[3..4]) <= 10);
+        }
+    }
+
+    #[test]
+    fn bc4_gradient() {
+        let values = channel(&gradient_block(), 0);
+        let decoded = decode_bc4(&encode_bc4(&values));
+
+        // The reference decoder rounds the interpolated values down, so they can be one lower.
+        assert!(max_error(&values, &decoded) <= 20);
+        assert_eq!(decoded[0], 0);
+        assert_eq!(decoded[15], 240);
+    }
+
+    #[test]
+    fn bc4_solid() {
+        let values = [77; 16];
+        assert_eq!(decode_bc4(&encode_bc4(&values)), values);
+    }
+
+    #[test]
+    fn bc7_reference_block() {
+        for (pixel, value) in decode_bc7_mode_6(&BC7_MODE_6_RAMP)
+            .iter()
+            .zip(BC7_MODE_6_RAMP_VALUES.iter())
+        {
+            assert_eq!(pixel, &[*value; 4]);
+        }
+
+        // The ramp is exactly representable, so the encoder should find the same block.
+        let block = BC7_MODE_6_RAMP_VALUES.map(|value| [value; 4]);
+        assert_eq!(encode_bc7(&block), BC7_MODE_6_RAMP);
+    }
+
+    #[test]
+    fn bc7_gradient() {
+        let block = gradient_block();
+        let decoded = decode_bc7_mode_6(&encode_bc7(&block));
+
+        for (pixel, decoded) in block.iter().zip(decoded.iter()) {
+            assert!(max_error(pixel, decoded) <= 10, "{:?} {:?}", pixel, decoded);
+        }
+    }
+
+    #[test]
+    fn bc7_solid() {
+        let block = [[10, 200, 30, 128]; 16];
+        let decoded = decode_bc7_mode_6(&encode_bc7(&block));
+
+        for (pixel, decoded) in block.iter().zip(decoded.iter()) {
+            assert!(max_error(pixel, decoded) <= 1);
+        }
+    }
+
+    #[test]
+    fn compress_padding() {
+        let size = Size::new(5, 3);
+        let pixels = vec![255; size.pixel_count() * 4];
+
+        for format in [
+            BcFormat::Bc1,
+            BcFormat::Bc3,
+            BcFormat::Bc4,
+            BcFormat::Bc5,
+            BcFormat::Bc7,
+        ] {
+            let compressed = compress(&pixels, size, format).unwrap();
+            assert_eq!(compressed.len(), 2 * format.block_bytes());
+        }
+    }
+}
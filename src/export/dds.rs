@@ -0,0 +1,221 @@
+//! Writes block compressed DDS files. The files always use the DX10 header extension, so the
+//! format is stored as a DXGI format.
+
+use super::{
+    bcn::{self, BcFormat},
+    channel_values, f32_to_u8, ChannelLayout, ColorEncoding, TextureUsage,
+};
+use crate::{
+    error::{Result, TexProError},
+    mipmap::{self, MipOptions},
+    node::grayscale::GrayConversion,
+    slot_image::SlotImage,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDSD_LINEARSIZE: u32 = 0x8_0000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x40_0000;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+
+/// Describes how a `SlotImage` should be written to a DDS file.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct DdsOptions {
+    pub format: BcFormat,
+    pub encoding: ColorEncoding,
+    /// Writes a full mip chain down to 1x1 pixels.
+    pub mipmaps: bool,
+    pub mip_options: MipOptions,
+    /// How RGBA images are made gray for the single channel formats.
+    #[serde(default)]
+    pub gray_conversion: GrayConversion,
+}
+
+impl Default for DdsOptions {
+    fn default() -> Self {
+        Self::from_usage(TextureUsage::default())
+    }
+}
+
+impl From<TextureUsage> for DdsOptions {
+    fn from(usage: TextureUsage) -> Self {
+        Self::from_usage(usage)
+    }
+}
+
+impl DdsOptions {
+    pub fn new(format: BcFormat) -> Self {
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+
+    /// Picks the format, color encoding and mip filtering that fits what the texture is used for:
+    /// BC7 for color and data, BC5 for normal maps and BC4 for masks.
+    pub fn from_usage(usage: TextureUsage) -> Self {
+        let format = match usage {
+            TextureUsage::Color | TextureUsage::Data => BcFormat::Bc7,
+            TextureUsage::Normal => BcFormat::Bc5,
+            TextureUsage::Mask => BcFormat::Bc4,
+        };
+
+        Self {
+            format,
+            encoding: usage.color_encoding(),
+            mipmaps: true,
+            mip_options: MipOptions::from_usage(usage),
+            gray_conversion: GrayConversion::default(),
+        }
+    }
+
+    pub fn format(mut self, format: BcFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: ColorEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn mip_options(mut self, mip_options: MipOptions) -> Self {
+        self.mip_options = mip_options;
+        self
+    }
+
+    pub fn gray_conversion(mut self, gray_conversion: GrayConversion) -> Self {
+        self.gray_conversion = gray_conversion;
+        self
+    }
+
+    fn dxgi_format(&self) -> Result<u32> {
+        let srgb = match self.encoding {
+            ColorEncoding::Linear => false,
+            ColorEncoding::Srgb if self.format.supports_srgb() => true,
+            ColorEncoding::Srgb => return Err(TexProError::UnsupportedExport),
+        };
+
+        Ok(match self.format {
+            BcFormat::Bc1 => 71,
+            BcFormat::Bc3 => 77,
+            BcFormat::Bc4 => 80,
+            BcFormat::Bc5 => 83,
+            BcFormat::Bc7 => 98,
+        } + srgb as u32)
+    }
+}
+
+/// Returns the interleaved 8 bit RGBA pixels that get compressed. BC4 compresses the grayscale
+/// value of the image.
+fn block_input(slot_image: &SlotImage, options: &DdsOptions) -> Result<Vec<u8>> {
+    let values = if options.format == BcFormat::Bc4 {
+        channel_values(
+            slot_image,
+            ChannelLayout::Gray,
+            options.encoding,
+            options.gray_conversion,
+        )?
+        .into_iter()
+        .flat_map(|value| [value, value, value, 1.0])
+        .collect()
+    } else {
+        channel_values(
+            slot_image,
+            ChannelLayout::Rgba,
+            options.encoding,
+            options.gray_conversion,
+        )?
+    };
+
+    Ok(values
+        .into_iter()
+        .map(|value| f32_to_u8(value, 0.5))
+        .collect())
+}
+
+fn write_u32s<W: Write>(writer: &mut W, values: &[u32]) -> Result<()> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Encodes a `SlotImage` as a DDS file into the given writer.
+pub fn encode<W: Write>(
+    slot_image: &SlotImage,
+    writer: &mut W,
+    options: &DdsOptions,
+) -> Result<()> {
+    let dxgi_format = options.dxgi_format()?;
+    let size = slot_image.size()?;
+
+    let levels = if options.mipmaps {
+        mipmap::mip_chain(slot_image, &options.mip_options)?
+    } else {
+        vec![slot_image.clone()]
+    };
+
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_LINEARSIZE;
+    let mut caps = DDSCAPS_TEXTURE;
+    if options.mipmaps {
+        flags |= DDSD_MIPMAPCOUNT;
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+    let linear_size =
+        (size.width.div_ceil(4)) * (size.height.div_ceil(4)) * options.format.block_bytes() as u32;
+
+    writer.write_all(b"DDS ")?;
+    write_u32s(
+        writer,
+        &[124, flags, size.height, size.width, linear_size, 0],
+    )?;
+    write_u32s(writer, &[levels.len() as u32])?;
+    write_u32s(writer, &[0; 11])?;
+
+    // Pixel format, pointing to the DX10 header.
+    write_u32s(writer, &[32, DDPF_FOURCC])?;
+    writer.write_all(b"DX10")?;
+    write_u32s(writer, &[0; 5])?;
+
+    write_u32s(writer, &[caps, 0, 0, 0, 0])?;
+
+    // DX10 header.
+    write_u32s(
+        writer,
+        &[dxgi_format, D3D10_RESOURCE_DIMENSION_TEXTURE2D, 0, 1, 0],
+    )?;
+
+    for level in levels {
+        let pixels = block_input(&level, options)?;
+        writer.write_all(&bcn::compress(&pixels, level.size()?, options.format)?)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a `SlotImage` to a DDS file.
+pub fn export<P: AsRef<Path>>(slot_image: &SlotImage, path: P, options: &DdsOptions) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    encode(slot_image, &mut writer, options)?;
+    writer.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,409 @@
+//! Writes uncompressed KTX2 files, with a basic data format descriptor that describes the
+//! channels and color encoding.
+
+use super::{channel_values, f32_to_u8, ChannelLayout, ColorEncoding, TextureUsage};
+use crate::{
+    error::{Result, TexProError},
+    mipmap::{self, MipOptions},
+    node::grayscale::GrayConversion,
+    slot_image::SlotImage,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+const IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+const HEADER_LENGTH: usize = 80;
+const LEVEL_INDEX_LENGTH: usize = 24;
+
+const KHR_DF_MODEL_RGBSDA: u32 = 1;
+const KHR_DF_PRIMARIES_BT709: u32 = 1;
+const KHR_DF_TRANSFER_LINEAR: u32 = 1;
+const KHR_DF_TRANSFER_SRGB: u32 = 2;
+const KHR_DF_CHANNEL_ALPHA: u32 = 15;
+const KHR_DF_SAMPLE_DATATYPE_LINEAR: u32 = 0x10;
+const KHR_DF_SAMPLE_DATATYPE_SIGNED: u32 = 0x40;
+const KHR_DF_SAMPLE_DATATYPE_FLOAT: u32 = 0x80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Ktx2Format {
+    R8,
+    Rg8,
+    Rgba8,
+    Rgba16Float,
+}
+
+impl Default for Ktx2Format {
+    fn default() -> Self {
+        Self::Rgba8
+    }
+}
+
+impl fmt::Display for Ktx2Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::R8 => write!(f, "R8"),
+            Self::Rg8 => write!(f, "RG8"),
+            Self::Rgba8 => write!(f, "RGBA8"),
+            Self::Rgba16Float => write!(f, "RGBA16F"),
+        }
+    }
+}
+
+impl Ktx2Format {
+    pub fn channel_count(self) -> usize {
+        match self {
+            Self::R8 => 1,
+            Self::Rg8 => 2,
+            Self::Rgba8 | Self::Rgba16Float => 4,
+        }
+    }
+
+    /// The number of bytes in each channel.
+    pub fn type_size(self) -> usize {
+        match self {
+            Self::R8 | Self::Rg8 | Self::Rgba8 => 1,
+            Self::Rgba16Float => 2,
+        }
+    }
+
+    fn vk_format(self, encoding: ColorEncoding) -> Result<u32> {
+        Ok(match (self, encoding) {
+            (Self::R8, ColorEncoding::Linear) => 9,
+            (Self::R8, ColorEncoding::Srgb) => 15,
+            (Self::Rg8, ColorEncoding::Linear) => 16,
+            (Self::Rg8, ColorEncoding::Srgb) => 22,
+            (Self::Rgba8, ColorEncoding::Linear) => 37,
+            (Self::Rgba8, ColorEncoding::Srgb) => 43,
+            (Self::Rgba16Float, ColorEncoding::Linear) => 97,
+            (Self::Rgba16Float, ColorEncoding::Srgb) => return Err(TexProError::UnsupportedExport),
+        })
+    }
+}
+
+/// Describes how a `SlotImage` should be written to a KTX2 file.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Ktx2Options {
+    pub format: Ktx2Format,
+    pub encoding: ColorEncoding,
+    /// Writes a full mip chain down to 1x1 pixels.
+    pub mipmaps: bool,
+    pub mip_options: MipOptions,
+    /// How RGBA images are made gray for the single channel formats.
+    #[serde(default)]
+    pub gray_conversion: GrayConversion,
+}
+
+impl Default for Ktx2Options {
+    fn default() -> Self {
+        Self::from_usage(TextureUsage::default())
+    }
+}
+
+impl From<TextureUsage> for Ktx2Options {
+    fn from(usage: TextureUsage) -> Self {
+        Self::from_usage(usage)
+    }
+}
+
+impl Ktx2Options {
+    pub fn new(format: Ktx2Format) -> Self {
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+
+    /// Picks the format, color encoding and mip filtering that fits what the texture is used for:
+    /// RGBA8 for color and data, RG8 for normal maps and R8 for masks.
+    pub fn from_usage(usage: TextureUsage) -> Self {
+        let format = match usage {
+            TextureUsage::Color | TextureUsage::Data => Ktx2Format::Rgba8,
+            TextureUsage::Normal => Ktx2Format::Rg8,
+            TextureUsage::Mask => Ktx2Format::R8,
+        };
+
+        Self {
+            format,
+            encoding: usage.color_encoding(),
+            mipmaps: true,
+            mip_options: MipOptions::from_usage(usage),
+            gray_conversion: GrayConversion::default(),
+        }
+    }
+
+    pub fn format(mut self, format: Ktx2Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: ColorEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn mip_options(mut self, mip_options: MipOptions) -> Self {
+        self.mip_options = mip_options;
+        self
+    }
+
+    pub fn gray_conversion(mut self, gray_conversion: GrayConversion) -> Self {
+        self.gray_conversion = gray_conversion;
+        self
+    }
+}
+
+/// Converts to a half precision float, rounding to the nearest even value.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    let (half, mantissa, shift) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+
+        // Subnormal, the implicit leading bit becomes explicit.
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        (mantissa >> shift, mantissa, shift)
+    } else {
+        (((exponent as u32) << 10) | (mantissa >> 13), mantissa, 13)
+    };
+
+    let round_bit = 1 << (shift - 1);
+    let round_up = mantissa & round_bit != 0
+        && (mantissa & (round_bit - 1) != 0 || mantissa & (round_bit << 1) != 0);
+
+    // A carry out of the mantissa correctly increases the exponent.
+    sign | (half + round_up as u32) as u16
+}
+
+/// Returns the bytes of a mip level in the given format.
+fn level_data(slot_image: &SlotImage, options: &Ktx2Options) -> Result<Vec<u8>> {
+    let channels = match options.format {
+        Ktx2Format::R8 => ChannelLayout::Gray,
+        Ktx2Format::Rg8 | Ktx2Format::Rgba8 | Ktx2Format::Rgba16Float => ChannelLayout::Rgba,
+    };
+    let values = channel_values(
+        slot_image,
+        channels,
+        options.encoding,
+        options.gray_conversion,
+    )?;
+
+    Ok(match options.format {
+        Ktx2Format::R8 | Ktx2Format::Rgba8 => values
+            .into_iter()
+            .map(|value| f32_to_u8(value, 0.5))
+            .collect(),
+        Ktx2Format::Rg8 => values
+            .chunks_exact(4)
+            .flat_map(|pixel| [f32_to_u8(pixel[0], 0.5), f32_to_u8(pixel[1], 0.5)])
+            .collect(),
+        Ktx2Format::Rgba16Float => values
+            .into_iter()
+            .flat_map(|value| f32_to_f16(value).to_le_bytes())
+            .collect(),
+    })
+}
+
+/// Builds the data format descriptor, a basic descriptor block with one sample per channel.
+fn data_format_descriptor(format: Ktx2Format, encoding: ColorEncoding) -> Vec<u8> {
+    let channel_count = format.channel_count() as u32;
+    let bits = format.type_size() as u32 * 8;
+    let block_size = 24 + 16 * channel_count;
+    let transfer = match encoding {
+        ColorEncoding::Linear => KHR_DF_TRANSFER_LINEAR,
+        ColorEncoding::Srgb => KHR_DF_TRANSFER_SRGB,
+    };
+
+    let mut words = vec![
+        4 + block_size,
+        0,
+        2 | (block_size << 16),
+        KHR_DF_MODEL_RGBSDA | (KHR_DF_PRIMARIES_BT709 << 8) | (transfer << 16),
+        0,
+        channel_count * bits / 8,
+        0,
+    ];
+
+    for channel in 0..channel_count {
+        let (channel_id, qualifiers) = if channel == 3 {
+            let qualifiers = match encoding {
+                ColorEncoding::Linear => 0,
+                ColorEncoding::Srgb => KHR_DF_SAMPLE_DATATYPE_LINEAR,
+            };
+            (KHR_DF_CHANNEL_ALPHA, qualifiers)
+        } else {
+            (channel, 0)
+        };
+
+        let (qualifiers, lower, upper) = if format == Ktx2Format::Rgba16Float {
+            (
+                qualifiers | KHR_DF_SAMPLE_DATATYPE_FLOAT | KHR_DF_SAMPLE_DATATYPE_SIGNED,
+                (-1.0_f32).to_bits(),
+                1.0_f32.to_bits(),
+            )
+        } else {
+            (qualifiers, 0, (1 << bits) - 1)
+        };
+
+        words.extend_from_slice(&[
+            (channel * bits) | ((bits - 1) << 16) | ((channel_id | qualifiers) << 24),
+            0,
+            lower,
+            upper,
+        ]);
+    }
+
+    words.into_iter().flat_map(u32::to_le_bytes).collect()
+}
+
+/// Builds the key/value data, which only holds the name of the writer.
+fn key_value_data() -> Vec<u8> {
+    let mut key_value = b"KTXwriter\0".to_vec();
+    key_value.extend_from_slice(
+        format!("{} {}\0", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")).as_bytes(),
+    );
+
+    let mut output = (key_value.len() as u32).to_le_bytes().to_vec();
+    output.append(&mut key_value);
+    output.resize(align(output.len(), 4), 0);
+    output
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+/// Encodes a `SlotImage` as a KTX2 file into the given writer.
+pub fn encode<W: Write>(
+    slot_image: &SlotImage,
+    writer: &mut W,
+    options: &Ktx2Options,
+) -> Result<()> {
+    let vk_format = options.format.vk_format(options.encoding)?;
+    let size = slot_image.size()?;
+
+    let levels = if options.mipmaps {
+        mipmap::mip_chain(slot_image, &options.mip_options)?
+    } else {
+        vec![slot_image.clone()]
+    }
+    .iter()
+    .map(|level| level_data(level, options))
+    .collect::<Result<Vec<Vec<u8>>>>()?;
+
+    let dfd = data_format_descriptor(options.format, options.encoding);
+    let kvd = key_value_data();
+    let dfd_offset = HEADER_LENGTH + LEVEL_INDEX_LENGTH * levels.len();
+    let kvd_offset = dfd_offset + dfd.len();
+
+    // Levels are stored from the smallest to the largest, aligned to the least common multiple
+    // of the texel size and 4. The texel sizes are all powers of two.
+    let alignment = (options.format.type_size() * options.format.channel_count()).max(4);
+    let mut level_offsets = vec![0; levels.len()];
+    let mut end = kvd_offset + kvd.len();
+    for (offset, level) in level_offsets.iter_mut().zip(levels.iter()).rev() {
+        *offset = align(end, alignment);
+        end = *offset + level.len();
+    }
+
+    let mut output = IDENTIFIER.to_vec();
+    for value in [
+        vk_format,
+        options.format.type_size() as u32,
+        size.width,
+        size.height,
+        0,
+        0,
+        1,
+        levels.len() as u32,
+        0,
+        dfd_offset as u32,
+        dfd.len() as u32,
+        kvd_offset as u32,
+        kvd.len() as u32,
+    ] {
+        output.extend_from_slice(&value.to_le_bytes());
+    }
+    // There is no supercompression global data.
+    output.extend_from_slice(&[0; 16]);
+
+    for (offset, level) in level_offsets.iter().zip(levels.iter()) {
+        for value in [*offset, level.len(), level.len()] {
+            output.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+    }
+
+    output.extend_from_slice(&dfd);
+    output.extend_from_slice(&kvd);
+    for (offset, level) in level_offsets.iter().zip(levels.iter()).rev() {
+        output.resize(*offset, 0);
+        output.extend_from_slice(level);
+    }
+
+    writer.write_all(&output)?;
+
+    Ok(())
+}
+
+/// Writes a `SlotImage` to a KTX2 file.
+pub fn export<P: AsRef<Path>>(
+    slot_image: &SlotImage,
+    path: P,
+    options: &Ktx2Options,
+) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    encode(slot_image, &mut writer, options)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f16_conversion() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert!(f32_to_f16(f32::NAN) & 0x3ff != 0);
+        // The smallest subnormal.
+        assert_eq!(f32_to_f16(5.960_464_5e-8), 0x0001);
+        // Halfway between 1.0 and the next value rounds to even.
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 / 2048.0), 0x3c02);
+    }
+}
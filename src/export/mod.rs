@@ -0,0 +1,504 @@
+pub mod bcn;
+pub mod dds;
+pub mod ktx2;
+pub mod pack;
+
+use crate::{
+    error::{Result, TexProError},
+    node::grayscale::GrayConversion,
+    slot_data::{Size, SrgbColorSpace},
+    slot_image::SlotImage,
+};
+use ::image::{
+    codecs::{hdr::HdrEncoder, png::PngEncoder},
+    DynamicImage, ImageBuffer, ImageEncoder, ImageFormat, ImageOutputFormat, Rgb,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    path::Path,
+};
+
+/// The number of bits used to store each channel in an exported image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+    Float32,
+}
+
+impl Default for BitDepth {
+    fn default() -> Self {
+        Self::Eight
+    }
+}
+
+impl fmt::Display for BitDepth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Eight => write!(f, "8 bit"),
+            Self::Sixteen => write!(f, "16 bit"),
+            Self::Float32 => write!(f, "32 bit float"),
+        }
+    }
+}
+
+/// Which channels are written to an exported image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ChannelLayout {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+}
+
+impl Default for ChannelLayout {
+    fn default() -> Self {
+        Self::Rgba
+    }
+}
+
+impl fmt::Display for ChannelLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Gray => write!(f, "Gray"),
+            Self::GrayAlpha => write!(f, "Gray Alpha"),
+            Self::Rgb => write!(f, "RGB"),
+            Self::Rgba => write!(f, "RGBA"),
+        }
+    }
+}
+
+impl ChannelLayout {
+    pub fn channel_count(self) -> usize {
+        match self {
+            Self::Gray => 1,
+            Self::GrayAlpha => 2,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+
+    pub fn has_alpha(self) -> bool {
+        matches!(self, Self::GrayAlpha | Self::Rgba)
+    }
+}
+
+/// How color values are encoded in an exported image. Alpha is always stored linearly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ColorEncoding {
+    Linear,
+    Srgb,
+}
+
+impl Default for ColorEncoding {
+    fn default() -> Self {
+        Self::Linear
+    }
+}
+
+impl fmt::Display for ColorEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Linear => write!(f, "Linear"),
+            Self::Srgb => write!(f, "sRGB"),
+        }
+    }
+}
+
+/// What an exported texture is used for, decides how it gets compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TextureUsage {
+    /// Color that is viewed directly, like albedo.
+    Color,
+    /// A tangent space normal map, only the red and green channels are stored.
+    Normal,
+    /// A single channel, like roughness or a mask.
+    Mask,
+    /// Non-color data in all four channels.
+    Data,
+}
+
+impl Default for TextureUsage {
+    fn default() -> Self {
+        Self::Color
+    }
+}
+
+impl fmt::Display for TextureUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Color => write!(f, "Color"),
+            Self::Normal => write!(f, "Normal"),
+            Self::Mask => write!(f, "Mask"),
+            Self::Data => write!(f, "Data"),
+        }
+    }
+}
+
+impl TextureUsage {
+    /// The color encoding textures with this usage are normally stored with.
+    pub fn color_encoding(self) -> ColorEncoding {
+        match self {
+            Self::Color => ColorEncoding::Srgb,
+            Self::Normal | Self::Mask | Self::Data => ColorEncoding::Linear,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ExportFormat {
+    Png,
+    Jpeg(u8),
+    Tga,
+    Tiff,
+    Bmp,
+    OpenExr,
+    Hdr,
+}
+
+impl Default for ExportFormat {
+    fn default() -> Self {
+        Self::Png
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Png => write!(f, "PNG"),
+            Self::Jpeg(quality) => write!(f, "JPEG: {}", quality),
+            Self::Tga => write!(f, "TGA"),
+            Self::Tiff => write!(f, "TIFF"),
+            Self::Bmp => write!(f, "BMP"),
+            Self::OpenExr => write!(f, "OpenEXR"),
+            Self::Hdr => write!(f, "Radiance HDR"),
+        }
+    }
+}
+
+impl ExportFormat {
+    /// Guesses the format from the extension of a path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(match ImageFormat::from_path(path)? {
+            ImageFormat::Png => Self::Png,
+            ImageFormat::Jpeg => Self::Jpeg(100),
+            ImageFormat::Tga => Self::Tga,
+            ImageFormat::Tiff => Self::Tiff,
+            ImageFormat::Bmp => Self::Bmp,
+            ImageFormat::OpenExr => Self::OpenExr,
+            ImageFormat::Hdr => Self::Hdr,
+            _ => return Err(TexProError::UnsupportedExport),
+        })
+    }
+
+    /// The highest precision the format can store.
+    pub fn max_bit_depth(self) -> BitDepth {
+        match self {
+            Self::Png | Self::Tiff => BitDepth::Sixteen,
+            Self::OpenExr | Self::Hdr => BitDepth::Float32,
+            Self::Jpeg(_) | Self::Tga | Self::Bmp => BitDepth::Eight,
+        }
+    }
+}
+
+/// Describes how a `SlotImage` should be written to an image file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub bit_depth: BitDepth,
+    pub channels: ChannelLayout,
+    pub encoding: ColorEncoding,
+    /// Applies ordered dithering when reducing to 8 bits, to avoid banding.
+    pub dither: bool,
+    /// How RGBA images are made gray for the gray channel layouts.
+    #[serde(default)]
+    pub gray_conversion: GrayConversion,
+}
+
+impl ExportOptions {
+    pub fn new(format: ExportFormat) -> Self {
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+
+    /// Creates options for the format of the path's extension, at the highest precision the
+    /// format can store.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let format = ExportFormat::from_path(path)?;
+        let channels = if format == ExportFormat::Hdr {
+            ChannelLayout::Rgb
+        } else {
+            ChannelLayout::Rgba
+        };
+
+        Ok(Self::new(format)
+            .bit_depth(format.max_bit_depth())
+            .channels(channels))
+    }
+
+    pub fn format(mut self, format: ExportFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn bit_depth(mut self, bit_depth: BitDepth) -> Self {
+        self.bit_depth = bit_depth;
+        self
+    }
+
+    pub fn channels(mut self, channels: ChannelLayout) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: ColorEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    pub fn gray_conversion(mut self, gray_conversion: GrayConversion) -> Self {
+        self.gray_conversion = gray_conversion;
+        self
+    }
+}
+
+// 4x4 Bayer matrix used for ordered dithering.
+const BAYER_4X4: [[f32; 4]; 4] = [
+    [0., 8., 2., 10.],
+    [12., 4., 14., 6.],
+    [3., 11., 1., 9.],
+    [15., 7., 13., 5.],
+];
+
+#[inline]
+fn dither_threshold(x: u32, y: u32) -> f32 {
+    (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] + 0.5) / 16.
+}
+
+#[inline]
+pub(crate) fn f32_to_u8(value: f32, threshold: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255. + threshold).min(255.) as u8
+}
+
+/// Returns the interleaved values of the channels in the given layout, with the color encoding
+/// applied to all color channels. RGBA images are made gray with `gray_conversion` for the gray
+/// layouts.
+pub(crate) fn channel_values(
+    slot_image: &SlotImage,
+    channels: ChannelLayout,
+    encoding: ColorEncoding,
+    gray_conversion: GrayConversion,
+) -> Result<Vec<f32>> {
+    let is_rgba = slot_image.is_rgba();
+    let encode = |value: f32| match encoding {
+        ColorEncoding::Linear => value,
+        ColorEncoding::Srgb => value.linear_to_srgb(),
+    };
+
+    Ok(slot_image
+        .to_f32()?
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let gray = if is_rgba {
+                gray_conversion.gray([pixel[0], pixel[1], pixel[2]])
+            } else {
+                pixel[0]
+            };
+
+            match channels {
+                ChannelLayout::Gray => vec![encode(gray)],
+                ChannelLayout::GrayAlpha => vec![encode(gray), pixel[3]],
+                ChannelLayout::Rgb => vec![encode(pixel[0]), encode(pixel[1]), encode(pixel[2])],
+                ChannelLayout::Rgba => vec![
+                    encode(pixel[0]),
+                    encode(pixel[1]),
+                    encode(pixel[2]),
+                    pixel[3],
+                ],
+            }
+        })
+        .collect())
+}
+
+/// Quantizes the values to 8 bits, rounding to the nearest value or with ordered dithering.
+fn to_u8(values: &[f32], size: Size, channel_count: usize, dither: bool) -> Vec<u8> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let threshold = if dither {
+                let pixel = (i / channel_count) as u32;
+                dither_threshold(pixel % size.width, pixel / size.width)
+            } else {
+                0.5
+            };
+
+            f32_to_u8(*value, threshold)
+        })
+        .collect()
+}
+
+/// Builds a `DynamicImage` that matches the given options.
+pub(crate) fn to_dynamic_image(
+    slot_image: &SlotImage,
+    options: &ExportOptions,
+) -> Result<DynamicImage> {
+    let size = slot_image.size()?;
+    let (width, height) = (size.width, size.height);
+    let channels = options.channels;
+    let values = channel_values(
+        slot_image,
+        channels,
+        options.encoding,
+        options.gray_conversion,
+    )?;
+
+    let image = match options.bit_depth {
+        BitDepth::Eight => {
+            let values = to_u8(&values, size, channels.channel_count(), options.dither);
+
+            match channels {
+                ChannelLayout::Gray => {
+                    ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageLuma8)
+                }
+                ChannelLayout::GrayAlpha => {
+                    ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageLumaA8)
+                }
+                ChannelLayout::Rgb => {
+                    ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageRgb8)
+                }
+                ChannelLayout::Rgba => {
+                    ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageRgba8)
+                }
+            }
+        }
+        BitDepth::Sixteen => {
+            let values = values
+                .into_iter()
+                .map(SlotImage::f32_to_u16)
+                .collect::<Vec<u16>>();
+
+            match channels {
+                ChannelLayout::Gray => {
+                    ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageLuma16)
+                }
+                ChannelLayout::GrayAlpha => {
+                    ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageLumaA16)
+                }
+                ChannelLayout::Rgb => {
+                    ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageRgb16)
+                }
+                ChannelLayout::Rgba => {
+                    ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageRgba16)
+                }
+            }
+        }
+        BitDepth::Float32 => match channels {
+            ChannelLayout::Rgb => {
+                ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageRgb32F)
+            }
+            ChannelLayout::Rgba => {
+                ImageBuffer::from_raw(width, height, values).map(DynamicImage::ImageRgba32F)
+            }
+            // There is no floating point grayscale image type to encode from.
+            ChannelLayout::Gray | ChannelLayout::GrayAlpha => {
+                return Err(TexProError::UnsupportedExport)
+            }
+        },
+    };
+
+    image.ok_or(TexProError::InvalidBufferCount)
+}
+
+/// Encodes a `SlotImage` into the given writer.
+pub fn encode<W: Write + Seek>(
+    slot_image: &SlotImage,
+    writer: &mut W,
+    options: &ExportOptions,
+) -> Result<()> {
+    let output_format = match options.format {
+        ExportFormat::Png => return encode_png(slot_image, writer, options),
+        ExportFormat::Jpeg(quality) => ImageOutputFormat::Jpeg(quality),
+        ExportFormat::Tga => ImageOutputFormat::Tga,
+        ExportFormat::Tiff => ImageOutputFormat::Tiff,
+        ExportFormat::Bmp => ImageOutputFormat::Bmp,
+        ExportFormat::OpenExr => ImageOutputFormat::OpenExr,
+        ExportFormat::Hdr => return encode_hdr(slot_image, writer, options),
+    };
+
+    to_dynamic_image(slot_image, options)?.write_to(writer, output_format)?;
+
+    Ok(())
+}
+
+/// `DynamicImage::write_to` in some `image` 0.24 releases writes 16 bit PNG samples in native
+/// endian, so PNGs go through `PngEncoder::write_image`, which converts them to big endian.
+fn encode_png<W: Write>(
+    slot_image: &SlotImage,
+    writer: &mut W,
+    options: &ExportOptions,
+) -> Result<()> {
+    if options.bit_depth == BitDepth::Float32 {
+        return Err(TexProError::UnsupportedExport);
+    }
+
+    let image = to_dynamic_image(slot_image, options)?;
+
+    PngEncoder::new(writer).write_image(
+        image.as_bytes(),
+        image.width(),
+        image.height(),
+        image.color(),
+    )?;
+
+    Ok(())
+}
+
+/// The `image` crate has no generic encoder for Radiance HDR, so it gets encoded separately.
+fn encode_hdr<W: Write>(
+    slot_image: &SlotImage,
+    writer: &mut W,
+    options: &ExportOptions,
+) -> Result<()> {
+    if options.channels != ChannelLayout::Rgb || options.bit_depth != BitDepth::Float32 {
+        return Err(TexProError::UnsupportedExport);
+    }
+
+    let size = slot_image.size()?;
+    let pixels = channel_values(
+        slot_image,
+        options.channels,
+        options.encoding,
+        options.gray_conversion,
+    )?
+    .chunks_exact(3)
+    .map(|pixel| Rgb([pixel[0], pixel[1], pixel[2]]))
+    .collect::<Vec<Rgb<f32>>>();
+
+    HdrEncoder::new(writer).encode(&pixels, size.width as usize, size.height as usize)?;
+
+    Ok(())
+}
+
+/// Writes a `SlotImage` to a file.
+pub fn export<P: AsRef<Path>>(
+    slot_image: &SlotImage,
+    path: P,
+    options: &ExportOptions,
+) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    encode(slot_image, &mut writer, options)?;
+    writer.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,262 @@
+use crate::{
+    error::{Result, TexProError},
+    live_graph::{LiveGraph, NodeState},
+    node::ResizeFilter,
+    node_graph::{NodeId, SlotId},
+    slot_data::{ChannelPixel, Size},
+    slot_image::{Buffer, SlotImage},
+};
+use ::image::imageops;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+/// Describes where the values of one channel in a packed texture come from.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PackedChannel {
+    /// The name of the gray output to read the channel from.
+    pub output: Option<String>,
+    /// Stores `1 - value` instead of the value, for instance to turn roughness into smoothness.
+    pub invert: bool,
+    /// The value of the channel if there is no output with the given name. This value is never
+    /// inverted.
+    pub default: ChannelPixel,
+}
+
+impl PackedChannel {
+    pub fn new(output: &str) -> Self {
+        Self {
+            output: Some(output.into()),
+            invert: false,
+            default: 0.0,
+        }
+    }
+
+    /// A channel that does not read from any output and always has the given value.
+    pub fn constant(value: ChannelPixel) -> Self {
+        Self {
+            output: None,
+            invert: false,
+            default: value,
+        }
+    }
+
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+
+    pub fn default(mut self, default: ChannelPixel) -> Self {
+        self.default = default;
+        self
+    }
+}
+
+/// A channel of a packed texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Red => write!(f, "Red"),
+            Self::Green => write!(f, "Green"),
+            Self::Blue => write!(f, "Blue"),
+            Self::Alpha => write!(f, "Alpha"),
+        }
+    }
+}
+
+/// Common ways of packing gray maps into the channels of a single texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PackPreset {
+    /// Unreal Engine: occlusion, roughness and metallic in red, green and blue.
+    UnrealOrm,
+    /// Metallic, roughness and occlusion in red, green and blue.
+    Mrao,
+    /// Unity HDRP mask map: metallic, occlusion, detail mask and smoothness.
+    UnityMaskMap,
+}
+
+impl fmt::Display for PackPreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnrealOrm => write!(f, "Unreal ORM"),
+            Self::Mrao => write!(f, "MRAO"),
+            Self::UnityMaskMap => write!(f, "Unity Mask Map"),
+        }
+    }
+}
+
+pub const OUTPUT_OCCLUSION: &str = "occlusion";
+pub const OUTPUT_ROUGHNESS: &str = "roughness";
+pub const OUTPUT_METALLIC: &str = "metallic";
+pub const OUTPUT_DETAIL_MASK: &str = "detail_mask";
+
+/// Packs several gray outputs of a `LiveGraph` into the channels of one RGBA image.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ChannelPack {
+    /// The red, green, blue and alpha channels.
+    pub channels: [PackedChannel; 4],
+    pub resize_filter: ResizeFilter,
+}
+
+impl Default for ChannelPack {
+    fn default() -> Self {
+        Self {
+            channels: [
+                PackedChannel::constant(0.0),
+                PackedChannel::constant(0.0),
+                PackedChannel::constant(0.0),
+                PackedChannel::constant(1.0),
+            ],
+            resize_filter: ResizeFilter::default(),
+        }
+    }
+}
+
+impl From<PackPreset> for ChannelPack {
+    fn from(preset: PackPreset) -> Self {
+        let occlusion = PackedChannel::new(OUTPUT_OCCLUSION).default(1.0);
+        let roughness = PackedChannel::new(OUTPUT_ROUGHNESS).default(0.5);
+        let metallic = PackedChannel::new(OUTPUT_METALLIC).default(0.0);
+
+        let channels = match preset {
+            PackPreset::UnrealOrm => [occlusion, roughness, metallic, PackedChannel::constant(1.0)],
+            PackPreset::Mrao => [metallic, roughness, occlusion, PackedChannel::constant(1.0)],
+            PackPreset::UnityMaskMap => [
+                metallic,
+                occlusion,
+                PackedChannel::new(OUTPUT_DETAIL_MASK).default(1.0),
+                roughness.invert(true),
+            ],
+        };
+
+        Self {
+            channels,
+            ..Self::default()
+        }
+    }
+}
+
+impl ChannelPack {
+    /// Creates a custom `ChannelPack` where all channels are constant. Use `channel()` to read
+    /// channels from outputs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the source of a channel.
+    pub fn channel(mut self, channel: Channel, packed_channel: PackedChannel) -> Self {
+        self.channels[channel as usize] = packed_channel;
+        self
+    }
+
+    pub fn resize_filter(mut self, resize_filter: ResizeFilter) -> Self {
+        self.resize_filter = resize_filter;
+        self
+    }
+
+    /// Returns the `NodeId`s of the outputs that are read by this `ChannelPack`.
+    fn output_ids(&self, live_graph: &LiveGraph) -> Vec<Option<NodeId>> {
+        self.channels
+            .iter()
+            .map(|channel| {
+                let name = channel.output.as_ref()?;
+                live_graph
+                    .node_graph
+                    .output_nodes()
+                    .iter()
+                    .find(|node| node.node_type.name() == Some(name))
+                    .map(|node| node.node_id)
+            })
+            .collect()
+    }
+
+    /// Packs the outputs into an RGBA image. All outputs that are read have to be clean.
+    ///
+    /// The channels are resized to the size of the largest output.
+    pub fn pack(&self, live_graph: &LiveGraph) -> Result<SlotImage> {
+        let mut sources: Vec<Option<Buffer>> = Vec::with_capacity(4);
+
+        for node_id in self.output_ids(live_graph) {
+            sources.push(if let Some(node_id) = node_id {
+                if live_graph.node_state(node_id)? != NodeState::Clean {
+                    return Err(TexProError::NodeDirty);
+                }
+
+                let slot_image = live_graph
+                    .slot_data(node_id, SlotId(0))?
+                    .image
+                    .to_gray(live_graph.gray_conversion())?;
+                let buffer = slot_image.bufs()[0].transient_buffer().buffer().clone();
+                Some(buffer)
+            } else {
+                None
+            });
+        }
+
+        let size = sources
+            .iter()
+            .flatten()
+            .map(|buffer| Size::from(buffer.dimensions()))
+            .max_by_key(|size| size.pixel_count())
+            .unwrap_or_else(|| Size::new(1, 1));
+
+        let mut buffers = self
+            .channels
+            .iter()
+            .zip(sources)
+            .map(|(channel, source)| match source {
+                Some(mut buffer) => {
+                    if Size::from(buffer.dimensions()) != size {
+                        buffer = imageops::resize(
+                            &buffer,
+                            size.width,
+                            size.height,
+                            self.resize_filter.into(),
+                        );
+                    }
+                    if channel.invert {
+                        for pixel in buffer.pixels_mut() {
+                            pixel.0[0] = 1.0 - pixel.0[0];
+                        }
+                    }
+                    buffer
+                }
+                None => Buffer::from_raw(
+                    size.width,
+                    size.height,
+                    vec![channel.default; size.pixel_count()],
+                )
+                .unwrap(),
+            })
+            .collect::<Vec<Buffer>>();
+
+        SlotImage::from_buffers_rgba(&mut buffers)
+    }
+
+    /// Waits for all outputs that are read to be clean, then packs them into an RGBA image.
+    pub fn await_pack(&self, live_graph: &Arc<RwLock<LiveGraph>>) -> Result<SlotImage> {
+        loop {
+            let output_ids = self.output_ids(&*live_graph.read()?);
+
+            for node_id in output_ids.into_iter().flatten() {
+                drop(LiveGraph::await_clean_read(live_graph, node_id)?);
+            }
+
+            match self.pack(&*live_graph.read()?) {
+                // An output was dirtied after it was awaited, so try again.
+                Err(TexProError::NodeDirty) => continue,
+                result => return result,
+            }
+        }
+    }
+}
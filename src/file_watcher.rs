@@ -0,0 +1,123 @@
+use crate::node_graph::NodeId;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// What is known about a file the last time it was checked. `None` fields mean the file could
+/// not be read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: Option<u64>,
+    hash: Option<u64>,
+}
+
+impl FileStamp {
+    fn new(path: &Path) -> Self {
+        let metadata = fs::metadata(path).ok();
+
+        Self {
+            modified: metadata
+                .as_ref()
+                .and_then(|metadata| metadata.modified().ok()),
+            len: metadata.as_ref().map(|metadata| metadata.len()),
+            hash: None,
+        }
+    }
+
+    fn with_hash(mut self, path: &Path) -> Self {
+        self.hash = fs::read(path).ok().map(|bytes| {
+            let mut hasher = DefaultHasher::new();
+            bytes.hash(&mut hasher);
+            hasher.finish()
+        });
+        self
+    }
+}
+
+/// Polls the image files read by a graph's nodes for changes.
+///
+/// A file is considered changed when its content changed. The content is only read when the
+/// modification time or size changed, so saving a file without changing it does not count as a
+/// change.
+#[derive(Debug)]
+pub(crate) struct FileWatcher {
+    stamps: BTreeMap<PathBuf, FileStamp>,
+    node_paths: BTreeMap<NodeId, Vec<PathBuf>>,
+    pub interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        Self {
+            stamps: BTreeMap::new(),
+            node_paths: BTreeMap::new(),
+            interval: Duration::from_millis(500),
+            last_poll: None,
+        }
+    }
+}
+
+impl FileWatcher {
+    /// Returns true if enough time has passed since the last poll.
+    pub fn poll_due(&self) -> bool {
+        self.last_poll
+            .map(|last_poll| last_poll.elapsed() >= self.interval)
+            .unwrap_or(true)
+    }
+
+    /// Checks the files of the given nodes and returns the nodes that read a file that changed
+    /// since the last check, or that read other files than at the last check. Files that are seen
+    /// for the first time are not considered changed, and files that are no longer read by any
+    /// node are forgotten.
+    pub fn changed_nodes(&mut self, node_paths: &[(NodeId, Vec<PathBuf>)]) -> Vec<NodeId> {
+        self.last_poll = Some(Instant::now());
+
+        let mut paths = node_paths
+            .iter()
+            .flat_map(|(_, paths)| paths.iter().cloned())
+            .collect::<Vec<PathBuf>>();
+        paths.sort_unstable();
+        paths.dedup();
+
+        self.stamps
+            .retain(|path, _| paths.binary_search(path).is_ok());
+
+        let mut changed_paths = Vec::new();
+        for path in paths {
+            let stamp = FileStamp::new(&path);
+
+            match self.stamps.get(&path) {
+                Some(old_stamp)
+                    if old_stamp.modified == stamp.modified && old_stamp.len == stamp.len => {}
+                Some(old_stamp) => {
+                    let stamp = stamp.with_hash(&path);
+                    if stamp.hash != old_stamp.hash {
+                        changed_paths.push(path.clone());
+                    }
+                    self.stamps.insert(path, stamp);
+                }
+                None => {
+                    self.stamps.insert(path.clone(), stamp.with_hash(&path));
+                }
+            }
+        }
+
+        let old_node_paths =
+            std::mem::replace(&mut self.node_paths, node_paths.iter().cloned().collect());
+
+        node_paths
+            .iter()
+            .filter(|(node_id, paths)| {
+                paths.iter().any(|path| changed_paths.contains(path))
+                    || matches!(old_node_paths.get(node_id), Some(old_paths) if old_paths != paths)
+            })
+            .map(|(node_id, _)| *node_id)
+            .collect()
+    }
+}
@@ -1,7 +1,11 @@
+pub mod bundle;
 pub mod edge;
 mod engine;
 pub mod error;
+pub mod export;
+mod file_watcher;
 pub mod live_graph;
+pub mod mipmap;
 pub mod node;
 pub mod node_graph;
 pub mod priority;
@@ -1,9 +1,13 @@
 use crate::{
+    bundle::Bundle,
     edge::Edge,
     error::{Result, TexProError},
+    export::{self, dds::DdsOptions, ktx2::Ktx2Options, ExportOptions},
+    file_watcher::FileWatcher,
     node::{
         embed::{EmbeddedSlotData, EmbeddedSlotDataId},
-        Node, Side,
+        grayscale::GrayConversion,
+        Node, Side, SlotType,
     },
     node_graph::*,
     priority::{Priority, PriorityPropagator},
@@ -13,6 +17,7 @@ use crate::{
 use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
     fmt::Display,
+    path::{Path, PathBuf},
     sync::{atomic::Ordering, Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
     thread,
     time::Duration,
@@ -34,6 +39,9 @@ pub enum NodeState {
     // Some input or setting was changed while the node was being processed, it will be processed
     // again when it's finished.
     ProcessingDirty,
+    // Processing the node or one of its ancestors failed, the error can be found with
+    // `LiveGraph::node_error()`. It will be processed again when it becomes dirty.
+    Failed,
 }
 
 impl Display for NodeState {
@@ -48,6 +56,7 @@ impl Display for NodeState {
                 Self::Prioritised => "Prioritised",
                 Self::Processing => "Processing",
                 Self::ProcessingDirty => "ProcessingDirty",
+                Self::Failed => "Failed",
             }
         )
     }
@@ -66,10 +75,18 @@ pub struct LiveGraph {
     embedded_slot_datas: Vec<Arc<EmbeddedSlotData>>,
     input_slot_datas: Vec<Arc<SlotData>>,
     node_state: BTreeMap<NodeId, NodeState>,
+    node_errors: BTreeMap<NodeId, TexProError>,
     changed: BTreeSet<NodeId>,
     priority_propagator: PriorityPropagator,
     pub auto_update: bool,
     pub use_cache: bool,
+    /// Polls the image files read by `Image` nodes, and marks the nodes and their descendants
+    /// dirty when a file changes.
+    pub watch_files: bool,
+    /// Uses a 1x1 magenta image for `Image` nodes with files that can't be read, instead of
+    /// failing the node.
+    pub missing_image_placeholder: bool,
+    file_watcher: FileWatcher,
     pub(crate) add_buffer_queue: Arc<RwLock<Vec<Arc<TransientBufferContainer>>>>,
 }
 
@@ -81,10 +98,14 @@ impl LiveGraph {
             embedded_slot_datas: Vec::new(),
             input_slot_datas: Vec::new(),
             node_state: BTreeMap::new(),
+            node_errors: BTreeMap::new(),
             changed: BTreeSet::new(),
             priority_propagator: PriorityPropagator::new(),
             auto_update: false,
             use_cache: false,
+            watch_files: false,
+            missing_image_placeholder: false,
+            file_watcher: FileWatcher::default(),
             add_buffer_queue,
         }
     }
@@ -94,6 +115,80 @@ impl LiveGraph {
         self.slot_data(node_id, slot_id)?.image.to_u8()
     }
 
+    /// Return a SlotData as interleaved RGBA `f32` values, without quantization.
+    pub fn buffer_rgba_f32(&self, node_id: NodeId, slot_id: SlotId) -> Result<Vec<f32>> {
+        self.slot_data(node_id, slot_id)?.image.to_f32()
+    }
+
+    /// Writes a SlotData to a file using the given `ExportOptions`. Premultiplied data is
+    /// unpremultiplied first, since image files store straight alpha. RGBA data is made gray
+    /// with the graph's `GrayConversion`, which replaces the one in the options.
+    pub fn export<P: AsRef<Path>>(
+        &self,
+        node_id: NodeId,
+        slot_id: SlotId,
+        path: P,
+        options: &ExportOptions,
+    ) -> Result<()> {
+        self.slot_data(node_id, slot_id)?
+            .straight_image()
+            .export(path, &options.gray_conversion(self.gray_conversion()))
+    }
+
+    /// Writes the output of an output node to a file. The color channels are encoded
+    /// according to the node's declared `color_space`, which replaces the `encoding` in the
+    /// given `ExportOptions`.
+    pub fn export_output<P: AsRef<Path>>(
+        &self,
+        node_id: NodeId,
+        path: P,
+        options: &ExportOptions,
+    ) -> Result<()> {
+        let node = self.node(node_id)?;
+        if !node.node_type.is_output() {
+            return Err(TexProError::InvalidNodeType);
+        }
+
+        self.export(
+            node_id,
+            SlotId(0),
+            path,
+            &options.encoding(node.color_space.encoding()),
+        )
+    }
+
+    /// Writes a SlotData to a block compressed DDS file using the given `DdsOptions`, with the
+    /// graph's `GrayConversion`.
+    pub fn export_dds<P: AsRef<Path>>(
+        &self,
+        node_id: NodeId,
+        slot_id: SlotId,
+        path: P,
+        options: &DdsOptions,
+    ) -> Result<()> {
+        export::dds::export(
+            &self.slot_data(node_id, slot_id)?.straight_image(),
+            path,
+            &options.gray_conversion(self.gray_conversion()),
+        )
+    }
+
+    /// Writes a SlotData to an uncompressed KTX2 file using the given `Ktx2Options`, with the
+    /// graph's `GrayConversion`.
+    pub fn export_ktx2<P: AsRef<Path>>(
+        &self,
+        node_id: NodeId,
+        slot_id: SlotId,
+        path: P,
+        options: &Ktx2Options,
+    ) -> Result<()> {
+        export::ktx2::export(
+            &self.slot_data(node_id, slot_id)?.straight_image(),
+            path,
+            &options.gray_conversion(self.gray_conversion()),
+        )
+    }
+
     /// Tries to get the output of a node. If it can't it submits a request for it.
     pub fn try_buffer_rgba(
         live_graph: &Arc<RwLock<LiveGraph>>,
@@ -152,6 +247,55 @@ impl LiveGraph {
         result
     }
 
+    /// Sets how often files are polled for changes when `watch_files` is enabled.
+    pub fn set_file_watch_interval(&mut self, interval: Duration) {
+        self.file_watcher.interval = interval;
+    }
+
+    /// Checks if any of the image files read by the nodes in the graph have changed since the
+    /// last check, and marks the nodes reading them and all their descendants dirty. Paths are
+    /// resolved against `search_paths` like when the images are read, see
+    /// `node_graph::resolve_path()`. Returns the `NodeId`s of the nodes reading changed files.
+    ///
+    /// Nodes that can't be marked dirty are failed with the error instead.
+    pub fn check_file_changes(&mut self, search_paths: &[PathBuf]) -> Result<Vec<NodeId>> {
+        let node_paths = self
+            .node_graph
+            .nodes()
+            .iter()
+            .map(|node| {
+                let paths = node
+                    .node_type
+                    .image_paths()
+                    .into_iter()
+                    .map(|path| {
+                        resolve_path(path, search_paths).unwrap_or_else(|| path.to_path_buf())
+                    })
+                    .collect::<Vec<PathBuf>>();
+                (node.node_id, paths)
+            })
+            .filter(|(_, paths)| !paths.is_empty())
+            .collect::<Vec<(NodeId, Vec<PathBuf>)>>();
+
+        let changed = self.file_watcher.changed_nodes(&node_paths);
+        for node_id in &changed {
+            if let Err(e) = self.set_state(*node_id, NodeState::Dirty) {
+                self.fail_node(*node_id, e)?;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// Checks for file changes if `watch_files` is enabled and it's time to poll again.
+    pub(crate) fn poll_file_changes(&mut self, search_paths: &[PathBuf]) -> Result<Vec<NodeId>> {
+        if self.watch_files && self.file_watcher.poll_due() {
+            self.check_file_changes(search_paths)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
     /// Return all changed `NodeId`s.
     pub fn changed_consume(&mut self) -> Vec<NodeId> {
         let output = self.changed.iter().copied().collect();
@@ -167,10 +311,10 @@ impl LiveGraph {
     ) -> Result<RwLockWriteGuard<LiveGraph>> {
         loop {
             if let Ok(mut live_graph) = live_graph.write() {
-                if live_graph.node_state(node_id)? == NodeState::Clean {
-                    return Ok(live_graph);
-                } else {
-                    live_graph.prioritise(node_id)?;
+                match live_graph.node_state(node_id)? {
+                    NodeState::Clean => return Ok(live_graph),
+                    NodeState::Failed => return Err(TexProError::NodeFailed),
+                    _ => live_graph.prioritise(node_id)?,
                 }
             }
 
@@ -184,8 +328,10 @@ impl LiveGraph {
     ) -> Result<RwLockReadGuard<LiveGraph>> {
         loop {
             if let Ok(live_graph) = live_graph.read() {
-                if live_graph.node_state(node_id)? == NodeState::Clean {
-                    return Ok(live_graph);
+                match live_graph.node_state(node_id)? {
+                    NodeState::Clean => return Ok(live_graph),
+                    NodeState::Failed => return Err(TexProError::NodeFailed),
+                    _ => (),
                 }
             }
 
@@ -284,7 +430,9 @@ impl LiveGraph {
         let mut processing = Vec::new();
         for node_id in self.node_graph.get_parents(node_id) {
             match self.node_state(node_id).unwrap() {
-                NodeState::Processing | NodeState::ProcessingDirty => processing.push(node_id),
+                NodeState::Processing | NodeState::ProcessingDirty | NodeState::Failed => {
+                    processing.push(node_id)
+                }
                 NodeState::Dirty | NodeState::Requested | NodeState::Prioritised => {
                     dirty.push(node_id)
                 }
@@ -310,6 +458,69 @@ impl LiveGraph {
         closest_processable
     }
 
+    /// Returns the error that made the node fail. If the node failed because one of its
+    /// ancestors failed, the ancestor's error is returned.
+    pub fn node_error(&self, node_id: NodeId) -> Option<&TexProError> {
+        if self.node_state(node_id) != Ok(NodeState::Failed) {
+            return None;
+        }
+
+        self.node_errors.get(&node_id).or_else(|| {
+            self.node_graph
+                .get_parents(node_id)
+                .into_iter()
+                .find_map(|node_id| self.node_error(node_id))
+        })
+    }
+
+    /// Marks the node and all its descendants as failed, with the given error.
+    pub(crate) fn fail_node(&mut self, node_id: NodeId, error: TexProError) -> Result<()> {
+        self.fail_descendants(node_id)?;
+        self.node_errors.insert(node_id, error);
+
+        Ok(())
+    }
+
+    /// Fails nodes waiting to be processed that have a failed parent, since they can't be
+    /// processed until the parent is fixed.
+    pub(crate) fn propagate_failures(&mut self) {
+        let waiting = self
+            .node_state
+            .iter()
+            .filter(|(_, node_state)| {
+                matches!(
+                    node_state,
+                    NodeState::Dirty | NodeState::Requested | NodeState::Prioritised
+                )
+            })
+            .map(|(node_id, _)| *node_id)
+            .collect::<Vec<NodeId>>();
+
+        for node_id in waiting {
+            if self.node_state(node_id) != Ok(NodeState::Failed)
+                && self
+                    .node_graph
+                    .get_parents(node_id)
+                    .into_iter()
+                    .any(|node_id| self.node_state(node_id) == Ok(NodeState::Failed))
+            {
+                let _ = self.fail_descendants(node_id);
+            }
+        }
+    }
+
+    fn fail_descendants(&mut self, node_id: NodeId) -> Result<()> {
+        let mut failed = self.node_graph.get_children_recursive(node_id)?;
+        failed.push(node_id);
+
+        for node_id in failed {
+            self.remove_nodes_data(node_id);
+            self.force_state(node_id, NodeState::Failed)?;
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn embedded_slot_datas(&self) -> &Vec<Arc<EmbeddedSlotData>> {
         &self.embedded_slot_datas
     }
@@ -470,6 +681,7 @@ impl LiveGraph {
         self.remove_nodes_data(node_id);
 
         self.node_state.remove(&node_id);
+        self.node_errors.remove(&node_id);
 
         Ok(edges)
     }
@@ -516,6 +728,10 @@ impl LiveGraph {
         let node_state_old = self.node_state(node_id)?;
 
         if node_state != node_state_old {
+            if node_state != NodeState::Failed {
+                self.node_errors.remove(&node_id);
+            }
+
             // If the state becomes dirty, propagate it to all children.
             if node_state == NodeState::Dirty {
                 for node_id in self.node_graph.get_children(node_id)? {
@@ -602,6 +818,35 @@ impl LiveGraph {
         self.node_graph.connected_edges(node_id, side, slot_id)
     }
 
+    /// Saves the graph, its embedded `SlotData`s and all image files it reads to a single file.
+    /// The image paths are resolved against `search_paths`, which should be the ones from
+    /// `TextureProcessor::search_paths()`. See `Bundle`.
+    pub fn save_bundle<P: AsRef<Path>>(&self, path: P, search_paths: &[PathBuf]) -> Result<()> {
+        Bundle::new(&self.node_graph, &self.embedded_slot_datas, search_paths)?.save(path)
+    }
+
+    /// Replaces the graph and the embedded `SlotData`s with the ones in a bundle file. The
+    /// image files in the bundle are written to `extract_dir`, which the loaded `Image` nodes
+    /// then read from.
+    pub fn load_bundle<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        path: P,
+        extract_dir: Q,
+    ) -> Result<()> {
+        let (node_graph, embedded_slot_datas) = Bundle::load(path)?.extract(extract_dir)?;
+
+        self.embedded_slot_datas.clear();
+        for embedded_slot_data in embedded_slot_datas {
+            for buf in embedded_slot_data.image.bufs() {
+                TransientBufferQueue::add_buffer(&self.add_buffer_queue, buf);
+            }
+            self.embedded_slot_datas.push(Arc::new(embedded_slot_data));
+        }
+        self.set_node_graph(node_graph);
+
+        Ok(())
+    }
+
     pub fn set_node_graph(&mut self, node_graph: NodeGraph) {
         self.node_graph = node_graph;
         self.reset_node_states();
@@ -613,6 +858,7 @@ impl LiveGraph {
     /// Note: It's important that this function does not use `set_state()`.
     pub(crate) fn reset_node_states(&mut self) {
         self.node_state.clear();
+        self.node_errors.clear();
         for node_id in self.node_ids() {
             self.node_state.insert(node_id, NodeState::default());
         }
@@ -622,6 +868,54 @@ impl LiveGraph {
         self.node_graph.output_ids()
     }
 
+    pub fn file_references(&self) -> Vec<FileReference> {
+        self.node_graph.file_references()
+    }
+
+    pub fn unresolved_file_references(&self) -> Vec<FileReference> {
+        self.node_graph.unresolved_file_references()
+    }
+
+    pub fn unresolved_file_references_in(&self, search_paths: &[PathBuf]) -> Vec<FileReference> {
+        self.node_graph.unresolved_file_references_in(search_paths)
+    }
+
+    /// Rewrites image paths starting with `from` to start with `to` and marks the changed
+    /// nodes dirty. See `NodeGraph::relink()`.
+    pub fn relink(&mut self, from: &Path, to: &Path) -> Result<Vec<NodeId>> {
+        let changed = self.node_graph.relink(from, to);
+        for node_id in &changed {
+            self.set_state(*node_id, NodeState::Dirty)?;
+        }
+
+        Ok(changed)
+    }
+
+    pub fn gray_conversion(&self) -> GrayConversion {
+        self.node_graph.gray_conversion
+    }
+
+    /// Sets how RGBA images are made gray when they are connected to gray input slots, and
+    /// marks the nodes with gray input slots dirty.
+    pub fn set_gray_conversion(&mut self, gray_conversion: GrayConversion) -> Result<()> {
+        if self.node_graph.gray_conversion == gray_conversion {
+            return Ok(());
+        }
+        self.node_graph.gray_conversion = gray_conversion;
+
+        for node in self.node_graph.nodes.clone() {
+            if node
+                .input_slots()
+                .iter()
+                .any(|slot| slot.slot_type == SlotType::Gray)
+            {
+                self.set_state(node.node_id, NodeState::Dirty)?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn rename_output_node(&mut self, node_id: NodeId, new_name: &str) -> Result<String> {
         self.node_graph.rename_output_node(node_id, new_name)
     }
@@ -0,0 +1,406 @@
+use crate::{
+    error::Result,
+    export::{ColorEncoding, TextureUsage},
+    node::ResizeFilter,
+    slot_data::{Size, SrgbColorSpace},
+    slot_image::{Buffer, SlotImage},
+    transient_buffer::{TransientBuffer, TransientBufferContainer},
+};
+use image::{imageops, Luma};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::max,
+    f32::consts::PI,
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+// The Kaiser filter reaches this many destination pixels out from the center.
+const KAISER_WIDTH: f32 = 3.0;
+const KAISER_ALPHA: f32 = 4.0;
+
+/// How each mip level is filtered down from the previous level.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum MipFilter {
+    /// Averages the pixels covered by each pixel in the smaller level. Fast but blurry.
+    Box,
+    /// A Kaiser windowed sinc filter, keeps more detail than the box filter.
+    Kaiser,
+    /// Resizes with one of the filters used when resizing node inputs.
+    Resize(ResizeFilter),
+}
+
+impl Default for MipFilter {
+    fn default() -> Self {
+        Self::Box
+    }
+}
+
+impl fmt::Display for MipFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Box => write!(f, "Box"),
+            Self::Kaiser => write!(f, "Kaiser"),
+            Self::Resize(resize_filter) => write!(f, "Resize: {}", resize_filter),
+        }
+    }
+}
+
+/// Describes how a mip chain is generated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct MipOptions {
+    pub filter: MipFilter,
+    /// How the color values in the image are encoded. sRGB encoded images are filtered in linear
+    /// space and encoded again, so they don't get darker in smaller levels.
+    pub encoding: ColorEncoding,
+    /// Renormalizes the RGB channels as a tangent space normal map after filtering, like the
+    /// ones made by `HeightToNormal`.
+    pub normalize: bool,
+    /// Scales the alpha in each level so the share of pixels above this alpha cutoff stays the
+    /// same as in the first level. Keeps alpha tested cutout textures from thinning out.
+    pub alpha_coverage: Option<f32>,
+    /// Samples across the edges as if the image is tiling. Only used by the Kaiser filter.
+    pub wrap: bool,
+}
+
+impl From<TextureUsage> for MipOptions {
+    fn from(usage: TextureUsage) -> Self {
+        Self::from_usage(usage)
+    }
+}
+
+impl MipOptions {
+    pub fn new(filter: MipFilter) -> Self {
+        Self {
+            filter,
+            ..Self::default()
+        }
+    }
+
+    /// Creates options that fit what the texture is used for. Normal maps get renormalized.
+    pub fn from_usage(usage: TextureUsage) -> Self {
+        Self::default().normalize(usage == TextureUsage::Normal)
+    }
+
+    pub fn filter(mut self, filter: MipFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: ColorEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    pub fn alpha_coverage(mut self, alpha_coverage: Option<f32>) -> Self {
+        self.alpha_coverage = alpha_coverage;
+        self
+    }
+
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+}
+
+/// Returns the size of the next smaller mip level.
+pub fn next_mip_size(size: Size) -> Size {
+    Size::new(max(size.width / 2, 1), max(size.height / 2, 1))
+}
+
+/// Returns the number of levels in a full mip chain for the given size, including the full size
+/// level.
+pub fn mip_level_count(size: Size) -> usize {
+    let mut size = size;
+    let mut count = 1;
+
+    while size.width > 1 || size.height > 1 {
+        size = next_mip_size(size);
+        count += 1;
+    }
+
+    count
+}
+
+/// Averages all pixels in the source that are covered by each pixel in the destination.
+fn downsample_box(buffer: &Buffer, size: Size) -> Buffer {
+    let (src_width, src_height) = buffer.dimensions();
+    let ratio_x = src_width as f32 / size.width as f32;
+    let ratio_y = src_height as f32 / size.height as f32;
+
+    Buffer::from_fn(size.width, size.height, |x, y| {
+        let x_start = (x as f32 * ratio_x) as u32;
+        let x_end = max(((x + 1) as f32 * ratio_x) as u32, x_start + 1).min(src_width);
+        let y_start = (y as f32 * ratio_y) as u32;
+        let y_end = max(((y + 1) as f32 * ratio_y) as u32, y_start + 1).min(src_height);
+
+        let mut sum = 0.0;
+        for y in y_start..y_end {
+            for x in x_start..x_end {
+                sum += buffer.get_pixel(x, y).0[0];
+            }
+        }
+
+        Luma([sum / ((x_end - x_start) * (y_end - y_start)) as f32])
+    })
+}
+
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+
+    for k in 1..16 {
+        let half_x_over_k = x / (2.0 * k as f32);
+        term *= half_x_over_k * half_x_over_k;
+        sum += term;
+    }
+
+    sum
+}
+
+fn kaiser(x: f32) -> f32 {
+    if x.abs() >= KAISER_WIDTH {
+        return 0.0;
+    }
+
+    let sinc = if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    };
+    let t = x / KAISER_WIDTH;
+
+    sinc * bessel_i0(KAISER_ALPHA * (1.0 - t * t).sqrt()) / bessel_i0(KAISER_ALPHA)
+}
+
+/// Resamples the buffer along one axis with the Kaiser filter.
+fn resample_kaiser_axis(buffer: &Buffer, length: u32, horizontal: bool, wrap: bool) -> Buffer {
+    let (src_width, src_height) = buffer.dimensions();
+    let src_length = if horizontal { src_width } else { src_height };
+    let (width, height) = if horizontal {
+        (length, src_height)
+    } else {
+        (src_width, length)
+    };
+
+    if src_length == length {
+        return buffer.clone();
+    }
+
+    let ratio = src_length as f32 / length as f32;
+    let support = KAISER_WIDTH * ratio;
+
+    // The weights are the same for each row or column, so they are only calculated once.
+    let taps = (0..length)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * ratio - 0.5;
+            let start = (center - support).floor() as i64;
+            let end = (center + support).ceil() as i64;
+
+            let mut taps = (start..=end)
+                .map(|s| {
+                    let index = if wrap {
+                        s.rem_euclid(src_length as i64)
+                    } else {
+                        s.clamp(0, src_length as i64 - 1)
+                    } as u32;
+                    (index, kaiser((s as f32 - center) / ratio))
+                })
+                .filter(|(_, weight)| *weight != 0.0)
+                .collect::<Vec<(u32, f32)>>();
+
+            let total = taps.iter().map(|(_, weight)| weight).sum::<f32>();
+            for (_, weight) in taps.iter_mut() {
+                *weight /= total;
+            }
+
+            taps
+        })
+        .collect::<Vec<Vec<(u32, f32)>>>();
+
+    Buffer::from_fn(width, height, |x, y| {
+        let (i, other) = if horizontal { (x, y) } else { (y, x) };
+
+        Luma([taps[i as usize]
+            .iter()
+            .map(|(index, weight)| {
+                let value = if horizontal {
+                    buffer.get_pixel(*index, other).0[0]
+                } else {
+                    buffer.get_pixel(other, *index).0[0]
+                };
+                value * weight
+            })
+            .sum()])
+    })
+}
+
+fn downsample(buffer: &Buffer, size: Size, options: &MipOptions) -> Buffer {
+    match options.filter {
+        MipFilter::Box => downsample_box(buffer, size),
+        MipFilter::Kaiser => resample_kaiser_axis(
+            &resample_kaiser_axis(buffer, size.width, true, options.wrap),
+            size.height,
+            false,
+            options.wrap,
+        ),
+        MipFilter::Resize(resize_filter) => {
+            imageops::resize(buffer, size.width, size.height, resize_filter.into())
+        }
+    }
+}
+
+/// Renormalizes the vectors stored in the first three buffers, in the 0.0 to 1.0 range.
+fn renormalize(buffers: &mut [Buffer]) {
+    if buffers.len() < 3 {
+        return;
+    }
+
+    let (width, height) = buffers[0].dimensions();
+    for y in 0..height {
+        for x in 0..width {
+            let vector = [0, 1, 2].map(|i| buffers[i].get_pixel(x, y).0[0] * 2.0 - 1.0);
+            let length = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+
+            if length > f32::EPSILON {
+                for (buffer, value) in buffers.iter_mut().zip(vector) {
+                    buffer.put_pixel(x, y, Luma([value / length * 0.5 + 0.5]));
+                }
+            }
+        }
+    }
+}
+
+/// The share of pixels with an alpha above the cutoff after scaling the alpha.
+fn alpha_coverage(alpha: &Buffer, cutoff: f32, scale: f32) -> f32 {
+    let covered = alpha
+        .pixels()
+        .filter(|pixel| pixel.0[0] * scale > cutoff)
+        .count();
+
+    covered as f32 / alpha.pixels().len() as f32
+}
+
+/// Scales the alpha so its coverage gets as close as possible to the target coverage.
+fn scale_alpha_coverage(alpha: &mut Buffer, cutoff: f32, target: f32) {
+    let (mut low, mut high) = (0.0_f32, 4.0_f32);
+    let mut best = (1.0, (alpha_coverage(alpha, cutoff, 1.0) - target).abs());
+
+    for _ in 0..16 {
+        let scale = (low + high) / 2.0;
+        let coverage = alpha_coverage(alpha, cutoff, scale);
+
+        if (coverage - target).abs() < best.1 {
+            best = (scale, (coverage - target).abs());
+        }
+
+        if coverage < target {
+            low = scale;
+        } else {
+            high = scale;
+        }
+    }
+
+    for pixel in alpha.pixels_mut() {
+        pixel.0[0] = (pixel.0[0] * best.0).min(1.0);
+    }
+}
+
+/// Converts the color buffers between sRGB and linear, alpha is left as is.
+fn convert_encoding(buffers: &mut [Buffer], convert: fn(f32) -> f32) {
+    for buffer in buffers.iter_mut().take(3) {
+        for pixel in buffer.pixels_mut() {
+            pixel.0[0] = convert(pixel.0[0]);
+        }
+    }
+}
+
+fn buffer_container(buffer: Buffer) -> Arc<TransientBufferContainer> {
+    Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
+        TransientBuffer::new(Box::new(buffer)),
+    ))))
+}
+
+fn slot_image_from_buffers(mut buffers: Vec<Buffer>) -> Result<SlotImage> {
+    if buffers.len() == 4 {
+        SlotImage::from_buffers_rgba(&mut buffers)
+    } else {
+        Ok(SlotImage::Gray(buffer_container(buffers.pop().unwrap())))
+    }
+}
+
+/// Generates the mip chain for the given image, stopping after the given number of levels or
+/// at 1x1 pixels. Each level is filtered from the one before it.
+fn mip_levels(
+    slot_image: &SlotImage,
+    options: &MipOptions,
+    level_count: usize,
+) -> Result<Vec<SlotImage>> {
+    let level_count = level_count.min(mip_level_count(slot_image.size()?));
+    let mut levels = vec![slot_image.clone()];
+
+    let mut current = slot_image
+        .bufs()
+        .iter()
+        .map(|tbc| tbc.transient_buffer().buffer().clone())
+        .collect::<Vec<Buffer>>();
+    if options.encoding == ColorEncoding::Srgb {
+        convert_encoding(&mut current, f32::srgb_to_linear);
+    }
+
+    let coverage = match (options.alpha_coverage, current.get(3)) {
+        (Some(cutoff), Some(alpha)) => Some((cutoff, alpha_coverage(alpha, cutoff, 1.0))),
+        _ => None,
+    };
+
+    for _ in 1..level_count {
+        let size = next_mip_size(Size::new(current[0].width(), current[0].height()));
+        current = current
+            .iter()
+            .map(|buffer| downsample(buffer, size, options))
+            .collect();
+
+        if options.normalize {
+            renormalize(&mut current);
+        }
+
+        let mut level = current.clone();
+        if let Some((cutoff, target)) = coverage {
+            scale_alpha_coverage(&mut level[3], cutoff, target);
+        }
+        if options.encoding == ColorEncoding::Srgb {
+            convert_encoding(&mut level, f32::linear_to_srgb);
+        }
+
+        levels.push(slot_image_from_buffers(level)?);
+    }
+
+    Ok(levels)
+}
+
+/// Generates a full mip chain. The first level is the given image, the last level is 1x1
+/// pixels.
+pub fn mip_chain(slot_image: &SlotImage, options: &MipOptions) -> Result<Vec<SlotImage>> {
+    mip_levels(slot_image, options, usize::MAX)
+}
+
+/// Returns the largest mip level that fits within the given number of pixels on both axes, for
+/// low resolution previews.
+pub fn preview(slot_image: &SlotImage, max_size: u32, options: &MipOptions) -> Result<SlotImage> {
+    let mut size = slot_image.size()?;
+    let mut level_count = 1;
+
+    while size.width > max_size.max(1) || size.height > max_size.max(1) {
+        size = next_mip_size(size);
+        level_count += 1;
+    }
+
+    Ok(mip_levels(slot_image, options, level_count)?
+        .pop()
+        .expect("There is always at least one level"))
+}
@@ -0,0 +1,166 @@
+use std::{
+    f32::consts::TAU,
+    fmt,
+    sync::{atomic::AtomicBool, Arc, RwLock},
+};
+
+use crate::{
+    error::{Result, TexProError},
+    node::{
+        process_shared::{cancelling, slot_data_with_name},
+        transform::{EdgeMode, Sampler},
+    },
+    node_graph::SlotId,
+    slot_data::SlotData,
+    slot_image::{Buffer, SlotImage},
+    transient_buffer::{TransientBuffer, TransientBufferContainer},
+};
+
+use super::{Node, ResizeFilter};
+
+use image::Luma;
+use serde::{Deserialize, Serialize};
+
+/// The settings of a `NodeType::AmbientOcclusion` node, which darkens the parts of a height map
+/// that are hidden behind the heights around them.
+///
+/// A height of 1 is as tall as `radius` is long. The height map wraps around, so the result
+/// tiles if the height map does.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct AmbientOcclusion {
+    /// How dark the occlusion gets, where 1 is fully dark for a pixel at the bottom of a
+    /// pit.
+    pub intensity: f32,
+    /// How far away heights can occlude a pixel, as a fraction of the image size.
+    pub radius: f32,
+    /// How many directions are searched for the horizon, and how many steps are taken in each
+    /// of them. Higher is smoother and slower.
+    pub quality: u32,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            radius: 0.05,
+            quality: 8,
+        }
+    }
+}
+
+impl fmt::Display for AmbientOcclusion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Intensity {}, radius {}, quality {}",
+            self.intensity, self.radius, self.quality
+        )
+    }
+}
+
+impl AmbientOcclusion {
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn quality(mut self, quality: u32) -> Self {
+        self.quality = quality;
+        self
+    }
+}
+
+pub(crate) fn process(
+    shutdown: Arc<AtomicBool>,
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    ambient_occlusion: AmbientOcclusion,
+) -> Result<Vec<Arc<SlotData>>> {
+    let slot_data = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data
+    } else {
+        return Ok(Vec::new());
+    };
+
+    let size = slot_data.size()?;
+    let (width, height) = (size.width, size.height);
+    let quality = ambient_occlusion.quality.max(1);
+    let radius = [
+        ambient_occlusion.radius * width as f32,
+        ambient_occlusion.radius * height as f32,
+    ];
+
+    // The directions to search in, evenly spread out around each pixel.
+    let directions = (0..quality)
+        .map(|i| ((i as f32 + 0.5) / quality as f32 * TAU).sin_cos())
+        .map(|(sin, cos)| [cos * radius[0], sin * radius[1]])
+        .collect::<Vec<_>>();
+
+    let mut buffer = Buffer::new(width, height);
+
+    {
+        let buffer_height = if let SlotImage::Gray(buf) = &slot_data.image {
+            buf.transient_buffer()
+        } else {
+            return Ok(Vec::new());
+        };
+        let buffer_height = buffer_height.buffer();
+        let sampler = Sampler::new(
+            vec![buffer_height.as_raw().as_slice()],
+            width,
+            height,
+            EdgeMode::Wrap,
+            ResizeFilter::Triangle,
+        );
+        let mut sample = [0.0];
+
+        for y in 0..height {
+            if cancelling(&node.cancel, &shutdown) {
+                return Err(TexProError::Canceled);
+            }
+
+            for x in 0..width {
+                let center = buffer_height.get_pixel(x, y)[0];
+                let (x_center, y_center) = (x as f32 + 0.5, y as f32 + 0.5);
+
+                let mut occlusion = 0.0;
+                for direction in &directions {
+                    // The steepest slope up to a height within the radius is the horizon.
+                    let mut horizon: f32 = 0.0;
+                    for step in 1..=quality {
+                        let distance = step as f32 / quality as f32;
+                        sampler.sample(
+                            x_center + direction[0] * distance,
+                            y_center + direction[1] * distance,
+                            &mut sample,
+                        );
+                        horizon = horizon.max((sample[0] - center) / distance);
+                    }
+
+                    // The sine of the horizon's angle.
+                    occlusion += horizon / horizon.hypot(1.0);
+                }
+                occlusion /= quality as f32;
+
+                buffer.put_pixel(
+                    x,
+                    y,
+                    Luma([(1.0 - occlusion * ambient_occlusion.intensity).clamp(0.0, 1.0)]),
+                );
+            }
+        }
+    }
+
+    Ok(vec![Arc::new(SlotData::new(
+        node.node_id,
+        SlotId(0),
+        SlotImage::Gray(Arc::new(TransientBufferContainer::new(Arc::new(
+            RwLock::new(TransientBuffer::new(Box::new(buffer))),
+        )))),
+    ))])
+}
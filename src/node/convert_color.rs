@@ -0,0 +1,187 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    error::Result,
+    node::process_shared::{map_colors, slot_data_with_name},
+    node_graph::SlotId,
+    slot_data::SlotData,
+};
+
+use super::Node;
+
+use serde::{Deserialize, Serialize};
+
+/// A way of describing colors with three components, which are stored in the red, green and
+/// blue channels of an image. All components go from 0 to 1, including hue.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorModel {
+    Rgb,
+    /// Hue, saturation and value.
+    Hsv,
+    /// Hue, saturation and lightness.
+    Hsl,
+    /// Luma and the blue and red differences as in JPEG, which is full range BT.601 with the
+    /// differences offset by 0.5.
+    Ycbcr,
+}
+
+impl Default for ColorModel {
+    fn default() -> Self {
+        Self::Rgb
+    }
+}
+
+impl fmt::Display for ColorModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rgb => write!(f, "RGB"),
+            Self::Hsv => write!(f, "HSV"),
+            Self::Hsl => write!(f, "HSL"),
+            Self::Ycbcr => write!(f, "YCbCr"),
+        }
+    }
+}
+
+impl ColorModel {
+    pub fn from_rgb(self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            Self::Rgb => rgb,
+            Self::Hsv => rgb_to_hsv(rgb),
+            Self::Hsl => rgb_to_hsl(rgb),
+            Self::Ycbcr => rgb_to_ycbcr(rgb),
+        }
+    }
+
+    pub fn to_rgb(self, color: [f32; 3]) -> [f32; 3] {
+        match self {
+            Self::Rgb => color,
+            Self::Hsv => hsv_to_rgb(color),
+            Self::Hsl => hsl_to_rgb(color),
+            Self::Ycbcr => ycbcr_to_rgb(color),
+        }
+    }
+}
+
+/// The settings of a `NodeType::ConvertColor` node. Alpha is kept as it is.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConvertColor {
+    pub from: ColorModel,
+    pub to: ColorModel,
+}
+
+impl fmt::Display for ConvertColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} to {}", self.from, self.to)
+    }
+}
+
+impl ConvertColor {
+    pub fn new(from: ColorModel, to: ColorModel) -> Self {
+        Self { from, to }
+    }
+}
+
+/// Returns the hue, the largest and the smallest component of an RGB color.
+fn hue_max_min([r, g, b]: [f32; 3]) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta <= 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+
+    (hue / 6.0, max, min)
+}
+
+/// Returns an RGB color with the given hue, chroma and smallest component.
+fn hue_chroma_to_rgb(hue: f32, chroma: f32, min: f32) -> [f32; 3] {
+    let hue = hue.rem_euclid(1.0) * 6.0;
+    let x = chroma * (1.0 - (hue.rem_euclid(2.0) - 1.0).abs());
+
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    [r + min, g + min, b + min]
+}
+
+pub(crate) fn rgb_to_hsv(rgb: [f32; 3]) -> [f32; 3] {
+    let (hue, max, min) = hue_max_min(rgb);
+    let saturation = if max <= 0.0 { 0.0 } else { (max - min) / max };
+
+    [hue, saturation, max]
+}
+
+pub(crate) fn hsv_to_rgb([hue, saturation, value]: [f32; 3]) -> [f32; 3] {
+    let chroma = value * saturation;
+    hue_chroma_to_rgb(hue, chroma, value - chroma)
+}
+
+pub(crate) fn rgb_to_hsl(rgb: [f32; 3]) -> [f32; 3] {
+    let (hue, max, min) = hue_max_min(rgb);
+    let lightness = (max + min) / 2.0;
+    let divisor = 1.0 - (2.0 * lightness - 1.0).abs();
+    let saturation = if divisor <= 0.0 {
+        0.0
+    } else {
+        (max - min) / divisor
+    };
+
+    [hue, saturation, lightness]
+}
+
+pub(crate) fn hsl_to_rgb([hue, saturation, lightness]: [f32; 3]) -> [f32; 3] {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    hue_chroma_to_rgb(hue, chroma, lightness - chroma / 2.0)
+}
+
+const KR: f32 = 0.299;
+const KB: f32 = 0.114;
+const KG: f32 = 1.0 - KR - KB;
+
+fn rgb_to_ycbcr([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let y = KR * r + KG * g + KB * b;
+    [
+        y,
+        (b - y) / (2.0 * (1.0 - KB)) + 0.5,
+        (r - y) / (2.0 * (1.0 - KR)) + 0.5,
+    ]
+}
+
+fn ycbcr_to_rgb([y, cb, cr]: [f32; 3]) -> [f32; 3] {
+    let r = y + 2.0 * (1.0 - KR) * (cr - 0.5);
+    let b = y + 2.0 * (1.0 - KB) * (cb - 0.5);
+    [r, (y - KR * r - KB * b) / KG, b]
+}
+
+pub(crate) fn process(
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    convert_color: ConvertColor,
+) -> Result<Vec<Arc<SlotData>>> {
+    let image = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data.straight_image()
+    } else {
+        return Ok(Vec::new());
+    };
+
+    Ok(vec![Arc::new(SlotData::new(
+        node.node_id,
+        SlotId(0),
+        map_colors(&image, |color| {
+            convert_color.to.from_rgb(convert_color.from.to_rgb(color))
+        })?,
+    ))])
+}
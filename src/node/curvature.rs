@@ -0,0 +1,128 @@
+use std::{
+    fmt,
+    sync::{atomic::AtomicBool, Arc, RwLock},
+};
+
+use crate::{
+    error::{Result, TexProError},
+    node::process_shared::{cancelling, slot_data_with_name, Sampling},
+    node_graph::SlotId,
+    slot_data::SlotData,
+    slot_image::{Buffer, SlotImage},
+    transient_buffer::{TransientBuffer, TransientBufferContainer},
+};
+
+use super::Node;
+
+use serde::{Deserialize, Serialize};
+
+/// The settings of a `NodeType::Curvature` node, which finds where a height map bends.
+///
+/// Flat and evenly sloped areas are 0.5, convex areas like ridges are brighter and concave
+/// areas like crevices are darker. The height map wraps around, so the result tiles if the
+/// height map does.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct Curvature {
+    /// How tall a height of 1 is, as a fraction of the image size. Taller heights bend more
+    /// sharply, so the curvature gets stronger.
+    pub intensity: f32,
+}
+
+impl Default for Curvature {
+    fn default() -> Self {
+        Self { intensity: 0.1 }
+    }
+}
+
+impl fmt::Display for Curvature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Intensity {}", self.intensity)
+    }
+}
+
+impl Curvature {
+    pub fn new(intensity: f32) -> Self {
+        Self { intensity }
+    }
+}
+
+pub(crate) fn process(
+    shutdown: Arc<AtomicBool>,
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    curvature: Curvature,
+) -> Result<Vec<Arc<SlotData>>> {
+    let slot_data = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data
+    } else {
+        return Ok(Vec::new());
+    };
+
+    let size = slot_data.size()?;
+    let (width, height) = (size.width, size.height);
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+
+    // The x and y of the normal at each pixel, found from the slopes on both sides of it.
+    let normals = {
+        let buffer_height = if let SlotImage::Gray(buf) = &slot_data.image {
+            buf.transient_buffer()
+        } else {
+            return Ok(Vec::new());
+        };
+        let buffer_height = buffer_height.buffer();
+        let sample = |x: u32, y: u32| buffer_height.get_pixel(x, y)[0];
+
+        let mut normals = Vec::with_capacity(size.pixel_count());
+        for y in 0..height {
+            if cancelling(&node.cancel, &shutdown) {
+                return Err(TexProError::Canceled);
+            }
+
+            for x in 0..width {
+                let slope_x = (sample(x.wrapping_sample_add(1, width), y)
+                    - sample(x.wrapping_sample_subtract(1, width), y))
+                    / 2.0
+                    * width as f32
+                    * curvature.intensity;
+                let slope_y = (sample(x, y.wrapping_sample_add(1, height))
+                    - sample(x, y.wrapping_sample_subtract(1, height)))
+                    / 2.0
+                    * height as f32
+                    * curvature.intensity;
+
+                let length = (slope_x * slope_x + slope_y * slope_y + 1.0).sqrt();
+                normals.push([-slope_x / length, -slope_y / length]);
+            }
+        }
+
+        normals
+    };
+
+    // Normals spread out over convex areas and come together over concave ones.
+    let mut values = Vec::with_capacity(size.pixel_count());
+    for y in 0..height {
+        if cancelling(&node.cancel, &shutdown) {
+            return Err(TexProError::Canceled);
+        }
+
+        for x in 0..width {
+            let divergence = (normals[index(x.wrapping_sample_add(1, width), y)][0]
+                - normals[index(x.wrapping_sample_subtract(1, width), y)][0]
+                + normals[index(x, y.wrapping_sample_add(1, height))][1]
+                - normals[index(x, y.wrapping_sample_subtract(1, height))][1])
+                / 2.0;
+
+            values.push((0.5 + divergence * 0.5).clamp(0.0, 1.0));
+        }
+    }
+
+    Ok(vec![Arc::new(SlotData::new(
+        node.node_id,
+        SlotId(0),
+        SlotImage::Gray(Arc::new(TransientBufferContainer::new(Arc::new(
+            RwLock::new(TransientBuffer::new(Box::new(
+                Buffer::from_raw(width, height, values).unwrap(),
+            ))),
+        )))),
+    ))])
+}
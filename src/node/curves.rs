@@ -0,0 +1,177 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    error::Result,
+    node::process_shared::{map_color_channels, slot_data_with_name},
+    node_graph::SlotId,
+    slot_data::SlotData,
+};
+
+use super::{levels::ToneChannels, Node};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct CurvePoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl CurvePoint {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+/// A curve through a number of control points, mapping input values (x) to output values (y).
+///
+/// The points are joined by a monotone cubic spline, so the curve is smooth but never
+/// overshoots the points. Before the first point and after the last one the curve is flat.
+/// Without any points the curve leaves values unchanged.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(from = "Vec<CurvePoint>", into = "Vec<CurvePoint>")]
+pub struct Curve {
+    points: Vec<CurvePoint>,
+    /// The slope of the curve at each point.
+    tangents: Vec<f32>,
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Self::new(vec![CurvePoint::new(0.0, 0.0), CurvePoint::new(1.0, 1.0)])
+    }
+}
+
+impl From<Vec<CurvePoint>> for Curve {
+    fn from(points: Vec<CurvePoint>) -> Self {
+        Self::new(points)
+    }
+}
+
+impl From<Curve> for Vec<CurvePoint> {
+    fn from(curve: Curve) -> Self {
+        curve.points
+    }
+}
+
+impl Curve {
+    /// Creates a curve through the points, which don't need to be sorted. Points with the same
+    /// x value as an earlier point are ignored.
+    pub fn new(mut points: Vec<CurvePoint>) -> Self {
+        points.retain(|point| point.x.is_finite() && point.y.is_finite());
+        points.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+        points.dedup_by(|b, a| (b.x - a.x).abs() <= f32::EPSILON);
+
+        let tangents = Self::tangents(&points);
+        Self { points, tangents }
+    }
+
+    pub fn points(&self) -> &[CurvePoint] {
+        &self.points
+    }
+
+    /// Calculates the tangents of a monotone cubic spline with the Fritsch-Carlson method.
+    fn tangents(points: &[CurvePoint]) -> Vec<f32> {
+        if points.len() < 2 {
+            return vec![0.0; points.len()];
+        }
+
+        let slopes = points
+            .windows(2)
+            .map(|pair| (pair[1].y - pair[0].y) / (pair[1].x - pair[0].x))
+            .collect::<Vec<f32>>();
+
+        let mut tangents = Vec::with_capacity(points.len());
+        tangents.push(slopes[0]);
+        for pair in slopes.windows(2) {
+            tangents.push(if pair[0] * pair[1] <= 0.0 {
+                0.0
+            } else {
+                (pair[0] + pair[1]) / 2.0
+            });
+        }
+        tangents.push(slopes[slopes.len() - 1]);
+
+        for (i, slope) in slopes.iter().enumerate() {
+            if slope.abs() <= f32::EPSILON {
+                tangents[i] = 0.0;
+                tangents[i + 1] = 0.0;
+                continue;
+            }
+
+            let alpha = tangents[i] / slope;
+            let beta = tangents[i + 1] / slope;
+            let length = alpha.hypot(beta);
+            if length > 3.0 {
+                tangents[i] = 3.0 / length * alpha * slope;
+                tangents[i + 1] = 3.0 / length * beta * slope;
+            }
+        }
+
+        tangents
+    }
+
+    pub fn apply(&self, value: f32) -> f32 {
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return value,
+        };
+        if value <= first.x {
+            return first.y;
+        } else if value >= last.x {
+            return last.y;
+        }
+
+        let i = self.points.partition_point(|point| point.x <= value) - 1;
+        let (a, b) = (self.points[i], self.points[i + 1]);
+        let width = b.x - a.x;
+        let t = (value - a.x) / width;
+        let (t2, t3) = (t * t, t * t * t);
+
+        (2.0 * t3 - 3.0 * t2 + 1.0) * a.y
+            + (t3 - 2.0 * t2 + t) * width * self.tangents[i]
+            + (-2.0 * t3 + 3.0 * t2) * b.y
+            + (t3 - t2) * width * self.tangents[i + 1]
+    }
+}
+
+/// The settings of a `NodeType::Curves` node.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Curves {
+    pub curves: ToneChannels<Curve>,
+}
+
+impl fmt::Display for Curves {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.curves {
+            ToneChannels::Combined(_) => write!(f, "Curves"),
+            ToneChannels::Separate(_) => write!(f, "Curves per channel"),
+        }
+    }
+}
+
+impl Curves {
+    pub fn new(curves: ToneChannels<Curve>) -> Self {
+        Self { curves }
+    }
+}
+
+pub(crate) fn process(
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    curves: &Curves,
+) -> Result<Vec<Arc<SlotData>>> {
+    let image = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data.straight_image()
+    } else {
+        return Ok(Vec::new());
+    };
+
+    Ok(vec![Arc::new(SlotData::new(
+        node.node_id,
+        SlotId(0),
+        map_color_channels(&image, |channel, value| {
+            curves.curves.channel(channel).apply(value)
+        }),
+    ))])
+}
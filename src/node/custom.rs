@@ -0,0 +1,278 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    error::{Result, TexProError},
+    node::process_shared::{output_size, slot_data_with_slot_id},
+    node_graph::SlotId,
+    slot_data::{Size, SlotData},
+    slot_image::SlotImage,
+};
+
+use super::{Node, SlotInput, SlotOutput, SlotType};
+
+use serde::{Deserialize, Serialize};
+
+/// Implements a node type outside of this crate.
+///
+/// Register it with `TextureProcessor::register_node_processor()` and create nodes of the type
+/// with `TextureProcessor::custom_node_type()`.
+pub trait NodeProcessor: Send + Sync {
+    /// A stable id for the node type, it's what graph files refer to the node type by. Prefixing
+    /// it with the name of the application avoids collisions, like "studio.tint".
+    fn id(&self) -> &str;
+
+    fn input_slots(&self) -> Vec<CustomSlot>;
+
+    fn output_slots(&self) -> Vec<CustomSlot>;
+
+    /// The parameters the node type takes, with their default values.
+    fn parameters(&self) -> Vec<Parameter> {
+        Vec::new()
+    }
+
+    /// Returns one `SlotImage` for each output slot, in order.
+    fn process(&self, context: &ProcessContext) -> Result<Vec<SlotImage>>;
+}
+
+/// An input or output slot of a custom node.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct CustomSlot {
+    pub name: String,
+    pub slot_type: SlotType,
+}
+
+impl CustomSlot {
+    pub fn new<S: Into<String>>(name: S, slot_type: SlotType) -> Self {
+        Self {
+            name: name.into(),
+            slot_type,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum ParameterValue {
+    Float(f32),
+    Int(i32),
+    Bool(bool),
+    Text(String),
+}
+
+impl fmt::Display for ParameterValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Float(value) => write!(f, "{}", value),
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl ParameterValue {
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            Self::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            Self::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn same_kind(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// Describes a parameter of a `NodeProcessor`. The type of the parameter is the type of its
+/// default value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub default: ParameterValue,
+}
+
+impl Parameter {
+    pub fn new<S: Into<String>>(name: S, default: ParameterValue) -> Self {
+        Self {
+            name: name.into(),
+            default,
+        }
+    }
+}
+
+/// The settings of a `NodeType::Custom` node.
+///
+/// The slots are stored in the node so graphs can be loaded, edited and saved even when the
+/// `NodeProcessor` is not registered. Such nodes fail with `TexProError::UnknownNodeType` when
+/// they are processed.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct CustomNode {
+    pub type_id: String,
+    /// Parameters that are not set use the default value from the `NodeProcessor`.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, ParameterValue>,
+    pub input_slots: Vec<CustomSlot>,
+    pub output_slots: Vec<CustomSlot>,
+}
+
+impl CustomNode {
+    pub(crate) fn new(node_processor: &dyn NodeProcessor) -> Self {
+        Self {
+            type_id: node_processor.id().into(),
+            parameters: BTreeMap::new(),
+            input_slots: node_processor.input_slots(),
+            output_slots: node_processor.output_slots(),
+        }
+    }
+
+    pub fn parameter<S: Into<String>>(mut self, name: S, value: ParameterValue) -> Self {
+        self.parameters.insert(name.into(), value);
+        self
+    }
+
+    pub(crate) fn slot_inputs(&self) -> Vec<SlotInput> {
+        slots(&self.input_slots)
+    }
+
+    pub(crate) fn slot_outputs(&self) -> Vec<SlotOutput> {
+        slots(&self.output_slots)
+    }
+}
+
+fn slots(custom_slots: &[CustomSlot]) -> Vec<SlotInput> {
+    custom_slots
+        .iter()
+        .enumerate()
+        .map(|(i, slot)| SlotInput::new(slot.name.clone(), SlotId(i as u32), slot.slot_type))
+        .collect()
+}
+
+/// What a `NodeProcessor` gets to work with when processing a node.
+pub struct ProcessContext<'a> {
+    node: &'a Node,
+    custom_node: &'a CustomNode,
+    slot_datas: &'a [Arc<SlotData>],
+    parameters: BTreeMap<String, ParameterValue>,
+    size: Size,
+    shutdown: &'a AtomicBool,
+}
+
+impl<'a> ProcessContext<'a> {
+    /// Returns the image in the input slot with the given name, if it's connected. Images with
+    /// premultiplied alpha are unpremultiplied.
+    pub fn input(&self, name: &str) -> Option<SlotImage> {
+        let slot_id = self
+            .custom_node
+            .input_slots
+            .iter()
+            .position(|slot| slot.name == name)?;
+
+        slot_data_with_slot_id(self.slot_datas, SlotId(slot_id as u32))
+            .map(|slot_data| slot_data.straight_image())
+    }
+
+    /// Returns the value of a parameter, or its default value if the node doesn't set it.
+    pub fn parameter(&self, name: &str) -> Option<&ParameterValue> {
+        self.parameters.get(name)
+    }
+
+    /// The size of the connected inputs, or the size from `ResizePolicy::SpecificSize` if
+    /// there are none.
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Whether the processing should stop, in which case the `NodeProcessor` should return
+    /// `TexProError::Canceled`.
+    pub fn is_canceled(&self) -> bool {
+        self.node.cancel.load(Ordering::Relaxed) || self.shutdown.load(Ordering::Relaxed)
+    }
+}
+
+pub(crate) fn process(
+    node_processor: Option<Arc<dyn NodeProcessor>>,
+    shutdown: Arc<AtomicBool>,
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    custom_node: &CustomNode,
+) -> Result<Vec<Arc<SlotData>>> {
+    let node_processor =
+        node_processor.ok_or_else(|| TexProError::UnknownNodeType(custom_node.type_id.clone()))?;
+
+    // Parameters of the wrong type, like from an older version of the node type, are ignored.
+    let parameters = node_processor
+        .parameters()
+        .into_iter()
+        .map(|parameter| {
+            let value = match custom_node.parameters.get(&parameter.name) {
+                Some(value) if value.same_kind(&parameter.default) => value.clone(),
+                _ => parameter.default,
+            };
+            (parameter.name, value)
+        })
+        .collect();
+
+    let context = ProcessContext {
+        node,
+        custom_node,
+        slot_datas,
+        parameters,
+        size: output_size(slot_datas, node)?,
+        shutdown: &shutdown,
+    };
+
+    let slot_images = node_processor.process(&context)?;
+    if context.is_canceled() {
+        return Err(TexProError::Canceled);
+    }
+
+    // A buggy `NodeProcessor` could otherwise hand images to the next nodes that don't match
+    // their input slots.
+    if slot_images.len() != custom_node.output_slots.len() {
+        return Err(TexProError::InvalidBufferCount);
+    }
+    for (slot_image, slot) in slot_images.iter().zip(&custom_node.output_slots) {
+        if slot_image.size()? != context.size {
+            return Err(TexProError::InvalidBufferCount);
+        }
+        match (slot.slot_type, slot_image.is_rgba()) {
+            (SlotType::Gray, true) | (SlotType::Rgba, false) => {
+                return Err(TexProError::InvalidSlotType)
+            }
+            _ => (),
+        }
+    }
+
+    Ok(slot_images
+        .into_iter()
+        .enumerate()
+        .map(|(i, slot_image)| Arc::new(SlotData::new(node.node_id, SlotId(i as u32), slot_image)))
+        .collect())
+}
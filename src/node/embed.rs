@@ -3,7 +3,7 @@ use std::sync::Arc;
 use crate::{
     error::{Result, TexProError},
     node_graph::SlotId,
-    slot_data::SlotData,
+    slot_data::{AlphaMode, SlotData},
     slot_image::SlotImage,
 };
 
@@ -18,6 +18,7 @@ pub struct EmbeddedSlotData {
     pub slot_data_id: EmbeddedSlotDataId,
     pub slot_id: SlotId,
     pub(crate) image: SlotImage,
+    pub alpha_mode: AlphaMode,
 }
 
 impl EmbeddedSlotData {
@@ -26,6 +27,7 @@ impl EmbeddedSlotData {
             slot_data_id,
             slot_id: slot_data.slot_id,
             image: slot_data.image.clone(),
+            alpha_mode: slot_data.alpha_mode,
         }
     }
 }
@@ -39,11 +41,10 @@ pub(crate) fn process(
         .iter()
         .find(|end| end.slot_data_id == embedded_node_data_id)
     {
-        Ok(vec![Arc::new(SlotData::new(
-            node.node_id,
-            SlotId(0),
-            enode_data.image.clone(),
-        ))])
+        Ok(vec![Arc::new(
+            SlotData::new(node.node_id, SlotId(0), enode_data.image.clone())
+                .alpha_mode(enode_data.alpha_mode),
+        )])
     } else {
         Err(TexProError::NodeProcessing)
     }
@@ -0,0 +1,841 @@
+use std::{
+    fmt,
+    sync::{atomic::AtomicBool, Arc, RwLock},
+};
+
+use crate::{
+    error::{Result, TexProError},
+    node::process_shared::{cancelling, output_size, slot_data_with_slot_id},
+    node_graph::SlotId,
+    slot_data::{Size, SlotData},
+    slot_image::{Buffer, SlotImage},
+    transient_buffer::{TransientBuffer, TransientBufferContainer},
+};
+
+use super::{grayscale::GrayConversion, Node, SlotType};
+
+use serde::{Deserialize, Serialize};
+
+/// A named input of an `Expression`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ExpressionInput {
+    pub name: String,
+    /// Either `SlotType::Gray` or `SlotType::Rgba`.
+    pub slot_type: SlotType,
+}
+
+/// The settings of a `NodeType::Expression` node, which evaluates `expression` for each pixel.
+///
+/// Each input gets an input slot with the same name, and unconnected inputs are 0. Values are
+/// either gray or RGBA, and when they are combined a gray value is used for all four channels.
+/// All operations work on each channel separately, and the output is RGBA if the result is.
+///
+/// The expressions can contain:
+/// - Numbers, the inputs by name, and `pi`.
+/// - `u` and `v`, the position of the pixel from 0 to 1, and `width` and `height`, the size of
+///   the image in pixels.
+/// - `+`, `-`, `*`, `/` and parentheses.
+/// - The comparisons `<`, `<=`, `>`, `>=`, `==` and `!=`, which are 1 when true and 0 when
+///   false.
+/// - `.r`, `.g`, `.b` and `.a` to get a channel of an RGBA value, and `rgba(r, g, b, a)` to
+///   create one.
+/// - The functions `sin`, `cos`, `tan`, `abs`, `floor`, `ceil`, `fract`, `sqrt`, `exp`, `ln`,
+///   `pow`, `min`, `max`, `step`, `clamp`, `lerp` and `smoothstep`.
+///
+/// For instance `lerp(a, b, smoothstep(0.4, 0.6, mask.r))`.
+///
+/// Without any connected inputs the size of the output is taken from
+/// `ResizePolicy::SpecificSize`, or is 1x1.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(from = "ExpressionFields")]
+pub struct Expression {
+    expression: String,
+    inputs: Vec<ExpressionInput>,
+    /// The result of `check`, or `None` if the expression is invalid.
+    #[serde(skip)]
+    output_type: Option<SlotType>,
+}
+
+#[derive(Deserialize)]
+struct ExpressionFields {
+    expression: String,
+    inputs: Vec<ExpressionInput>,
+}
+
+impl From<ExpressionFields> for Expression {
+    fn from(fields: ExpressionFields) -> Self {
+        Self {
+            expression: fields.expression,
+            inputs: fields.inputs,
+            output_type: None,
+        }
+        .with_output_type()
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl Expression {
+    pub fn new<S: Into<String>>(expression: S) -> Self {
+        Self {
+            expression: expression.into(),
+            inputs: Vec::new(),
+            output_type: None,
+        }
+        .with_output_type()
+    }
+
+    pub fn input<S: Into<String>>(mut self, name: S, slot_type: SlotType) -> Self {
+        self.inputs.push(ExpressionInput {
+            name: name.into(),
+            slot_type,
+        });
+        self.with_output_type()
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    pub fn inputs(&self) -> &[ExpressionInput] {
+        &self.inputs
+    }
+
+    /// Parses and type checks the expression, and returns the type of its result.
+    pub fn check(&self) -> Result<SlotType> {
+        Ok(self.compile()?.slot_type)
+    }
+
+    /// The type of the result, or `None` if the expression is invalid.
+    ///
+    /// Unlike `check` this doesn't compile the expression again.
+    pub fn output_type(&self) -> Option<SlotType> {
+        self.output_type
+    }
+
+    fn with_output_type(mut self) -> Self {
+        self.output_type = self.check().ok();
+        self
+    }
+
+    fn compile(&self) -> Result<Program> {
+        for (i, input) in self.inputs.iter().enumerate() {
+            let mut chars = input.name.chars();
+            if !matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+                || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                return Err(invalid(format!(
+                    "`{}` is not a valid input name",
+                    input.name
+                )));
+            }
+            if is_reserved(&input.name) {
+                return Err(invalid(format!("`{}` is a reserved name", input.name)));
+            }
+            if self.inputs[..i]
+                .iter()
+                .any(|other| other.name == input.name)
+            {
+                return Err(invalid(format!(
+                    "there are several `{}` inputs",
+                    input.name
+                )));
+            }
+            if input.slot_type == SlotType::GrayOrRgba {
+                return Err(invalid(format!(
+                    "the `{}` input has to be either gray or RGBA",
+                    input.name
+                )));
+            }
+        }
+
+        let ast = Parser::new(tokenize(&self.expression)?).parse()?;
+        let mut ops = Vec::new();
+        let slot_type = self.compile_ast(&ast, &mut ops)?;
+
+        Ok(Program { ops, slot_type })
+    }
+
+    /// Adds the operations for the `Ast` to `ops` and returns the type of its result.
+    fn compile_ast(&self, ast: &Ast, ops: &mut Vec<Op>) -> Result<SlotType> {
+        Ok(match ast {
+            Ast::Number(number) => {
+                ops.push(Op::Constant(*number));
+                SlotType::Gray
+            }
+            Ast::Variable(name) => match name.as_str() {
+                "u" => {
+                    ops.push(Op::U);
+                    SlotType::Gray
+                }
+                "v" => {
+                    ops.push(Op::V);
+                    SlotType::Gray
+                }
+                "width" => {
+                    ops.push(Op::Width);
+                    SlotType::Gray
+                }
+                "height" => {
+                    ops.push(Op::Height);
+                    SlotType::Gray
+                }
+                "pi" => {
+                    ops.push(Op::Constant(std::f32::consts::PI));
+                    SlotType::Gray
+                }
+                _ => {
+                    let index = self
+                        .inputs
+                        .iter()
+                        .position(|input| &input.name == name)
+                        .ok_or_else(|| invalid(format!("unknown name `{}`", name)))?;
+                    ops.push(Op::Input(index));
+                    self.inputs[index].slot_type
+                }
+            },
+            Ast::Channel(ast, channel) => {
+                if self.compile_ast(ast, ops)? != SlotType::Rgba {
+                    return Err(invalid(format!(
+                        "`.{}` can only be used on RGBA values",
+                        CHANNELS[*channel]
+                    )));
+                }
+                ops.push(Op::Channel(*channel));
+                SlotType::Gray
+            }
+            Ast::Negate(ast) => {
+                let slot_type = self.compile_ast(ast, ops)?;
+                ops.push(Op::Negate);
+                slot_type
+            }
+            Ast::Binary(operator, left, right) => {
+                let left = self.compile_ast(left, ops)?;
+                let right = self.compile_ast(right, ops)?;
+                ops.push(Op::Binary(*operator));
+                combined_type(&[left, right])
+            }
+            Ast::Call(name, arguments) => {
+                let (op, arity) = if name == "rgba" {
+                    (Op::Rgba, 4)
+                } else {
+                    let function = Function::from_name(name)
+                        .ok_or_else(|| invalid(format!("unknown function `{}`", name)))?;
+                    (Op::Call(function), function.arity())
+                };
+                if arguments.len() != arity {
+                    return Err(invalid(format!(
+                        "`{}` takes {} arguments but got {}",
+                        name,
+                        arity,
+                        arguments.len()
+                    )));
+                }
+
+                let slot_types = arguments
+                    .iter()
+                    .map(|argument| self.compile_ast(argument, ops))
+                    .collect::<Result<Vec<SlotType>>>()?;
+                ops.push(op);
+
+                if op == Op::Rgba {
+                    if slot_types.contains(&SlotType::Rgba) {
+                        return Err(invalid("the arguments of `rgba` have to be gray".into()));
+                    }
+                    SlotType::Rgba
+                } else {
+                    combined_type(&slot_types)
+                }
+            }
+        })
+    }
+}
+
+fn invalid(message: String) -> TexProError {
+    TexProError::InvalidExpression(message)
+}
+
+fn is_reserved(name: &str) -> bool {
+    matches!(name, "u" | "v" | "width" | "height" | "pi" | "rgba")
+        || Function::from_name(name).is_some()
+}
+
+fn combined_type(slot_types: &[SlotType]) -> SlotType {
+    if slot_types.contains(&SlotType::Rgba) {
+        SlotType::Rgba
+    } else {
+        SlotType::Gray
+    }
+}
+
+const CHANNELS: [&str; 4] = ["r", "g", "b", "a"];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Name(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 14] = [
+    "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "(", ")", ",", ".",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars = source.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit()
+            || (c == '.' && matches!(chars.get(i + 1), Some(c) if c.is_ascii_digit()))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                i += 1;
+                if i < chars.len() && (chars[i] == '+' || chars[i] == '-') {
+                    i += 1;
+                }
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+
+            let number = chars[start..i].iter().collect::<String>();
+            tokens.push(Token::Number(number.parse().map_err(|_| {
+                invalid(format!("`{}` is not a valid number", number))
+            })?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| {
+                    symbol
+                        .chars()
+                        .enumerate()
+                        .all(|(offset, c)| chars.get(i + offset) == Some(&c))
+                })
+                .ok_or_else(|| invalid(format!("unexpected character `{}`", c)))?;
+
+            i += symbol.len();
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl BinaryOperator {
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        Some(match symbol {
+            "+" => Self::Add,
+            "-" => Self::Subtract,
+            "*" => Self::Multiply,
+            "/" => Self::Divide,
+            "<" => Self::Less,
+            "<=" => Self::LessOrEqual,
+            ">" => Self::Greater,
+            ">=" => Self::GreaterOrEqual,
+            "==" => Self::Equal,
+            "!=" => Self::NotEqual,
+            _ => return None,
+        })
+    }
+
+    /// Lower numbers bind looser.
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Less
+            | Self::LessOrEqual
+            | Self::Greater
+            | Self::GreaterOrEqual
+            | Self::Equal
+            | Self::NotEqual => 0,
+            Self::Add | Self::Subtract => 1,
+            Self::Multiply | Self::Divide => 2,
+        }
+    }
+
+    fn apply(self, left: f32, right: f32) -> f32 {
+        let boolean = |value: bool| if value { 1.0 } else { 0.0 };
+
+        match self {
+            Self::Add => left + right,
+            Self::Subtract => left - right,
+            Self::Multiply => left * right,
+            Self::Divide => left / right,
+            Self::Less => boolean(left < right),
+            Self::LessOrEqual => boolean(left <= right),
+            Self::Greater => boolean(left > right),
+            Self::GreaterOrEqual => boolean(left >= right),
+            Self::Equal => boolean(left == right),
+            Self::NotEqual => boolean(left != right),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Abs,
+    Floor,
+    Ceil,
+    Fract,
+    Sqrt,
+    Exp,
+    Ln,
+    Pow,
+    Min,
+    Max,
+    Step,
+    Clamp,
+    Lerp,
+    Smoothstep,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sin" => Self::Sin,
+            "cos" => Self::Cos,
+            "tan" => Self::Tan,
+            "abs" => Self::Abs,
+            "floor" => Self::Floor,
+            "ceil" => Self::Ceil,
+            "fract" => Self::Fract,
+            "sqrt" => Self::Sqrt,
+            "exp" => Self::Exp,
+            "ln" => Self::Ln,
+            "pow" => Self::Pow,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "step" => Self::Step,
+            "clamp" => Self::Clamp,
+            "lerp" => Self::Lerp,
+            "smoothstep" => Self::Smoothstep,
+            _ => return None,
+        })
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Self::Sin
+            | Self::Cos
+            | Self::Tan
+            | Self::Abs
+            | Self::Floor
+            | Self::Ceil
+            | Self::Fract
+            | Self::Sqrt
+            | Self::Exp
+            | Self::Ln => 1,
+            Self::Pow | Self::Min | Self::Max | Self::Step => 2,
+            Self::Clamp | Self::Lerp | Self::Smoothstep => 3,
+        }
+    }
+
+    fn apply(self, a: &[f32; 3]) -> f32 {
+        match self {
+            Self::Sin => a[0].sin(),
+            Self::Cos => a[0].cos(),
+            Self::Tan => a[0].tan(),
+            Self::Abs => a[0].abs(),
+            Self::Floor => a[0].floor(),
+            Self::Ceil => a[0].ceil(),
+            Self::Fract => a[0] - a[0].floor(),
+            Self::Sqrt => a[0].sqrt(),
+            Self::Exp => a[0].exp(),
+            Self::Ln => a[0].ln(),
+            Self::Pow => a[0].powf(a[1]),
+            Self::Min => a[0].min(a[1]),
+            Self::Max => a[0].max(a[1]),
+            Self::Step => {
+                if a[1] < a[0] {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+            Self::Clamp => a[0].max(a[1]).min(a[2]),
+            Self::Lerp => a[0] + (a[1] - a[0]) * a[2],
+            Self::Smoothstep => {
+                let t = ((a[2] - a[0]) / (a[1] - a[0])).clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Ast {
+    Number(f32),
+    Variable(String),
+    Channel(Box<Ast>, usize),
+    Negate(Box<Ast>),
+    Binary(BinaryOperator, Box<Ast>, Box<Ast>),
+    Call(String, Vec<Ast>),
+}
+
+/// How deeply expressions can be nested. The parser recurses for each level, so deeper
+/// expressions are an error instead of overflowing the stack.
+const MAX_DEPTH: usize = 256;
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens,
+            position: 0,
+            depth: 0,
+        }
+    }
+
+    fn parse(mut self) -> Result<Ast> {
+        let ast = self.binary(0)?;
+
+        match self.peek() {
+            None => Ok(ast),
+            Some(token) => Err(unexpected(token)),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| invalid("unexpected end of expression".into()))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(token) => Err(unexpected(token)),
+                None => Err(invalid(format!("expected `{}`", symbol))),
+            }
+        }
+    }
+
+    /// Parses binary operations that bind at least as tight as `precedence`.
+    fn binary(&mut self, precedence: u8) -> Result<Ast> {
+        let mut left = self.unary()?;
+
+        while let Some(Token::Symbol(symbol)) = self.peek() {
+            let operator = match BinaryOperator::from_symbol(symbol) {
+                Some(operator) if operator.precedence() >= precedence => operator,
+                _ => break,
+            };
+            self.position += 1;
+
+            let right = self.binary(operator.precedence() + 1)?;
+            left = Ast::Binary(operator, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    /// All recursion goes through here, so this is where the nesting depth is limited.
+    fn unary(&mut self) -> Result<Ast> {
+        if self.depth == MAX_DEPTH {
+            return Err(invalid("the expression is nested too deeply".into()));
+        }
+        self.depth += 1;
+
+        let ast = if self.eat("-") {
+            self.unary().map(|ast| Ast::Negate(Box::new(ast)))
+        } else {
+            self.postfix()
+        };
+
+        self.depth -= 1;
+        ast
+    }
+
+    fn postfix(&mut self) -> Result<Ast> {
+        let mut ast = self.primary()?;
+
+        while self.eat(".") {
+            let channel = match self.next()? {
+                Token::Name(name) => CHANNELS
+                    .iter()
+                    .position(|channel| *channel == name)
+                    .ok_or_else(|| invalid(format!("unknown channel `{}`", name)))?,
+                token => return Err(unexpected(&token)),
+            };
+            ast = Ast::Channel(Box::new(ast), channel);
+        }
+
+        Ok(ast)
+    }
+
+    fn primary(&mut self) -> Result<Ast> {
+        match self.next()? {
+            Token::Number(number) => Ok(Ast::Number(number)),
+            Token::Name(name) => {
+                if self.eat("(") {
+                    let mut arguments = Vec::new();
+                    if !self.eat(")") {
+                        loop {
+                            arguments.push(self.binary(0)?);
+                            if self.eat(")") {
+                                break;
+                            }
+                            self.expect(",")?;
+                        }
+                    }
+                    Ok(Ast::Call(name, arguments))
+                } else {
+                    Ok(Ast::Variable(name))
+                }
+            }
+            Token::Symbol("(") => {
+                let ast = self.binary(0)?;
+                self.expect(")")?;
+                Ok(ast)
+            }
+            token => Err(unexpected(&token)),
+        }
+    }
+}
+
+fn unexpected(token: &Token) -> TexProError {
+    invalid(match token {
+        Token::Number(number) => format!("unexpected number `{}`", number),
+        Token::Name(name) => format!("unexpected name `{}`", name),
+        Token::Symbol(symbol) => format!("unexpected `{}`", symbol),
+    })
+}
+
+/// An operation on the stack of values used when evaluating an expression.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Constant(f32),
+    Input(usize),
+    U,
+    V,
+    Width,
+    Height,
+    Channel(usize),
+    Negate,
+    Binary(BinaryOperator),
+    Call(Function),
+    Rgba,
+}
+
+/// Gray values have the same value in all four channels.
+type Value = [f32; 4];
+
+/// A compiled expression, a list of operations in postfix order.
+struct Program {
+    ops: Vec<Op>,
+    slot_type: SlotType,
+}
+
+impl Program {
+    fn evaluate(
+        &self,
+        stack: &mut Vec<Value>,
+        inputs: &[Vec<&Buffer>],
+        size: Size,
+        x: u32,
+        y: u32,
+    ) -> Value {
+        stack.clear();
+
+        for op in &self.ops {
+            let value = match *op {
+                Op::Constant(value) => [value; 4],
+                Op::Input(index) => {
+                    let input = &inputs[index];
+                    let channel = |i: usize| input[i.min(input.len() - 1)].get_pixel(x, y).0[0];
+                    [channel(0), channel(1), channel(2), channel(3)]
+                }
+                Op::U => [x as f32 / size.width as f32; 4],
+                Op::V => [y as f32 / size.height as f32; 4],
+                Op::Width => [size.width as f32; 4],
+                Op::Height => [size.height as f32; 4],
+                Op::Channel(channel) => {
+                    let value = stack.pop().unwrap();
+                    [value[channel]; 4]
+                }
+                Op::Negate => {
+                    let value = stack.pop().unwrap();
+                    [-value[0], -value[1], -value[2], -value[3]]
+                }
+                Op::Binary(operator) => {
+                    let right = stack.pop().unwrap();
+                    let left = stack.pop().unwrap();
+                    let channel = |i: usize| operator.apply(left[i], right[i]);
+                    [channel(0), channel(1), channel(2), channel(3)]
+                }
+                Op::Call(function) => {
+                    let arity = function.arity();
+                    let arguments = &stack[stack.len() - arity..];
+                    let channel = |i: usize| {
+                        let mut channel_arguments = [0.0; 3];
+                        for (argument, value) in channel_arguments.iter_mut().zip(arguments) {
+                            *argument = value[i];
+                        }
+                        function.apply(&channel_arguments)
+                    };
+                    let value = [channel(0), channel(1), channel(2), channel(3)];
+
+                    stack.truncate(stack.len() - arity);
+                    value
+                }
+                Op::Rgba => {
+                    let arguments = &stack[stack.len() - 4..];
+                    let value = [
+                        arguments[0][0],
+                        arguments[1][0],
+                        arguments[2][0],
+                        arguments[3][0],
+                    ];
+
+                    stack.truncate(stack.len() - 4);
+                    value
+                }
+            };
+
+            stack.push(value);
+        }
+
+        stack.pop().unwrap()
+    }
+}
+
+pub(crate) fn process(
+    shutdown: Arc<AtomicBool>,
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    expression: &Expression,
+    gray_conversion: GrayConversion,
+) -> Result<Vec<Arc<SlotData>>> {
+    let program = expression.compile()?;
+
+    let size = output_size(slot_datas, node)?;
+
+    let images = expression
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            let rgba = input.slot_type == SlotType::Rgba;
+            match slot_data_with_slot_id(slot_datas, SlotId(i as u32)) {
+                Some(slot_data) if rgba => slot_data.straight_image().as_type(true),
+                Some(slot_data) => slot_data.straight_image().to_gray(gray_conversion),
+                None => Ok(SlotImage::from_value(size, 0.0, rgba)),
+            }
+        })
+        .collect::<Result<Vec<SlotImage>>>()?;
+
+    let channel_count = if program.slot_type == SlotType::Rgba {
+        4
+    } else {
+        1
+    };
+    let mut channels = vec![Vec::with_capacity(size.pixel_count()); channel_count];
+
+    {
+        let containers = images.iter().map(SlotImage::bufs).collect::<Vec<_>>();
+        let transient_buffers = containers
+            .iter()
+            .map(|bufs| {
+                bufs.iter()
+                    .map(|tbc| tbc.transient_buffer())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let inputs = transient_buffers
+            .iter()
+            .map(|bufs| bufs.iter().map(|tb| tb.buffer()).collect::<Vec<&Buffer>>())
+            .collect::<Vec<_>>();
+
+        let mut stack = Vec::with_capacity(program.ops.len());
+        for y in 0..size.height {
+            if cancelling(&node.cancel, &shutdown) {
+                return Err(TexProError::Canceled);
+            }
+
+            for x in 0..size.width {
+                let value = program.evaluate(&mut stack, &inputs, size, x, y);
+                for (channel, value) in channels.iter_mut().zip(value.iter()) {
+                    channel.push(*value);
+                }
+            }
+        }
+    }
+
+    let mut buffers = channels
+        .into_iter()
+        .map(|channel| Buffer::from_raw(size.width, size.height, channel).unwrap())
+        .collect::<Vec<Buffer>>();
+
+    let slot_image = if program.slot_type == SlotType::Rgba {
+        SlotImage::from_buffers_rgba(&mut buffers)?
+    } else {
+        SlotImage::Gray(Arc::new(TransientBufferContainer::new(Arc::new(
+            RwLock::new(TransientBuffer::new(Box::new(buffers.remove(0)))),
+        ))))
+    };
+
+    Ok(vec![Arc::new(SlotData::new(
+        node.node_id,
+        SlotId(0),
+        slot_image,
+    ))])
+}
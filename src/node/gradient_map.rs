@@ -0,0 +1,182 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    error::Result,
+    node::process_shared::slot_data_with_name,
+    node_graph::SlotId,
+    slot_data::SlotData,
+    slot_image::{Buffer, SlotImage},
+};
+
+use super::Node;
+
+use image::Luma;
+use serde::{Deserialize, Serialize};
+
+/// A color at a position on a `GradientMap` ramp. The color has straight alpha.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct GradientStop {
+    pub position: f32,
+    pub color: [f32; 4],
+}
+
+impl GradientStop {
+    pub fn new(position: f32, color: [f32; 4]) -> Self {
+        Self { position, color }
+    }
+}
+
+/// How colors change between the stops of a `GradientMap`.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GradientInterpolation {
+    Linear,
+    /// Each stop's color is kept until the next stop.
+    Constant,
+    /// Like `Linear`, but eases in and out of each stop.
+    Smooth,
+}
+
+impl Default for GradientInterpolation {
+    fn default() -> Self {
+        Self::Linear
+    }
+}
+
+impl fmt::Display for GradientInterpolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Linear => write!(f, "Linear"),
+            Self::Constant => write!(f, "Constant"),
+            Self::Smooth => write!(f, "Smooth"),
+        }
+    }
+}
+
+/// The settings of a `NodeType::GradientMap` node, which maps a gray input to the colors of a
+/// ramp.
+///
+/// Values before the first stop get its color, and values after the last stop get its color.
+/// The stops don't need to be sorted. Colors are interpolated in the linear working space.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct GradientMap {
+    pub stops: Vec<GradientStop>,
+    pub interpolation: GradientInterpolation,
+    /// Whether the output gets the alpha of the ramp, otherwise it's opaque.
+    pub alpha: bool,
+}
+
+impl Default for GradientMap {
+    fn default() -> Self {
+        Self::new(vec![
+            GradientStop::new(0.0, [0.0, 0.0, 0.0, 1.0]),
+            GradientStop::new(1.0, [1.0, 1.0, 1.0, 1.0]),
+        ])
+    }
+}
+
+impl GradientMap {
+    pub fn new(stops: Vec<GradientStop>) -> Self {
+        Self {
+            stops,
+            interpolation: GradientInterpolation::default(),
+            alpha: false,
+        }
+    }
+
+    pub fn interpolation(mut self, interpolation: GradientInterpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn alpha(mut self, alpha: bool) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Returns the color of the ramp at a position.
+    pub fn color(&self, position: f32) -> [f32; 4] {
+        color(&sorted_stops(&self.stops), self.interpolation, position)
+    }
+}
+
+fn sorted_stops(stops: &[GradientStop]) -> Vec<GradientStop> {
+    let mut stops = stops
+        .iter()
+        .filter(|stop| stop.position.is_finite())
+        .copied()
+        .collect::<Vec<_>>();
+    stops.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
+    stops
+}
+
+/// Returns the color at a position on a ramp with sorted stops.
+fn color(stops: &[GradientStop], interpolation: GradientInterpolation, position: f32) -> [f32; 4] {
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return [0.0, 0.0, 0.0, 1.0],
+    };
+    if position <= first.position {
+        return first.color;
+    } else if position >= last.position {
+        return last.color;
+    }
+
+    let i = stops.partition_point(|stop| stop.position <= position) - 1;
+    let (a, b) = (stops[i], stops[i + 1]);
+    let t = (position - a.position) / (b.position - a.position);
+    let t = match interpolation {
+        GradientInterpolation::Linear => t,
+        GradientInterpolation::Constant => 0.0,
+        GradientInterpolation::Smooth => t * t * (3.0 - 2.0 * t),
+    };
+
+    let mut color = [0.0; 4];
+    for (channel, value) in color.iter_mut().enumerate() {
+        *value = a.color[channel] + (b.color[channel] - a.color[channel]) * t;
+    }
+    color
+}
+
+pub(crate) fn process(
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    gradient_map: &GradientMap,
+) -> Result<Vec<Arc<SlotData>>> {
+    let slot_data = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data
+    } else {
+        return Ok(Vec::new());
+    };
+
+    let size = slot_data.size()?;
+    let stops = sorted_stops(&gradient_map.stops);
+
+    let mut buffers = {
+        let input = slot_data.image.as_type(false)?.bufs().remove(0);
+        let input = input.transient_buffer();
+        let input = input.buffer();
+
+        let colors = input
+            .pixels()
+            .map(|pixel| color(&stops, gradient_map.interpolation, pixel.0[0]))
+            .collect::<Vec<[f32; 4]>>();
+
+        (0..4)
+            .map(|channel| {
+                Buffer::from_fn(size.width, size.height, |x, y| {
+                    if channel == 3 && !gradient_map.alpha {
+                        Luma([1.0])
+                    } else {
+                        Luma([colors[(y * size.width + x) as usize][channel]])
+                    }
+                })
+            })
+            .collect::<Vec<Buffer>>()
+    };
+
+    Ok(vec![Arc::new(SlotData::new(
+        node.node_id,
+        SlotId(0),
+        SlotImage::from_buffers_rgba(&mut buffers)?,
+    ))])
+}
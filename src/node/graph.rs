@@ -23,11 +23,14 @@ pub(crate) fn process(
 
     // Insert `SlotData`s into the graph TexPro.
     for slot_data in slot_datas {
-        live_graph.add_input_slot_data(Arc::new(SlotData::new(
-            NodeId(slot_data.slot_id.0),
-            SlotId(0),
-            slot_data.image.clone(),
-        )));
+        live_graph.add_input_slot_data(Arc::new(
+            SlotData::new(
+                NodeId(slot_data.slot_id.0),
+                SlotId(0),
+                slot_data.image.clone(),
+            )
+            .alpha_mode(slot_data.alpha_mode),
+        ));
     }
 
     let live_graph = Arc::new(RwLock::new(live_graph));
@@ -42,7 +45,8 @@ pub(crate) fn process(
                 node.node_id,
                 SlotId(output_node_id.0),
                 slot_data.image.clone(),
-            );
+            )
+            .alpha_mode(slot_data.alpha_mode);
             output.push(Arc::new(output_node_data));
         }
     }
@@ -0,0 +1,78 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    error::Result, node::process_shared::slot_data_with_name, node_graph::SlotId,
+    slot_data::SlotData,
+};
+
+use super::Node;
+
+use serde::{Deserialize, Serialize};
+
+/// How the red, green and blue channels of an image are turned into a single gray channel.
+/// Alpha is not taken into account.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GrayConversion {
+    /// The luminance of linear sRGB colors, as in Rec. 709.
+    Rec709,
+    /// The luma weights from Rec. 601.
+    Rec601,
+    Average,
+    /// The brightest of the three channels.
+    Max,
+    Red,
+    Green,
+    Blue,
+}
+
+impl Default for GrayConversion {
+    fn default() -> Self {
+        Self::Average
+    }
+}
+
+impl fmt::Display for GrayConversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rec709 => write!(f, "Rec. 709"),
+            Self::Rec601 => write!(f, "Rec. 601"),
+            Self::Average => write!(f, "Average"),
+            Self::Max => write!(f, "Max"),
+            Self::Red => write!(f, "Red"),
+            Self::Green => write!(f, "Green"),
+            Self::Blue => write!(f, "Blue"),
+        }
+    }
+}
+
+impl GrayConversion {
+    pub fn gray(self, [r, g, b]: [f32; 3]) -> f32 {
+        match self {
+            Self::Rec709 => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+            Self::Rec601 => 0.299 * r + 0.587 * g + 0.114 * b,
+            Self::Average => (r + g + b) / 3.,
+            Self::Max => r.max(g).max(b),
+            Self::Red => r,
+            Self::Green => g,
+            Self::Blue => b,
+        }
+    }
+}
+
+pub(crate) fn process(
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    gray_conversion: GrayConversion,
+) -> Result<Vec<Arc<SlotData>>> {
+    let image = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data.straight_image()
+    } else {
+        return Ok(Vec::new());
+    };
+
+    Ok(vec![Arc::new(SlotData::new(
+        node.node_id,
+        SlotId(0),
+        image.to_gray(gray_conversion)?,
+    ))])
+}
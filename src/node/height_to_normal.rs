@@ -1,4 +1,7 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{
+    fmt,
+    sync::{atomic::AtomicBool, Arc},
+};
 
 use crate::{
     error::{Result, TexProError},
@@ -12,11 +15,139 @@ use super::Node;
 
 use image::{ImageBuffer, Luma};
 use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// How the slopes of a height map are found.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NormalFilter {
+    /// The difference to the pixel before, which is sharp but shifted by half a pixel.
+    Backward,
+    /// The difference between the pixels on both sides.
+    Central,
+    /// Like `Central`, but smoothed over the neighboring rows or columns.
+    Sobel,
+    /// Like `Sobel`, but gives the same result in all directions.
+    Scharr,
+}
+
+impl Default for NormalFilter {
+    fn default() -> Self {
+        Self::Backward
+    }
+}
+
+impl fmt::Display for NormalFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Backward => write!(f, "Backward"),
+            Self::Central => write!(f, "Central"),
+            Self::Sobel => write!(f, "Sobel"),
+            Self::Scharr => write!(f, "Scharr"),
+        }
+    }
+}
+
+impl NormalFilter {
+    /// The weights of the rows above, at and below a pixel, for filters that smooth across
+    /// the slope.
+    fn weights(self) -> [f32; 3] {
+        match self {
+            Self::Backward | Self::Central => [0.0, 1.0, 0.0],
+            Self::Sobel => [1.0, 2.0, 1.0],
+            Self::Scharr => [3.0, 10.0, 3.0],
+        }
+    }
+}
+
+/// Which way the green channel of a normal map points.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NormalFormat {
+    /// Green points up, as in OpenGL, Blender and Unity.
+    OpenGl,
+    /// Green points down, as in DirectX and Unreal Engine.
+    DirectX,
+}
+
+impl Default for NormalFormat {
+    fn default() -> Self {
+        Self::OpenGl
+    }
+}
+
+impl fmt::Display for NormalFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OpenGl => write!(f, "OpenGL"),
+            Self::DirectX => write!(f, "DirectX"),
+        }
+    }
+}
+
+/// The settings of a `NodeType::HeightToNormal` node, which makes a tangent space normal map
+/// from a height map. The height map wraps around, so the result tiles if the height map does.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct HeightToNormal {
+    /// How tall a height of 1 is, where 1 is as tall as the image is wide.
+    pub intensity: f32,
+    pub filter: NormalFilter,
+    pub format: NormalFormat,
+    /// Outputs the normals as they are, from -1 to 1, instead of remapped to 0 to 1 like they
+    /// are stored in images.
+    pub signed: bool,
+}
+
+impl Default for HeightToNormal {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            filter: NormalFilter::default(),
+            format: NormalFormat::default(),
+            signed: false,
+        }
+    }
+}
+
+impl fmt::Display for HeightToNormal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Intensity {}, {}, {}",
+            self.intensity, self.filter, self.format
+        )?;
+        if self.signed {
+            write!(f, ", signed")?;
+        }
+        Ok(())
+    }
+}
+
+impl HeightToNormal {
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn filter(mut self, filter: NormalFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn format(mut self, format: NormalFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn signed(mut self, signed: bool) -> Self {
+        self.signed = signed;
+        self
+    }
+}
 
 pub(crate) fn process(
     shutdown: Arc<AtomicBool>,
     slot_datas: &[Arc<SlotData>],
     node: &Node,
+    height_to_normal: HeightToNormal,
 ) -> Result<Vec<Arc<SlotData>>> {
     let slot_data = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
         slot_data
@@ -26,8 +157,12 @@ pub(crate) fn process(
 
     let size = slot_data.size()?;
     let (width, height) = (size.width, size.height);
-    let pixel_distance_x = 1. / width as f32;
-    let pixel_distance_y = 1. / height as f32;
+    let weights = height_to_normal.filter.weights();
+    let weight_sum = weights.iter().sum::<f32>();
+    let green_sign = match height_to_normal.format {
+        NormalFormat::OpenGl => 1.0,
+        NormalFormat::DirectX => -1.0,
+    };
 
     let mut buffer_normal: [Buffer; 3] = [
         ImageBuffer::new(width, height),
@@ -52,15 +187,47 @@ pub(crate) fn process(
         }
 
         for (x, y, px) in buffer_iterator.take_while(|_| !cancelling(&node.cancel, &shutdown)) {
-            let sample_up = buffer_height.get_pixel(x, y.wrapping_sample_subtract(1, height))[0];
-            let sample_left = buffer_height.get_pixel(x.wrapping_sample_subtract(1, width), y)[0];
+            let (left, right) = (
+                x.wrapping_sample_subtract(1, width),
+                x.wrapping_sample_add(1, width),
+            );
+            let (up, down) = (
+                y.wrapping_sample_subtract(1, height),
+                y.wrapping_sample_add(1, height),
+            );
+
+            // The slopes in pixels, going right and down.
+            let (slope_x, slope_y) = if height_to_normal.filter == NormalFilter::Backward {
+                (
+                    px[0] - buffer_height.get_pixel(left, y)[0],
+                    px[0] - buffer_height.get_pixel(x, up)[0],
+                )
+            } else {
+                let sample = |x, y| buffer_height.get_pixel(x, y)[0];
+                let slope_x = weights[0] * (sample(right, up) - sample(left, up))
+                    + weights[1] * (sample(right, y) - sample(left, y))
+                    + weights[2] * (sample(right, down) - sample(left, down));
+                let slope_y = weights[0] * (sample(left, down) - sample(left, up))
+                    + weights[1] * (sample(x, down) - sample(x, up))
+                    + weights[2] * (sample(right, down) - sample(right, up));
+
+                (slope_x / (2.0 * weight_sum), slope_y / (2.0 * weight_sum))
+            };
 
-            let tangent = vec3(pixel_distance_x, 0., px[0] - sample_left).normalize();
-            let bitangent = vec3(0., pixel_distance_y, sample_up - px[0]).normalize();
-            let normal = tangent.cross(&bitangent).normalize();
+            let normal = vec3(
+                -slope_x * width as f32 * height_to_normal.intensity,
+                slope_y * height as f32 * height_to_normal.intensity * green_sign,
+                1.0,
+            )
+            .normalize();
 
             for (i, buffer) in buffer_normal.iter_mut().enumerate() {
-                buffer.put_pixel(x, y, Luma([normal[i] * 0.5 + 0.5]));
+                let value = if height_to_normal.signed {
+                    normal[i]
+                } else {
+                    normal[i] * 0.5 + 0.5
+                };
+                buffer.put_pixel(x, y, Luma([value]));
             }
         }
     }
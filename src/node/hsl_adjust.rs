@@ -0,0 +1,92 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    error::Result,
+    node::{
+        convert_color::{hsl_to_rgb, rgb_to_hsl},
+        process_shared::{map_colors, slot_data_with_name},
+    },
+    node_graph::SlotId,
+    slot_data::SlotData,
+};
+
+use super::Node;
+
+use serde::{Deserialize, Serialize};
+
+/// The settings of a `NodeType::HslAdjust` node, which shifts the hue, saturation and
+/// lightness of each pixel. Alpha is kept as it is.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct HslAdjust {
+    /// How far around the color wheel the hue is rotated, where 1 is a full turn.
+    pub hue_offset: f32,
+    /// Added to the saturation, so -1 makes the image gray.
+    pub saturation: f32,
+    /// Added to the lightness, so -1 makes the image black and 1 makes it white.
+    pub lightness: f32,
+}
+
+impl Default for HslAdjust {
+    fn default() -> Self {
+        Self {
+            hue_offset: 0.0,
+            saturation: 0.0,
+            lightness: 0.0,
+        }
+    }
+}
+
+impl fmt::Display for HslAdjust {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "H {:+}, S {:+}, L {:+}",
+            self.hue_offset, self.saturation, self.lightness
+        )
+    }
+}
+
+impl HslAdjust {
+    pub fn hue_offset(mut self, hue_offset: f32) -> Self {
+        self.hue_offset = hue_offset;
+        self
+    }
+
+    pub fn saturation(mut self, saturation: f32) -> Self {
+        self.saturation = saturation;
+        self
+    }
+
+    pub fn lightness(mut self, lightness: f32) -> Self {
+        self.lightness = lightness;
+        self
+    }
+
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let [hue, saturation, lightness] = rgb_to_hsl(rgb);
+
+        hsl_to_rgb([
+            (hue + self.hue_offset).rem_euclid(1.0),
+            (saturation + self.saturation).clamp(0.0, 1.0),
+            (lightness + self.lightness).clamp(0.0, 1.0),
+        ])
+    }
+}
+
+pub(crate) fn process(
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    hsl_adjust: HslAdjust,
+) -> Result<Vec<Arc<SlotData>>> {
+    let image = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data.straight_image()
+    } else {
+        return Ok(Vec::new());
+    };
+
+    Ok(vec![Arc::new(SlotData::new(
+        node.node_id,
+        SlotId(0),
+        map_colors(&image, |color| hsl_adjust.apply(color))?,
+    ))])
+}
@@ -1,22 +1,26 @@
-use std::{path::Path, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::{
-    error::Result, node_graph::SlotId, shared::read_slot_image, slot_data::SlotData,
+    error::{Result, TexProError},
+    node_graph::{resolve_path, NodeId, SlotId},
+    shared::read_slot_image,
+    slot_data::SlotData,
     slot_image::SlotImage,
 };
 
 use super::{pixel_buffer, Node};
 
-pub(crate) fn process(node: &Node, path: &Path) -> Result<Vec<Arc<SlotData>>> {
-    let slot_image = match read_slot_image(path) {
-        Ok(slot_image) => slot_image,
-        Err(_) => SlotImage::Rgba([
-            pixel_buffer(1.0),
-            pixel_buffer(0.0),
-            pixel_buffer(1.0),
-            pixel_buffer(1.0),
-        ]),
-    };
+pub(crate) fn process(
+    node: &Node,
+    path: &Path,
+    search_paths: &[PathBuf],
+) -> Result<Vec<Arc<SlotData>>> {
+    let resolved = resolve_path(path, search_paths);
+    let slot_image = read_slot_image(resolved.as_deref().unwrap_or(path))
+        .map_err(|e| TexProError::ReadImage(path.to_path_buf(), Box::new(e)))?;
 
     Ok(vec![Arc::new(SlotData::new(
         node.node_id,
@@ -24,3 +28,18 @@ pub(crate) fn process(node: &Node, path: &Path) -> Result<Vec<Arc<SlotData>>> {
         slot_image,
     ))])
 }
+
+/// A 1x1 magenta image used in place of images that could not be read, when
+/// `LiveGraph::missing_image_placeholder` is enabled.
+pub(crate) fn placeholder(node_id: NodeId) -> Vec<Arc<SlotData>> {
+    vec![Arc::new(SlotData::new(
+        node_id,
+        SlotId(0),
+        SlotImage::Rgba([
+            pixel_buffer(1.0),
+            pixel_buffer(0.0),
+            pixel_buffer(1.0),
+            pixel_buffer(1.0),
+        ]),
+    ))]
+}
@@ -0,0 +1,251 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    error::Result,
+    node::process_shared::{map_color_channels, slot_data_with_name},
+    node_graph::SlotId,
+    slot_data::SlotData,
+};
+
+use super::Node;
+
+use serde::{Deserialize, Serialize};
+
+/// Settings that are either used for all color channels, or given separately for red, green
+/// and blue. Alpha is never adjusted, and gray images use the red settings.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub enum ToneChannels<T> {
+    Combined(T),
+    Separate([T; 3]),
+}
+
+impl<T: Default> Default for ToneChannels<T> {
+    fn default() -> Self {
+        Self::Combined(T::default())
+    }
+}
+
+impl<T> ToneChannels<T> {
+    pub fn channel(&self, channel: usize) -> &T {
+        match self {
+            Self::Combined(settings) => settings,
+            Self::Separate(settings) => &settings[channel.min(2)],
+        }
+    }
+
+    pub(crate) fn channel_mut(&mut self, channel: usize) -> &mut T {
+        match self {
+            Self::Combined(settings) => settings,
+            Self::Separate(settings) => &mut settings[channel.min(2)],
+        }
+    }
+}
+
+/// The adjustment of a single channel in a `Levels` node.
+///
+/// Values from `input_black` to `input_white` are remapped to 0 to 1, raised to `1 / gamma`,
+/// and then remapped to `output_black` to `output_white`. Values outside of the input range
+/// are clamped.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct Level {
+    pub input_black: f32,
+    pub input_white: f32,
+    /// Values above 1 brighten the midtones and values below 1 darken them.
+    pub gamma: f32,
+    pub output_black: f32,
+    pub output_white: f32,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            input_black: 0.0,
+            input_white: 1.0,
+            gamma: 1.0,
+            output_black: 0.0,
+            output_white: 1.0,
+        }
+    }
+}
+
+impl Level {
+    pub fn input(mut self, black: f32, white: f32) -> Self {
+        self.input_black = black;
+        self.input_white = white;
+        self
+    }
+
+    pub fn gamma(mut self, gamma: f32) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    pub fn output(mut self, black: f32, white: f32) -> Self {
+        self.output_black = black;
+        self.output_white = white;
+        self
+    }
+
+    pub fn apply(&self, value: f32) -> f32 {
+        let input_range = self.input_white - self.input_black;
+        let value = if input_range.abs() <= f32::EPSILON {
+            if value < self.input_black {
+                0.0
+            } else {
+                1.0
+            }
+        } else {
+            ((value - self.input_black) / input_range).clamp(0.0, 1.0)
+        };
+        let value = value.powf(1.0 / self.gamma.max(f32::EPSILON));
+
+        self.output_black + value * (self.output_white - self.output_black)
+    }
+}
+
+/// Sets the input range of `Levels` from the histogram of the input image, so its darkest
+/// value becomes black and its brightest value becomes white.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct AutoLevels {
+    /// The fraction of the pixels that are allowed to be clipped at each end of the range,
+    /// which keeps a few outliers from deciding the range.
+    pub clip: f32,
+}
+
+impl Default for AutoLevels {
+    fn default() -> Self {
+        Self { clip: 0.001 }
+    }
+}
+
+/// The settings of a `NodeType::Levels` node.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct Levels {
+    pub levels: ToneChannels<Level>,
+    /// With `ToneChannels::Combined` the range is taken from all color channels together,
+    /// otherwise from each channel separately.
+    pub auto_levels: Option<AutoLevels>,
+}
+
+impl fmt::Display for Levels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.levels, self.auto_levels) {
+            (_, Some(_)) => write!(f, "Auto-levels"),
+            (ToneChannels::Combined(_), None) => write!(f, "Levels"),
+            (ToneChannels::Separate(_), None) => write!(f, "Levels per channel"),
+        }
+    }
+}
+
+impl Levels {
+    pub fn new(levels: ToneChannels<Level>) -> Self {
+        Self {
+            levels,
+            auto_levels: None,
+        }
+    }
+
+    pub fn auto_levels(mut self, auto_levels: AutoLevels) -> Self {
+        self.auto_levels = Some(auto_levels);
+        self
+    }
+}
+
+// The resolution of the histogram used for auto-levels.
+const HISTOGRAM_BINS: usize = 1024;
+
+/// Finds the range of the values after clipping the given fraction of them at each end, or
+/// `None` if all values are the same.
+fn histogram_range(values: &[&[f32]], clip: f32) -> Option<(f32, f32)> {
+    let values = || values.iter().flat_map(|values| values.iter().copied());
+
+    let (min, max) = values().fold((f32::MAX, f32::MIN), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+    if max <= min {
+        return None;
+    }
+
+    let scale = (HISTOGRAM_BINS - 1) as f32 / (max - min);
+    let mut histogram = vec![0_usize; HISTOGRAM_BINS];
+    for value in values() {
+        histogram[((value - min) * scale).round() as usize] += 1;
+    }
+
+    let count = histogram.iter().sum::<usize>();
+    let clip_count = (count as f32 * clip.clamp(0.0, 0.5)) as usize;
+    let low = clipped_bin(histogram.iter().enumerate(), clip_count)?;
+    let high = clipped_bin(histogram.iter().enumerate().rev(), clip_count)?;
+    let (low, high) = (min + low as f32 / scale, min + high as f32 / scale);
+
+    if high > low {
+        Some((low, high))
+    } else {
+        None
+    }
+}
+
+/// Returns the first bin after `clip_count` values have been skipped.
+fn clipped_bin<'a, I: Iterator<Item = (usize, &'a usize)>>(
+    bins: I,
+    clip_count: usize,
+) -> Option<usize> {
+    let mut cumulative = 0;
+    bins.map(|(bin, bin_count)| {
+        cumulative += bin_count;
+        (bin, cumulative)
+    })
+    .find(|(_, cumulative)| *cumulative > clip_count)
+    .map(|(bin, _)| bin)
+}
+
+pub(crate) fn process(
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    levels: Levels,
+) -> Result<Vec<Arc<SlotData>>> {
+    let image = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data.straight_image()
+    } else {
+        return Ok(Vec::new());
+    };
+
+    let mut channel_levels = levels.levels;
+
+    if let Some(auto_levels) = levels.auto_levels {
+        let bufs = image.bufs();
+        let color_bufs = &bufs[..bufs.len().min(3)];
+        let transient_buffers = color_bufs
+            .iter()
+            .map(|tbc| tbc.transient_buffer())
+            .collect::<Vec<_>>();
+        let values = transient_buffers
+            .iter()
+            .map(|transient_buffer| transient_buffer.buffer().as_raw().as_slice())
+            .collect::<Vec<&[f32]>>();
+
+        let ranges = match channel_levels {
+            ToneChannels::Combined(_) => vec![histogram_range(&values, auto_levels.clip)],
+            ToneChannels::Separate(_) => values
+                .iter()
+                .map(|values| histogram_range(&[values], auto_levels.clip))
+                .collect(),
+        };
+
+        for (channel, range) in ranges.into_iter().enumerate() {
+            if let Some((low, high)) = range {
+                let level = channel_levels.channel_mut(channel);
+                level.input_black = low;
+                level.input_white = high;
+            }
+        }
+    }
+
+    Ok(vec![Arc::new(SlotData::new(
+        node.node_id,
+        SlotId(0),
+        map_color_channels(&image, |channel, value| {
+            channel_levels.channel(channel).apply(value)
+        }),
+    ))])
+}
@@ -0,0 +1,311 @@
+use std::{
+    fmt,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use crate::{
+    error::{Result, TexProError},
+    node::process_shared::{cancelling, process_channels, slot_data_with_name},
+    slot_data::{Size, SlotData},
+};
+
+use super::Node;
+
+use serde::{Deserialize, Serialize};
+
+/// How a `MakeTile` node hides the seams of an image.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TileMethod {
+    /// Cross-fades towards a copy of the image offset by half its size, whose seams are in the
+    /// middle where they are not blended in.
+    Offset,
+    /// Cross-fades towards a mirrored copy of the image, so both sides of each edge meet
+    /// halfway.
+    Mirror,
+    /// Offsets the image by half its size and covers the seams with patches taken from the
+    /// rest of the image.
+    Patch,
+}
+
+impl Default for TileMethod {
+    fn default() -> Self {
+        Self::Offset
+    }
+}
+
+impl fmt::Display for TileMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Offset => write!(f, "Offset"),
+            Self::Mirror => write!(f, "Mirror"),
+            Self::Patch => write!(f, "Patch"),
+        }
+    }
+}
+
+/// The settings of a `NodeType::MakeTile` node, which makes an image tile seamlessly.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct MakeTile {
+    pub method: TileMethod,
+    /// How far from the edges the blending reaches as a fraction of the image size, from 0 to
+    /// 0.5. For `TileMethod::Patch` it's the radius of the patches.
+    pub blend_width: f32,
+    /// Decides where `TileMethod::Patch` takes its patches from.
+    pub seed: u32,
+}
+
+impl Default for MakeTile {
+    fn default() -> Self {
+        Self {
+            method: TileMethod::default(),
+            blend_width: 0.2,
+            seed: 0,
+        }
+    }
+}
+
+impl fmt::Display for MakeTile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, blend width {}", self.method, self.blend_width)
+    }
+}
+
+impl MakeTile {
+    pub fn new(method: TileMethod) -> Self {
+        Self {
+            method,
+            ..Self::default()
+        }
+    }
+
+    pub fn blend_width(mut self, blend_width: f32) -> Self {
+        self.blend_width = blend_width;
+        self
+    }
+
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+fn smoothstep(edge_0: f32, edge_1: f32, x: f32) -> f32 {
+    if edge_1 <= edge_0 {
+        return if x < edge_0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edge_0) / (edge_1 - edge_0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// The channels of an image as plain values, so they can be sampled in any order.
+struct Channels {
+    values: Vec<Vec<f32>>,
+    size: Size,
+}
+
+impl Channels {
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.size.width + x) as usize
+    }
+
+    /// Returns the channels moved by half the image size, wrapping around the edges.
+    fn offset_half(&self) -> Self {
+        let Size { width, height } = self.size;
+        let values = self
+            .values
+            .iter()
+            .map(|channel| {
+                (0..height)
+                    .flat_map(|y| {
+                        (0..width).map(move |x| {
+                            channel[self.index((x + width / 2) % width, (y + height / 2) % height)]
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            values,
+            size: self.size,
+        }
+    }
+}
+
+/// Blends each pixel along one axis towards the pixel `other` returns, by the weight `weight`
+/// returns for the pixel's distance to the nearest edge as a fraction of the image size.
+fn cross_fade<O, W>(
+    channels: &Channels,
+    vertical: bool,
+    other: O,
+    weight: W,
+    cancel: &dyn Fn() -> bool,
+) -> Result<Channels>
+where
+    O: Fn(u32, u32) -> u32,
+    W: Fn(f32) -> f32,
+{
+    let Size { width, height } = channels.size;
+    let length = if vertical { height } else { width };
+    let mut values = vec![Vec::with_capacity(channels.size.pixel_count()); channels.values.len()];
+
+    for y in 0..height {
+        if cancel() {
+            return Err(TexProError::Canceled);
+        }
+
+        for x in 0..width {
+            let position = if vertical { y } else { x };
+            let distance =
+                (position as f32 + 0.5).min(length as f32 - position as f32 - 0.5) / length as f32;
+            let weight = weight(distance);
+
+            let other = other(position, length);
+            let (other_x, other_y) = if vertical { (x, other) } else { (other, y) };
+            let (index, other_index) = (channels.index(x, y), channels.index(other_x, other_y));
+
+            for (output, channel) in values.iter_mut().zip(channels.values.iter()) {
+                output.push(channel[index] + (channel[other_index] - channel[index]) * weight);
+            }
+        }
+    }
+
+    Ok(Channels {
+        values,
+        size: channels.size,
+    })
+}
+
+/// A small and fast random number generator, it only needs to place patches.
+struct XorShift(u32);
+
+impl XorShift {
+    fn new(seed: u32) -> Self {
+        // Zero would only ever give zeroes.
+        Self(seed.wrapping_mul(0x9E37_79B9) | 1)
+    }
+
+    /// Returns a number from `min` up to, but not including, `max`.
+    fn range(&mut self, min: u32, max: u32) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        min + self.0 % (max - min).max(1)
+    }
+}
+
+/// Covers the seams in the middle of the offset channels with patches from `source`.
+fn patch(
+    source: &Channels,
+    mut offset: Channels,
+    radius: u32,
+    seed: u32,
+    cancel: &dyn Fn() -> bool,
+) -> Result<Channels> {
+    let Size { width, height } = source.size;
+    let mut rng = XorShift::new(seed);
+
+    // Patches overlap so they're at full strength along the whole seam.
+    let step = (radius / 2).max(1) as usize;
+    let centers = (0..width)
+        .step_by(step)
+        .map(|x| (x, height / 2))
+        .chain((0..height).step_by(step).map(|y| (width / 2, y)))
+        .collect::<Vec<(u32, u32)>>();
+
+    for (center_x, center_y) in centers {
+        if cancel() {
+            return Err(TexProError::Canceled);
+        }
+
+        // The patch is taken from far enough from the edges that it doesn't have a seam.
+        let source_x = rng.range(radius, width - radius);
+        let source_y = rng.range(radius, height - radius);
+
+        for patch_y in 0..radius * 2 {
+            for patch_x in 0..radius * 2 {
+                let (dx, dy) = (
+                    patch_x as f32 + 0.5 - radius as f32,
+                    patch_y as f32 + 0.5 - radius as f32,
+                );
+                let weight = 1.0 - smoothstep(0.5, 1.0, dx.hypot(dy) / radius as f32);
+                if weight <= 0.0 {
+                    continue;
+                }
+
+                let target = offset.index(
+                    (center_x + width + patch_x - radius) % width,
+                    (center_y + height + patch_y - radius) % height,
+                );
+                let from = source.index(source_x + patch_x - radius, source_y + patch_y - radius);
+
+                for (output, channel) in offset.values.iter_mut().zip(source.values.iter()) {
+                    output[target] += (channel[from] - output[target]) * weight;
+                }
+            }
+        }
+    }
+
+    // Moves the image back to where it was, it tiles so the new seams don't show.
+    Ok(offset.offset_half())
+}
+
+pub(crate) fn process(
+    shutdown: Arc<AtomicBool>,
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    make_tile: MakeTile,
+) -> Result<Vec<Arc<SlotData>>> {
+    let slot_data = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data
+    } else {
+        return Ok(Vec::new());
+    };
+
+    let slot_data = process_channels(node, &slot_data, |size, values| {
+        let channels = Channels {
+            values: values.iter().map(|values| values.to_vec()).collect(),
+            size,
+        };
+
+        let cancel = || cancelling(&node.cancel, &shutdown);
+        let blend_width = make_tile.blend_width.clamp(0.0, 0.5);
+
+        let channels = match make_tile.method {
+            TileMethod::Offset => {
+                let weight = |distance| 1.0 - smoothstep(0.0, blend_width, distance);
+                let offset = |position, length| (position + length / 2) % length;
+
+                let channels = cross_fade(&channels, false, offset, weight, &cancel)?;
+                cross_fade(&channels, true, offset, weight, &cancel)?
+            }
+            TileMethod::Mirror => {
+                let weight = |distance| 0.5 * (1.0 - smoothstep(0.0, blend_width, distance));
+                let mirror = |position, length| length - 1 - position;
+
+                let channels = cross_fade(&channels, false, mirror, weight, &cancel)?;
+                cross_fade(&channels, true, mirror, weight, &cancel)?
+            }
+            TileMethod::Patch => {
+                let radius = (blend_width * size.width.min(size.height) as f32) as u32;
+                let radius = radius.min(size.width.min(size.height) / 4);
+
+                if radius == 0 {
+                    channels.offset_half()
+                } else {
+                    patch(
+                        &channels,
+                        channels.offset_half(),
+                        radius,
+                        make_tile.seed,
+                        &cancel,
+                    )?
+                }
+            }
+        };
+
+        Ok(channels.values)
+    })?;
+
+    Ok(vec![Arc::new(slot_data)])
+}
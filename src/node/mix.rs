@@ -12,18 +12,36 @@ use crate::{
     transient_buffer::{TransientBuffer, TransientBufferContainer},
 };
 
-use super::Node;
+use super::{grayscale::GrayConversion, Node};
 
-use image::{ImageBuffer, Luma};
+use image::Luma;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Copy, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
 pub enum MixType {
     Add,
     Subtract,
     Multiply,
     Divide,
     Pow,
+    Screen,
+    Overlay,
+    SoftLight,
+    HardLight,
+    LinearDodge,
+    ColorDodge,
+    LinearBurn,
+    ColorBurn,
+    Difference,
+    Exclusion,
+    /// Keeps the darker of the two values.
+    Min,
+    /// Keeps the lighter of the two values.
+    Max,
+    /// Interpolates from left to right by the opacity and mask.
+    Lerp,
+    /// Picks right where the opacity times the mask is at least 0.5, and left elsewhere.
+    Switch,
 }
 
 impl Default for MixType {
@@ -43,15 +61,216 @@ impl fmt::Display for MixType {
                 Self::Multiply => "Multiply",
                 Self::Divide => "Divide",
                 Self::Pow => "Power",
+                Self::Screen => "Screen",
+                Self::Overlay => "Overlay",
+                Self::SoftLight => "Soft Light",
+                Self::HardLight => "Hard Light",
+                Self::LinearDodge => "Linear Dodge",
+                Self::ColorDodge => "Color Dodge",
+                Self::LinearBurn => "Linear Burn",
+                Self::ColorBurn => "Color Burn",
+                Self::Difference => "Difference",
+                Self::Exclusion => "Exclusion",
+                Self::Min => "Min",
+                Self::Max => "Max",
+                Self::Lerp => "Lerp",
+                Self::Switch => "Switch",
             }
         )
     }
 }
 
+impl MixType {
+    /// Blends a `left` (backdrop) value with a `right` (source) value.
+    fn blend(self, left: f32, right: f32) -> f32 {
+        match self {
+            Self::Add | Self::LinearDodge => left + right,
+            Self::Subtract => left - right,
+            Self::Multiply => left * right,
+            Self::Divide => left / right,
+            Self::Pow => left.powf(right),
+            Self::Screen => 1.0 - (1.0 - left) * (1.0 - right),
+            Self::Overlay => Self::HardLight.blend(right, left),
+            Self::SoftLight => {
+                if right <= 0.5 {
+                    left - (1.0 - 2.0 * right) * left * (1.0 - left)
+                } else {
+                    let d = if left <= 0.25 {
+                        ((16.0 * left - 12.0) * left + 4.0) * left
+                    } else {
+                        left.sqrt()
+                    };
+                    left + (2.0 * right - 1.0) * (d - left)
+                }
+            }
+            Self::HardLight => {
+                if right <= 0.5 {
+                    2.0 * left * right
+                } else {
+                    1.0 - 2.0 * (1.0 - left) * (1.0 - right)
+                }
+            }
+            Self::ColorDodge => {
+                if left <= 0.0 {
+                    0.0
+                } else if right >= 1.0 {
+                    1.0
+                } else {
+                    (left / (1.0 - right)).min(1.0)
+                }
+            }
+            Self::LinearBurn => left + right - 1.0,
+            Self::ColorBurn => {
+                if left >= 1.0 {
+                    1.0
+                } else if right <= 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - left) / right).min(1.0)
+                }
+            }
+            Self::Difference => (left - right).abs(),
+            Self::Exclusion => left + right - 2.0 * left * right,
+            Self::Min => left.min(right),
+            Self::Max => left.max(right),
+            Self::Lerp | Self::Switch => right,
+        }
+    }
+
+    /// How much of the blended value is used for a given opacity times mask.
+    fn amount(self, amount: f32) -> f32 {
+        match self {
+            Self::Switch => {
+                if amount >= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            _ => amount.clamp(0.0, 1.0),
+        }
+    }
+
+    fn mix(self, left: f32, right: f32, amount: f32) -> f32 {
+        let blended = self.blend(left, right);
+
+        if amount >= 1.0 {
+            blended
+        } else {
+            left * (1.0 - amount) + blended * amount
+        }
+    }
+}
+
+/// How a `Mix` node handles the alpha of RGBA inputs.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MixAlpha {
+    /// The output is fully opaque.
+    Opaque,
+    /// The right input is composited over the left one, and the colors are blended where both
+    /// are visible.
+    Composite,
+}
+
+impl Default for MixAlpha {
+    fn default() -> Self {
+        Self::Opaque
+    }
+}
+
+impl fmt::Display for MixAlpha {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Opaque => write!(f, "Opaque"),
+            Self::Composite => write!(f, "Composite"),
+        }
+    }
+}
+
+/// The settings of a `NodeType::Mix` node.
+///
+/// How much of the blended result is used is `opacity` times the optional "mask" input. With
+/// `MixAlpha::Composite` it scales the alpha of the right input instead.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+#[serde(from = "MixRepr")]
+pub struct Mix {
+    pub mix_type: MixType,
+    pub alpha: MixAlpha,
+    pub opacity: f32,
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        Self::new(MixType::default())
+    }
+}
+
+impl From<MixType> for Mix {
+    fn from(mix_type: MixType) -> Self {
+        Self::new(mix_type)
+    }
+}
+
+impl Mix {
+    pub fn new(mix_type: MixType) -> Self {
+        Self {
+            mix_type,
+            alpha: MixAlpha::default(),
+            opacity: 1.0,
+        }
+    }
+
+    pub fn alpha(mut self, alpha: MixAlpha) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+}
+
+// Graphs saved before `Mix` existed only store a `MixType`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MixRepr {
+    MixType(MixType),
+    Mix {
+        mix_type: MixType,
+        #[serde(default)]
+        alpha: MixAlpha,
+        #[serde(default = "default_opacity")]
+        opacity: f32,
+    },
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+impl From<MixRepr> for Mix {
+    fn from(repr: MixRepr) -> Self {
+        match repr {
+            MixRepr::MixType(mix_type) => Self::new(mix_type),
+            MixRepr::Mix {
+                mix_type,
+                alpha,
+                opacity,
+            } => Self {
+                mix_type,
+                alpha,
+                opacity,
+            },
+        }
+    }
+}
+
 pub(crate) fn process(
     slot_datas: &[Arc<SlotData>],
     node: &Node,
-    mix_type: MixType,
+    mix: Mix,
+    gray_conversion: GrayConversion,
 ) -> Result<Vec<Arc<SlotData>>> {
     let (image_left, image_right): (SlotImage, SlotImage) = {
         if let Some(slot_data_left) = slot_data_with_name(slot_datas, node, "left") {
@@ -59,13 +278,17 @@ pub(crate) fn process(
 
             let image_right = {
                 if let Some(slot_data) = slot_data_with_name(slot_datas, node, "right") {
-                    slot_data.image.as_type(is_rgba)?
+                    if is_rgba {
+                        slot_data.straight_image().as_type(true)?
+                    } else {
+                        slot_data.straight_image().to_gray(gray_conversion)?
+                    }
                 } else {
                     SlotImage::from_value(slot_data_left.size()?, 0.0, is_rgba)
                 }
             };
 
-            (slot_data_left.image.clone(), image_right)
+            (slot_data_left.straight_image(), image_right)
         } else if let Some(slot_data_right) = slot_data_with_name(slot_datas, node, "right") {
             let image_left = SlotImage::from_value(
                 slot_data_right.size()?,
@@ -73,7 +296,7 @@ pub(crate) fn process(
                 slot_data_right.image.is_rgba(),
             );
 
-            (image_left, slot_data_right.image.clone())
+            (image_left, slot_data_right.straight_image())
         } else {
             return Ok(vec![Arc::new(SlotData::new(
                 node.node_id,
@@ -85,20 +308,33 @@ pub(crate) fn process(
 
     let size = image_left.size()?;
 
+    let mask = match slot_data_with_name(slot_datas, node, "mask") {
+        Some(slot_data) => Some(slot_data.image.as_type(false)?.bufs().remove(0)),
+        None => None,
+    };
+    let mask = mask.as_ref().map(|tbc| tbc.transient_buffer());
+    let mask = mask.as_ref().map(|tbc| tbc.buffer());
+    let amount = |x: u32, y: u32| {
+        let mask = mask.map_or(1.0, |mask| mask.get_pixel(x, y).0[0]);
+        mix.mix_type.amount(mix.opacity * mask)
+    };
+
     let slot_image: SlotImage = match (image_left, image_right) {
         (SlotImage::Gray(left), SlotImage::Gray(right)) => {
             let (left, right) = (left.transient_buffer(), right.transient_buffer());
             let (left, right) = (left.buffer(), right.buffer());
 
-            // let (left, right) = (left.buffer_read()?, right.buffer_read()?);
-
-            SlotImage::Gray(match mix_type {
-                MixType::Add => process_add_gray(left, right, size),
-                MixType::Subtract => process_subtract_gray(left, right, size),
-                MixType::Multiply => process_multiply_gray(left, right, size),
-                MixType::Divide => process_divide_gray(left, right, size),
-                MixType::Pow => process_pow_gray(left, right, size),
-            })
+            SlotImage::Gray(buffer_container(Buffer::from_fn(
+                size.width,
+                size.height,
+                |x, y| {
+                    Luma([mix.mix_type.mix(
+                        left.get_pixel(x, y).0[0],
+                        right.get_pixel(x, y).0[0],
+                        amount(x, y),
+                    )])
+                },
+            )))
         }
         (SlotImage::Rgba(left), SlotImage::Rgba(right)) => {
             let (left, right) = (
@@ -115,12 +351,33 @@ pub(crate) fn process(
                 right.iter().map(|tbc| tbc.buffer()).collect::<Vec<_>>(),
             );
 
-            SlotImage::Rgba(match mix_type {
-                MixType::Add => process_add_rgba(&left, &right, size),
-                MixType::Subtract => process_subtract_rgba(&left, &right, size),
-                MixType::Multiply => process_multiply_rgba(&left, &right, size),
-                MixType::Divide => process_divide_rgba(&left, &right, size),
-                MixType::Pow => process_pow_rgba(&left, &right, size),
+            SlotImage::Rgba(match mix.alpha {
+                MixAlpha::Opaque => {
+                    let channel = |channel: usize| {
+                        buffer_container(Buffer::from_fn(size.width, size.height, |x, y| {
+                            Luma([mix.mix_type.mix(
+                                left[channel].get_pixel(x, y).0[0],
+                                right[channel].get_pixel(x, y).0[0],
+                                amount(x, y),
+                            )])
+                        }))
+                    };
+
+                    [
+                        channel(0),
+                        channel(1),
+                        channel(2),
+                        buffer_container(
+                            Buffer::from_raw(
+                                size.width,
+                                size.height,
+                                vec![1.0; size.pixel_count()],
+                            )
+                            .unwrap(),
+                        ),
+                    ]
+                }
+                MixAlpha::Composite => composite(mix.mix_type, &left, &right, amount, size),
             })
         }
         _ => return Ok(Vec::new()),
@@ -133,170 +390,59 @@ pub(crate) fn process(
     ))])
 }
 
-fn process_add_gray(left: &Buffer, right: &Buffer, size: Size) -> Arc<TransientBufferContainer> {
-    Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
-        TransientBuffer::new(Box::new(ImageBuffer::from_fn(
-            size.width,
-            size.height,
-            |x, y| Luma([left.get_pixel(x, y).0[0] + right.get_pixel(x, y).0[0]]),
-        ))),
-    ))))
-}
-
-fn process_subtract_gray(
-    left: &Buffer,
-    right: &Buffer,
-    size: Size,
-) -> Arc<TransientBufferContainer> {
+fn buffer_container(buffer: Buffer) -> Arc<TransientBufferContainer> {
     Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
-        TransientBuffer::new(Box::new(ImageBuffer::from_fn(
-            size.width,
-            size.height,
-            |x, y| Luma([left.get_pixel(x, y).0[0] - right.get_pixel(x, y).0[0]]),
-        ))),
+        TransientBuffer::new(Box::new(buffer)),
     ))))
 }
 
-fn process_multiply_gray(
-    left: &Buffer,
-    right: &Buffer,
-    size: Size,
-) -> Arc<TransientBufferContainer> {
-    Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
-        TransientBuffer::new(Box::new(ImageBuffer::from_fn(
-            size.width,
-            size.height,
-            |x, y| Luma([left.get_pixel(x, y).0[0] * right.get_pixel(x, y).0[0]]),
-        ))),
-    ))))
-}
-
-fn process_divide_gray(left: &Buffer, right: &Buffer, size: Size) -> Arc<TransientBufferContainer> {
-    Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
-        TransientBuffer::new(Box::new(ImageBuffer::from_fn(
-            size.width,
-            size.height,
-            |x, y| Luma([left.get_pixel(x, y).0[0] / right.get_pixel(x, y).0[0]]),
-        ))),
-    ))))
-}
-
-fn process_pow_gray(left: &Buffer, right: &Buffer, size: Size) -> Arc<TransientBufferContainer> {
-    Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
-        TransientBuffer::new(Box::new(ImageBuffer::from_fn(
-            size.width,
-            size.height,
-            |x, y| Luma([left.get_pixel(x, y).0[0].powf(right.get_pixel(x, y).0[0])]),
-        ))),
-    ))))
-}
-
-fn process_add_rgba(
+/// Composites `right` over `left` using the "source over" operator, with the blended colors
+/// used where both are visible. The alpha of `right` is scaled by `amount`. All images have
+/// straight alpha.
+fn composite(
+    mix_type: MixType,
     left: &[&Buffer],
     right: &[&Buffer],
+    amount: impl Fn(u32, u32) -> f32,
     size: Size,
 ) -> [Arc<TransientBufferContainer>; 4] {
-    [
-        process_add_gray(left[0], right[0], size),
-        process_add_gray(left[1], right[1], size),
-        process_add_gray(left[2], right[2], size),
-        Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
-            TransientBuffer::new(Box::new(
-                Buffer::from_raw(
-                    size.width,
-                    size.height,
-                    vec![1.0; (size.width * size.height) as usize],
-                )
-                .unwrap(),
-            )),
-        )))),
-    ]
-}
+    let alphas = |x: u32, y: u32| {
+        (
+            left[3].get_pixel(x, y).0[0],
+            right[3].get_pixel(x, y).0[0] * amount(x, y),
+        )
+    };
 
-fn process_subtract_rgba(
-    left: &[&Buffer],
-    right: &[&Buffer],
-    size: Size,
-) -> [Arc<TransientBufferContainer>; 4] {
-    [
-        process_subtract_gray(left[0], right[0], size),
-        process_subtract_gray(left[1], right[1], size),
-        process_subtract_gray(left[2], right[2], size),
-        Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
-            TransientBuffer::new(Box::new(
-                Buffer::from_raw(
-                    size.width,
-                    size.height,
-                    vec![1.0; (size.width * size.height) as usize],
-                )
-                .unwrap(),
-            )),
-        )))),
-    ]
-}
+    let alpha = Buffer::from_fn(size.width, size.height, |x, y| {
+        let (alpha_left, alpha_right) = alphas(x, y);
+        Luma([alpha_right + alpha_left * (1.0 - alpha_right)])
+    });
 
-fn process_multiply_rgba(
-    left: &[&Buffer],
-    right: &[&Buffer],
-    size: Size,
-) -> [Arc<TransientBufferContainer>; 4] {
-    [
-        process_multiply_gray(left[0], right[0], size),
-        process_multiply_gray(left[1], right[1], size),
-        process_multiply_gray(left[2], right[2], size),
-        Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
-            TransientBuffer::new(Box::new(
-                Buffer::from_raw(
-                    size.width,
-                    size.height,
-                    vec![1.0; (size.width * size.height) as usize],
-                )
-                .unwrap(),
-            )),
-        )))),
-    ]
-}
+    let color = |channel: usize| {
+        Buffer::from_fn(size.width, size.height, |x, y| {
+            let (alpha_left, alpha_right) = alphas(x, y);
+            let alpha_out = alpha.get_pixel(x, y).0[0];
 
-fn process_divide_rgba(
-    left: &[&Buffer],
-    right: &[&Buffer],
-    size: Size,
-) -> [Arc<TransientBufferContainer>; 4] {
-    [
-        process_divide_gray(left[0], right[0], size),
-        process_divide_gray(left[1], right[1], size),
-        process_divide_gray(left[2], right[2], size),
-        Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
-            TransientBuffer::new(Box::new(
-                Buffer::from_raw(
-                    size.width,
-                    size.height,
-                    vec![1.0; (size.width * size.height) as usize],
-                )
-                .unwrap(),
-            )),
-        )))),
-    ]
-}
+            if alpha_out <= 0.0 {
+                Luma([0.0])
+            } else {
+                let (left, right) = (
+                    left[channel].get_pixel(x, y).0[0],
+                    right[channel].get_pixel(x, y).0[0],
+                );
+                let premultiplied = alpha_right * (1.0 - alpha_left) * right
+                    + alpha_right * alpha_left * mix_type.blend(left, right)
+                    + (1.0 - alpha_right) * alpha_left * left;
+
+                Luma([premultiplied / alpha_out])
+            }
+        })
+    };
 
-fn process_pow_rgba(
-    left: &[&Buffer],
-    right: &[&Buffer],
-    size: Size,
-) -> [Arc<TransientBufferContainer>; 4] {
     [
-        process_pow_gray(left[0], right[0], size),
-        process_pow_gray(left[1], right[1], size),
-        process_pow_gray(left[2], right[2], size),
-        Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
-            TransientBuffer::new(Box::new(
-                Buffer::from_raw(
-                    size.width,
-                    size.height,
-                    vec![1.0; (size.width * size.height) as usize],
-                )
-                .unwrap(),
-            )),
-        )))),
+        buffer_container(color(0)),
+        buffer_container(color(1)),
+        buffer_container(color(2)),
+        buffer_container(alpha),
     ]
 }
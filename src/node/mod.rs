@@ -1,20 +1,35 @@
+pub mod ambient_occlusion;
 pub mod combine_rgba;
+pub mod convert_color;
+pub mod curvature;
+pub mod curves;
+pub mod custom;
 pub mod embed;
+pub mod expression;
+pub mod gradient_map;
 pub mod graph;
+pub mod grayscale;
 pub mod height_to_normal;
+pub mod hsl_adjust;
 pub mod image;
 pub mod input_gray;
 pub mod input_rgba;
+pub mod levels;
+pub mod make_tile;
 pub mod mix;
 pub mod node_type;
 pub mod output;
+pub mod premultiply;
 pub mod process_shared;
 pub mod separate_rgba;
+pub mod transform;
 pub mod value;
+pub mod warp;
 pub mod write;
 
 use crate::{
     error::{Result, TexProError},
+    export::{ColorEncoding, TextureUsage},
     node_graph::*,
     priority::Priority,
     slot_data::*,
@@ -98,6 +113,55 @@ impl From<ResizeFilter> for FilterType {
     }
 }
 
+/// The color space of the values in a node's images.
+///
+/// For `Image` and `Embed` nodes it describes the source data, and `Srgb` data is converted to
+/// linear when it's loaded so all processing happens in a linear working space. For output
+/// nodes it describes how the output should be encoded when it's exported, see
+/// `LiveGraph::export_output()`. Alpha is always linear.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ColorSpace {
+    /// Values that are not colors, like masks and normals, which are never converted.
+    Raw,
+    Srgb,
+    Linear,
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        Self::Raw
+    }
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Raw => write!(f, "Raw"),
+            Self::Srgb => write!(f, "sRGB"),
+            Self::Linear => write!(f, "Linear"),
+        }
+    }
+}
+
+impl From<TextureUsage> for ColorSpace {
+    fn from(usage: TextureUsage) -> Self {
+        match usage {
+            TextureUsage::Color => Self::Srgb,
+            TextureUsage::Normal | TextureUsage::Mask | TextureUsage::Data => Self::Raw,
+        }
+    }
+}
+
+impl ColorSpace {
+    /// The encoding to use when exporting values from the linear working space.
+    pub fn encoding(self) -> ColorEncoding {
+        match self {
+            Self::Srgb => ColorEncoding::Srgb,
+            Self::Linear | Self::Raw => ColorEncoding::Linear,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Side {
     Input,
@@ -116,6 +180,8 @@ pub struct Node {
     pub node_type: NodeType,
     pub resize_policy: ResizePolicy,
     pub resize_filter: ResizeFilter,
+    #[serde(default)]
+    pub color_space: ColorSpace,
     #[serde(skip)]
     pub priority: Arc<Priority>,
     #[serde(skip)]
@@ -129,6 +195,7 @@ impl Node {
             node_type,
             resize_policy: ResizePolicy::default(),
             resize_filter: ResizeFilter::default(),
+            color_space: ColorSpace::default(),
             priority: Arc::new(Priority::new()),
             cancel: Arc::new(false.into()),
         }
@@ -140,6 +207,7 @@ impl Node {
             node_type,
             resize_policy: ResizePolicy::default(),
             resize_filter: ResizeFilter::default(),
+            color_space: ColorSpace::default(),
             priority: Arc::new(Priority::new()),
             cancel: Arc::new(false.into()),
         }
@@ -160,6 +228,11 @@ impl Node {
         self
     }
 
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
     pub fn input_slot_with_id(&self, slot_id: SlotId) -> Result<Slot> {
         self.input_slots()
             .into_iter()
@@ -193,7 +266,7 @@ impl Node {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub enum SlotType {
     Gray,
     Rgba,
@@ -1,16 +1,44 @@
 use crate::{
-    edge::Edge, error::Result, node_graph::*, shared::resize_buffers, slot_data::SlotData,
+    edge::Edge,
+    error::Result,
+    node_graph::*,
+    shared::resize_buffers,
+    slot_data::{AlphaMode, SlotData},
     texture_processor::TextureProcessor,
 };
-use serde::{Deserialize, Serialize};
-use std::{fmt, mem, path::PathBuf, sync::Arc};
+use serde::{
+    de::{self, value::MapAccessDeserializer, IntoDeserializer, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{
+    fmt, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use super::{
+    ambient_occlusion::AmbientOcclusion,
+    convert_color::ConvertColor,
+    curvature::Curvature,
+    curves::Curves,
+    custom::CustomNode,
     embed::{EmbeddedSlotData, EmbeddedSlotDataId},
-    mix::MixType,
+    expression::Expression,
+    gradient_map::GradientMap,
+    grayscale::GrayConversion,
+    height_to_normal::HeightToNormal,
+    hsl_adjust::HslAdjust,
+    levels::Levels,
+    make_tile::MakeTile,
+    mix::Mix,
+    transform::Transform,
+    warp::{DirectionalWarp, VectorWarp, Warp, WarpKind},
     Node, SlotInput, SlotOutput, SlotType, *,
 };
+// `remote = "Self"` makes the derives generate inherent functions, which the `Serialize` and
+// `Deserialize` impls below call, so the older form of `HeightToNormal` can still be read.
 #[derive(Deserialize, Serialize, Clone)]
+#[serde(remote = "Self")]
 pub enum NodeType {
     InputGray(String),
     InputRgba(String),
@@ -21,10 +49,64 @@ pub enum NodeType {
     Embed(EmbeddedSlotDataId), // Maybe `Image` can handle both embedded and external images?
     Write(PathBuf),            // Probably remove this type, leave saving to application.
     Value(f32),
-    Mix(MixType),
-    HeightToNormal,
+    Mix(Mix),
+    HeightToNormal(HeightToNormal),
     SeparateRgba,
     CombineRgba,
+    Premultiply,
+    Unpremultiply,
+    Expression(Expression),
+    Custom(CustomNode),
+    Levels(Levels),
+    Curves(Curves),
+    GradientMap(GradientMap),
+    HslAdjust(HslAdjust),
+    ConvertColor(ConvertColor),
+    Grayscale(GrayConversion),
+    Transform(Transform),
+    MakeTile(MakeTile),
+    Warp(Warp),
+    DirectionalWarp(DirectionalWarp),
+    VectorWarp(VectorWarp),
+    AmbientOcclusion(AmbientOcclusion),
+    Curvature(Curvature),
+}
+
+impl Serialize for NodeType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        NodeType::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeTypeVisitor)
+    }
+}
+
+/// Hands nodes to the derived `NodeType::deserialize()`, except for `"HeightToNormal"`, which is
+/// how graphs saved before `HeightToNormal` had settings store it. The node isn't buffered, so
+/// errors still point at what's wrong in it.
+struct NodeTypeVisitor;
+
+impl<'de> Visitor<'de> for NodeTypeVisitor {
+    type Value = NodeType;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("enum NodeType")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<NodeType, E> {
+        if value == "HeightToNormal" {
+            Ok(NodeType::HeightToNormal(HeightToNormal::default()))
+        } else {
+            NodeType::deserialize(value.into_deserializer())
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> std::result::Result<NodeType, A::Error> {
+        NodeType::deserialize(MapAccessDeserializer::new(map))
+    }
 }
 
 impl fmt::Debug for NodeType {
@@ -40,9 +122,30 @@ impl fmt::Debug for NodeType {
             Self::Write(_) => write!(f, "Write"),
             Self::Value(value) => write!(f, "Value: {}", value),
             Self::Mix(_) => write!(f, "Mix"),
-            Self::HeightToNormal => write!(f, "HeightToNormal"),
+            Self::HeightToNormal(height_to_normal) => {
+                write!(f, "HeightToNormal: {}", height_to_normal)
+            }
             Self::SeparateRgba => write!(f, "SeparateRgba"),
             Self::CombineRgba => write!(f, "CombineRgba"),
+            Self::Premultiply => write!(f, "Premultiply"),
+            Self::Unpremultiply => write!(f, "Unpremultiply"),
+            Self::Expression(expression) => write!(f, "Expression: {}", expression),
+            Self::Custom(custom_node) => write!(f, "Custom: {}", custom_node.type_id),
+            Self::Levels(levels) => write!(f, "{}", levels),
+            Self::Curves(curves) => write!(f, "{}", curves),
+            Self::GradientMap(_) => write!(f, "GradientMap"),
+            Self::HslAdjust(hsl_adjust) => write!(f, "HslAdjust: {}", hsl_adjust),
+            Self::ConvertColor(convert_color) => write!(f, "ConvertColor: {}", convert_color),
+            Self::Grayscale(gray_conversion) => write!(f, "Grayscale: {}", gray_conversion),
+            Self::Transform(transform) => write!(f, "Transform: {}", transform),
+            Self::MakeTile(make_tile) => write!(f, "MakeTile: {}", make_tile),
+            Self::Warp(warp) => write!(f, "Warp: {}", warp),
+            Self::DirectionalWarp(warp) => write!(f, "DirectionalWarp: {}", warp),
+            Self::VectorWarp(warp) => write!(f, "VectorWarp: {}", warp),
+            Self::AmbientOcclusion(ambient_occlusion) => {
+                write!(f, "AmbientOcclusion: {}", ambient_occlusion)
+            }
+            Self::Curvature(curvature) => write!(f, "Curvature: {}", curvature),
         }
     }
 }
@@ -86,6 +189,33 @@ impl NodeType {
         }
     }
 
+    /// Returns the paths of all image files the node reads, including the ones read by nodes in
+    /// nested graphs.
+    pub fn image_paths(&self) -> Vec<&Path> {
+        match self {
+            Self::Image(path) => vec![path.as_path()],
+            Self::Graph(node_graph) => node_graph
+                .nodes()
+                .iter()
+                .flat_map(|node| node.node_type.image_paths())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Mutable version of `image_paths()`.
+    pub fn image_paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        match self {
+            Self::Image(path) => vec![path],
+            Self::Graph(node_graph) => node_graph
+                .nodes
+                .iter_mut()
+                .flat_map(|node| node.node_type.image_paths_mut())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
     pub fn to_slot_type(&self) -> Option<SlotType> {
         match self {
             Self::InputGray(_) | Self::OutputGray(_) => Some(SlotType::Gray),
@@ -100,6 +230,7 @@ fn process_node_internal(
     slot_datas: &[Arc<SlotData>],
     embedded_slot_datas: &[Arc<EmbeddedSlotData>],
     input_slot_datas: &[Arc<SlotData>],
+    gray_conversion: GrayConversion,
     tex_pro: &Arc<TextureProcessor>,
 ) -> Result<Vec<Arc<SlotData>>> {
     let shutdown = Arc::clone(&tex_pro.shutdown);
@@ -109,16 +240,81 @@ fn process_node_internal(
         NodeType::InputGray(_) => input_gray::process(&node, input_slot_datas),
         NodeType::OutputRgba(_) | NodeType::OutputGray(_) => output::process(slot_datas, &node),
         NodeType::Graph(ref node_graph) => graph::process(slot_datas, &node, node_graph, tex_pro)?,
-        NodeType::Image(ref path) => image::process(&node, path)?,
+        NodeType::Image(ref path) => image::process(&node, path, &tex_pro.search_paths()?)?,
         NodeType::Embed(embedded_node_data_id) => {
             embed::process(&node, embedded_slot_datas, embedded_node_data_id)?
         }
         NodeType::Write(ref path) => write::process(slot_datas, path)?,
         NodeType::Value(val) => value::process(&node, val),
-        NodeType::Mix(mix_type) => mix::process(slot_datas, &node, mix_type)?,
-        NodeType::HeightToNormal => height_to_normal::process(shutdown, slot_datas, &node)?,
+        NodeType::Mix(options) => mix::process(slot_datas, &node, options, gray_conversion)?,
+        NodeType::HeightToNormal(height_to_normal) => {
+            height_to_normal::process(shutdown, slot_datas, &node, height_to_normal)?
+        }
         NodeType::SeparateRgba => separate_rgba::process(slot_datas, &node)?,
         NodeType::CombineRgba => combine_rgba::process(slot_datas, &node)?,
+        NodeType::Premultiply => premultiply::process(slot_datas, &node, AlphaMode::Premultiplied),
+        NodeType::Unpremultiply => premultiply::process(slot_datas, &node, AlphaMode::Straight),
+        NodeType::Expression(ref expression) => {
+            expression::process(shutdown, slot_datas, &node, expression, gray_conversion)?
+        }
+        NodeType::Custom(ref custom_node) => custom::process(
+            tex_pro.node_processor(&custom_node.type_id)?,
+            shutdown,
+            slot_datas,
+            &node,
+            custom_node,
+        )?,
+        NodeType::Levels(levels) => levels::process(slot_datas, &node, levels)?,
+        NodeType::Curves(ref curves) => curves::process(slot_datas, &node, curves)?,
+        NodeType::GradientMap(ref gradient_map) => {
+            gradient_map::process(slot_datas, &node, gradient_map)?
+        }
+        NodeType::HslAdjust(hsl_adjust) => hsl_adjust::process(slot_datas, &node, hsl_adjust)?,
+        NodeType::ConvertColor(convert_color) => {
+            convert_color::process(slot_datas, &node, convert_color)?
+        }
+        NodeType::Grayscale(gray_conversion) => {
+            grayscale::process(slot_datas, &node, gray_conversion)?
+        }
+        NodeType::Transform(transform) => {
+            transform::process(shutdown, slot_datas, &node, transform)?
+        }
+        NodeType::MakeTile(make_tile) => {
+            make_tile::process(shutdown, slot_datas, &node, make_tile)?
+        }
+        NodeType::Warp(warp) => warp::process(shutdown, slot_datas, &node, WarpKind::Warp(warp))?,
+        NodeType::DirectionalWarp(warp) => {
+            warp::process(shutdown, slot_datas, &node, WarpKind::Directional(warp))?
+        }
+        NodeType::VectorWarp(warp) => {
+            warp::process(shutdown, slot_datas, &node, WarpKind::Vector(warp))?
+        }
+        NodeType::AmbientOcclusion(ambient_occlusion) => {
+            ambient_occlusion::process(shutdown, slot_datas, &node, ambient_occlusion)?
+        }
+        NodeType::Curvature(curvature) => {
+            curvature::process(shutdown, slot_datas, &node, curvature)?
+        }
+    };
+
+    let output = if node.color_space == ColorSpace::Srgb
+        && matches!(node.node_type, NodeType::Image(_) | NodeType::Embed(_))
+    {
+        output
+            .iter()
+            .map(|slot_data| {
+                Ok(Arc::new(
+                    SlotData::new(
+                        slot_data.node_id,
+                        slot_data.slot_id,
+                        slot_data.image.srgb_to_linear()?,
+                    )
+                    .alpha_mode(slot_data.alpha_mode),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        output
     };
 
     if !matches!(
@@ -156,8 +352,11 @@ impl Node {
             NodeType::Mix(_) => vec![
                 SlotInput::new("left".into(), SlotId(0), SlotType::GrayOrRgba),
                 SlotInput::new("right".into(), SlotId(1), SlotType::GrayOrRgba),
+                SlotInput::new("mask".into(), SlotId(2), SlotType::Gray),
             ],
-            NodeType::HeightToNormal => {
+            NodeType::HeightToNormal(_)
+            | NodeType::AmbientOcclusion(_)
+            | NodeType::Curvature(_) => {
                 vec![SlotInput::new("input".into(), SlotId(0), SlotType::Gray)]
             }
             NodeType::SeparateRgba => {
@@ -169,6 +368,46 @@ impl Node {
                 SlotInput::new("blue".into(), SlotId(2), SlotType::Gray),
                 SlotInput::new("alpha".into(), SlotId(3), SlotType::Gray),
             ],
+            NodeType::Premultiply | NodeType::Unpremultiply => {
+                vec![SlotInput::new("input".into(), SlotId(0), SlotType::Rgba)]
+            }
+            NodeType::Expression(ref expression) => expression
+                .inputs()
+                .iter()
+                .enumerate()
+                .map(|(i, input)| {
+                    SlotInput::new(input.name.clone(), SlotId(i as u32), input.slot_type)
+                })
+                .collect(),
+            NodeType::Custom(ref custom_node) => custom_node.slot_inputs(),
+            NodeType::Levels(_) | NodeType::Curves(_) => {
+                vec![SlotInput::new(
+                    "input".into(),
+                    SlotId(0),
+                    SlotType::GrayOrRgba,
+                )]
+            }
+            NodeType::GradientMap(_) => {
+                vec![SlotInput::new("input".into(), SlotId(0), SlotType::Gray)]
+            }
+            NodeType::HslAdjust(_) | NodeType::ConvertColor(_) => {
+                vec![SlotInput::new("input".into(), SlotId(0), SlotType::Rgba)]
+            }
+            NodeType::Warp(_) | NodeType::DirectionalWarp(_) => vec![
+                SlotInput::new("input".into(), SlotId(0), SlotType::GrayOrRgba),
+                SlotInput::new("intensity".into(), SlotId(1), SlotType::Gray),
+            ],
+            NodeType::VectorWarp(_) => vec![
+                SlotInput::new("input".into(), SlotId(0), SlotType::GrayOrRgba),
+                SlotInput::new("vector".into(), SlotId(1), SlotType::Rgba),
+            ],
+            NodeType::Grayscale(_) | NodeType::Transform(_) | NodeType::MakeTile(_) => {
+                vec![SlotInput::new(
+                    "input".into(),
+                    SlotId(0),
+                    SlotType::GrayOrRgba,
+                )]
+            }
         }
     }
 
@@ -194,7 +433,7 @@ impl Node {
                 SlotId(0),
                 SlotType::GrayOrRgba,
             )],
-            NodeType::HeightToNormal => {
+            NodeType::HeightToNormal(_) => {
                 vec![SlotOutput::new("output".into(), SlotId(0), SlotType::Rgba)]
             }
             NodeType::SeparateRgba => vec![
@@ -206,6 +445,34 @@ impl Node {
             NodeType::CombineRgba => {
                 vec![SlotOutput::new("output".into(), SlotId(0), SlotType::Rgba)]
             }
+            NodeType::Premultiply | NodeType::Unpremultiply => {
+                vec![SlotOutput::new("output".into(), SlotId(0), SlotType::Rgba)]
+            }
+            NodeType::Expression(ref expression) => vec![SlotOutput::new(
+                "output".into(),
+                SlotId(0),
+                expression.output_type().unwrap_or(SlotType::GrayOrRgba),
+            )],
+            NodeType::Custom(ref custom_node) => custom_node.slot_outputs(),
+            NodeType::Levels(_)
+            | NodeType::Curves(_)
+            | NodeType::Transform(_)
+            | NodeType::MakeTile(_)
+            | NodeType::Warp(_)
+            | NodeType::DirectionalWarp(_)
+            | NodeType::VectorWarp(_) => {
+                vec![SlotOutput::new(
+                    "output".into(),
+                    SlotId(0),
+                    SlotType::GrayOrRgba,
+                )]
+            }
+            NodeType::GradientMap(_) | NodeType::HslAdjust(_) | NodeType::ConvertColor(_) => {
+                vec![SlotOutput::new("output".into(), SlotId(0), SlotType::Rgba)]
+            }
+            NodeType::Grayscale(_) | NodeType::AmbientOcclusion(_) | NodeType::Curvature(_) => {
+                vec![SlotOutput::new("output".into(), SlotId(0), SlotType::Gray)]
+            }
         }
     }
 }
@@ -216,6 +483,7 @@ pub(crate) fn process_node(
     embedded_slot_datas: &[Arc<EmbeddedSlotData>],
     input_slot_datas: &[Arc<SlotData>],
     edges: &[Edge],
+    gray_conversion: GrayConversion,
     tex_pro: Arc<TextureProcessor>,
 ) -> Result<Vec<Arc<SlotData>>> {
     assert_eq!(
@@ -235,18 +503,54 @@ pub(crate) fn process_node(
 
         assign_slot_ids(&slot_datas, &edges)
     };
+    let slot_datas = to_gray_where_needed(&slot_datas, &node, gray_conversion)?;
 
     let output = process_node_internal(
         node,
         &slot_datas,
         embedded_slot_datas,
         input_slot_datas,
+        gray_conversion,
         &tex_pro,
     )?;
 
     Ok(output)
 }
 
+/// Converts RGBA images going into gray input slots to gray, since a `SlotType::GrayOrRgba`
+/// output can be connected to a gray input. Gray output nodes are left alone, they pass on
+/// whatever they get.
+fn to_gray_where_needed(
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    gray_conversion: GrayConversion,
+) -> Result<Vec<Arc<SlotData>>> {
+    if let NodeType::OutputGray(_) = node.node_type {
+        return Ok(slot_datas.to_vec());
+    }
+
+    let input_slots = node.input_slots();
+
+    slot_datas
+        .iter()
+        .map(|slot_data| {
+            let gray_slot = input_slots
+                .iter()
+                .any(|slot| slot.slot_id == slot_data.slot_id && slot.slot_type == SlotType::Gray);
+
+            Ok(if gray_slot && slot_data.image.is_rgba() {
+                Arc::new(SlotData::new(
+                    slot_data.node_id,
+                    slot_data.slot_id,
+                    slot_data.straight_image().to_gray(gray_conversion)?,
+                ))
+            } else {
+                Arc::clone(slot_data)
+            })
+        })
+        .collect()
+}
+
 fn assign_slot_ids(slot_datas: &[Arc<SlotData>], edges: &[Edge]) -> Vec<Arc<SlotData>> {
     edges
         .iter()
@@ -257,11 +561,10 @@ fn assign_slot_ids(slot_datas: &[Arc<SlotData>], edges: &[Edge]) -> Vec<Arc<Slot
                     edge.output_slot == slot_data.slot_id && edge.output_id == slot_data.node_id
                 })
                 .unwrap();
-            Arc::new(SlotData::new(
-                edge.input_id,
-                edge.input_slot,
-                slot_data.image.clone(),
-            ))
+            Arc::new(
+                SlotData::new(edge.input_id, edge.input_slot, slot_data.image.clone())
+                    .alpha_mode(slot_data.alpha_mode),
+            )
         })
         .collect::<Vec<Arc<SlotData>>>()
 }
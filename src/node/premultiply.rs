@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use crate::{
+    node::{pixel_buffer, process_shared::slot_data_with_name},
+    node_graph::SlotId,
+    slot_data::{AlphaMode, SlotData},
+    slot_image::SlotImage,
+};
+
+use super::Node;
+
+/// Converts the input to the given `AlphaMode`. Input that is already in that mode is passed
+/// through untouched.
+pub(crate) fn process(
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    alpha_mode: AlphaMode,
+) -> Vec<Arc<SlotData>> {
+    let slot_image = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        match (slot_data.alpha_mode, alpha_mode) {
+            (AlphaMode::Straight, AlphaMode::Premultiplied) => slot_data.image.premultiply(),
+            (AlphaMode::Premultiplied, AlphaMode::Straight) => slot_data.image.unpremultiply(),
+            _ => slot_data.image.clone(),
+        }
+    } else {
+        SlotImage::Rgba([
+            pixel_buffer(0.0),
+            pixel_buffer(0.0),
+            pixel_buffer(0.0),
+            pixel_buffer(0.0),
+        ])
+    };
+
+    vec![Arc::new(
+        SlotData::new(node.node_id, SlotId(0), slot_image).alpha_mode(alpha_mode),
+    )]
+}
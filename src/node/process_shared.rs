@@ -1,11 +1,17 @@
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc,
+    Arc, RwLock,
 };
 
-use crate::{node_graph::SlotId, slot_data::SlotData};
+use crate::{
+    error::Result,
+    node_graph::SlotId,
+    slot_data::{AlphaMode, Size, SlotData},
+    slot_image::{Buffer, SlotImage},
+    transient_buffer::{TransientBuffer, TransientBufferContainer},
+};
 
-use super::Node;
+use super::{Node, ResizePolicy};
 
 pub(crate) fn slot_data_with_name(
     slot_datas: &[Arc<SlotData>],
@@ -28,6 +34,147 @@ pub(crate) fn slot_data_with_slot_id(
         .map(Arc::clone)
 }
 
+/// The size of the inputs, which have all been resized to the same size. Nodes without any
+/// inputs use the size from `ResizePolicy::SpecificSize`, or 1x1.
+pub(crate) fn output_size(slot_datas: &[Arc<SlotData>], node: &Node) -> Result<Size> {
+    Ok(match slot_datas.first() {
+        Some(slot_data) => slot_data.size()?,
+        None => match node.resize_policy {
+            ResizePolicy::SpecificSize(size) => size,
+            _ => Size::new(1, 1),
+        },
+    })
+}
+
+/// Applies `f` to each value in the color channels of an image and keeps its alpha channel.
+/// `f` also gets the index of the channel, which is always 0 for gray images.
+pub(crate) fn map_color_channels<F: Fn(usize, f32) -> f32>(image: &SlotImage, f: F) -> SlotImage {
+    let map = |channel: usize, tbc: &Arc<TransientBufferContainer>| {
+        let mut buffer = tbc.transient_buffer().buffer().clone();
+        for value in buffer.iter_mut() {
+            *value = f(channel, *value);
+        }
+
+        Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
+            TransientBuffer::new(Box::new(buffer)),
+        ))))
+    };
+
+    match image {
+        SlotImage::Gray(buf) => SlotImage::Gray(map(0, buf)),
+        SlotImage::Rgba(bufs) => SlotImage::Rgba([
+            map(0, &bufs[0]),
+            map(1, &bufs[1]),
+            map(2, &bufs[2]),
+            Arc::clone(&bufs[3]),
+        ]),
+    }
+}
+
+/// Applies `f` to the red, green and blue values of each pixel in an image and keeps its alpha
+/// channel. Gray images are converted to RGBA first.
+pub(crate) fn map_colors<F: Fn([f32; 3]) -> [f32; 3]>(
+    image: &SlotImage,
+    f: F,
+) -> Result<SlotImage> {
+    let bufs = image.as_type(true)?.bufs();
+    let colors = {
+        let transient_buffers = bufs[..3]
+            .iter()
+            .map(|tbc| tbc.transient_buffer())
+            .collect::<Vec<_>>();
+        let (red, green, blue) = (
+            transient_buffers[0].buffer(),
+            transient_buffers[1].buffer(),
+            transient_buffers[2].buffer(),
+        );
+
+        red.iter()
+            .zip(green.iter())
+            .zip(blue.iter())
+            .map(|((r, g), b)| f([*r, *g, *b]))
+            .collect::<Vec<[f32; 3]>>()
+    };
+
+    let map = |channel: usize| {
+        let mut buffer = bufs[channel].transient_buffer().buffer().clone();
+        for (value, color) in buffer.iter_mut().zip(colors.iter()) {
+            *value = color[channel];
+        }
+
+        Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
+            TransientBuffer::new(Box::new(buffer)),
+        ))))
+    };
+
+    Ok(SlotImage::Rgba([
+        map(0),
+        map(1),
+        map(2),
+        Arc::clone(&bufs[3]),
+    ]))
+}
+
+/// Builds a new image from the values `f` returns for the channels of `slot_data`, which must
+/// be as many and of the same size as the ones it gets.
+///
+/// Straight RGBA is handed to `f` premultiplied, otherwise the colors of transparent pixels
+/// bleed into the visible ones when they are blended.
+pub(crate) fn process_channels<F>(node: &Node, slot_data: &SlotData, f: F) -> Result<SlotData>
+where
+    F: FnOnce(Size, &[&[f32]]) -> Result<Vec<Vec<f32>>>,
+{
+    let straight_rgba = slot_data.image.is_rgba() && slot_data.alpha_mode == AlphaMode::Straight;
+    let image = if straight_rgba {
+        slot_data.image.premultiply()
+    } else {
+        slot_data.image.clone()
+    };
+
+    let size = image.size()?;
+    let values = {
+        let bufs = image.bufs();
+        let transient_buffers = bufs
+            .iter()
+            .map(|tbc| tbc.transient_buffer())
+            .collect::<Vec<_>>();
+        let channels = transient_buffers
+            .iter()
+            .map(|transient_buffer| transient_buffer.buffer().as_raw().as_slice())
+            .collect::<Vec<&[f32]>>();
+
+        f(size, &channels)?
+    };
+
+    let tbcs = values
+        .into_iter()
+        .map(|values| {
+            Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
+                TransientBuffer::new(Box::new(
+                    Buffer::from_raw(size.width, size.height, values).unwrap(),
+                )),
+            ))))
+        })
+        .collect::<Vec<_>>();
+
+    let processed = match image {
+        SlotImage::Gray(_) => SlotImage::Gray(Arc::clone(&tbcs[0])),
+        SlotImage::Rgba(_) => SlotImage::Rgba([
+            Arc::clone(&tbcs[0]),
+            Arc::clone(&tbcs[1]),
+            Arc::clone(&tbcs[2]),
+            Arc::clone(&tbcs[3]),
+        ]),
+    };
+    let processed = if straight_rgba {
+        processed.unpremultiply()
+    } else {
+        processed
+    };
+
+    Ok(SlotData::new(node.node_id, SlotId(0), processed).alpha_mode(slot_data.alpha_mode))
+}
+
 pub(crate) trait Sampling {
     fn wrapping_sample_add(self, right_side: Self, max: Self) -> Self;
     fn wrapping_sample_subtract(self, right_side: Self, max: Self) -> Self;
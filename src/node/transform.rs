@@ -0,0 +1,341 @@
+use std::{
+    f32::consts::TAU,
+    fmt,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use crate::{
+    error::{Result, TexProError},
+    node::process_shared::{cancelling, process_channels, slot_data_with_name},
+    slot_data::SlotData,
+};
+
+use super::{Node, ResizeFilter};
+
+use serde::{Deserialize, Serialize};
+
+/// What is sampled outside of an image.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// The image repeats.
+    Wrap,
+    /// The pixels at the edges are stretched out.
+    Clamp,
+    /// The image repeats, with every other repetition flipped.
+    Mirror,
+    /// Everything outside is transparent, or black for gray images.
+    Transparent,
+}
+
+impl Default for EdgeMode {
+    fn default() -> Self {
+        Self::Wrap
+    }
+}
+
+impl fmt::Display for EdgeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wrap => write!(f, "Wrap"),
+            Self::Clamp => write!(f, "Clamp"),
+            Self::Mirror => write!(f, "Mirror"),
+            Self::Transparent => write!(f, "Transparent"),
+        }
+    }
+}
+
+/// The settings of a `NodeType::Transform` node.
+///
+/// Offsets and the pivot are fractions of the image size. The image is first repeated `tiles`
+/// times within its own area, then scaled and rotated around the pivot, and last offset.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub offset: [f32; 2],
+    /// Clockwise rotation, where 1 is a full turn.
+    pub rotation: f32,
+    pub scale: [f32; 2],
+    pub tiles: [u32; 2],
+    pub pivot: [f32; 2],
+    pub edge_mode: EdgeMode,
+    /// `ResizeFilter::Nearest` samples the closest pixel and `ResizeFilter::Triangle` samples
+    /// bilinearly, the other filters sample bicubically.
+    pub filter: ResizeFilter,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            offset: [0.0, 0.0],
+            rotation: 0.0,
+            scale: [1.0, 1.0],
+            tiles: [1, 1],
+            pivot: [0.5, 0.5],
+            edge_mode: EdgeMode::default(),
+            filter: ResizeFilter::Triangle,
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Offset {:?}, rotation {}, scale {:?}, tiles {:?}",
+            self.offset, self.rotation, self.scale, self.tiles
+        )
+    }
+}
+
+impl Transform {
+    pub fn offset(mut self, x: f32, y: f32) -> Self {
+        self.offset = [x, y];
+        self
+    }
+
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn scale(mut self, x: f32, y: f32) -> Self {
+        self.scale = [x, y];
+        self
+    }
+
+    pub fn tiles(mut self, x: u32, y: u32) -> Self {
+        self.tiles = [x, y];
+        self
+    }
+
+    pub fn pivot(mut self, x: f32, y: f32) -> Self {
+        self.pivot = [x, y];
+        self
+    }
+
+    pub fn edge_mode(mut self, edge_mode: EdgeMode) -> Self {
+        self.edge_mode = edge_mode;
+        self
+    }
+
+    pub fn filter(mut self, filter: ResizeFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+/// Samples all channels of an image at once, with filtering and edge handling.
+pub(crate) struct Sampler<'a> {
+    channels: Vec<&'a [f32]>,
+    width: u32,
+    height: u32,
+    /// How many times the image repeats inside the area that `edge_mode` treats as the image.
+    tiles: [u32; 2],
+    edge_mode: EdgeMode,
+    filter: ResizeFilter,
+}
+
+impl<'a> Sampler<'a> {
+    pub fn new(
+        channels: Vec<&'a [f32]>,
+        width: u32,
+        height: u32,
+        edge_mode: EdgeMode,
+        filter: ResizeFilter,
+    ) -> Self {
+        Self {
+            channels,
+            width,
+            height,
+            tiles: [1, 1],
+            edge_mode,
+            filter,
+        }
+    }
+
+    pub fn tiles(mut self, tiles: [u32; 2]) -> Self {
+        self.tiles = [tiles[0].max(1), tiles[1].max(1)];
+        self
+    }
+
+    /// Samples at a position in pixels, where the center of the first pixel is at 0.5. The
+    /// result for each channel is written to `output`.
+    pub fn sample(&self, x: f32, y: f32, output: &mut [f32]) {
+        for value in output.iter_mut() {
+            *value = 0.0;
+        }
+
+        let (taps_x, count_x) = taps(self.filter, x - 0.5);
+        let (taps_y, count_y) = taps(self.filter, y - 0.5);
+
+        for &(tap_y, weight_y) in &taps_y[..count_y] {
+            let tap_y = match resolve(tap_y, self.height, self.tiles[1], self.edge_mode) {
+                Some(tap_y) => tap_y,
+                None => continue,
+            };
+
+            for &(tap_x, weight_x) in &taps_x[..count_x] {
+                let tap_x = match resolve(tap_x, self.width, self.tiles[0], self.edge_mode) {
+                    Some(tap_x) => tap_x,
+                    None => continue,
+                };
+
+                let index = tap_y * self.width as usize + tap_x;
+                let weight = weight_x * weight_y;
+                for (value, channel) in output.iter_mut().zip(self.channels.iter()) {
+                    *value += channel[index] * weight;
+                }
+            }
+        }
+    }
+}
+
+/// Returns the pixels to sample along one axis and their weights, for a position where the
+/// center of the first pixel is at 0.
+fn taps(filter: ResizeFilter, position: f32) -> ([(i64, f32); 4], usize) {
+    let mut taps = [(0, 0.0); 4];
+    let first = position.floor();
+    let t = position - first;
+    let first = first as i64;
+
+    match filter {
+        ResizeFilter::Nearest => {
+            taps[0] = (position.round() as i64, 1.0);
+            (taps, 1)
+        }
+        ResizeFilter::Triangle => {
+            taps[0] = (first, 1.0 - t);
+            taps[1] = (first + 1, t);
+            (taps, 2)
+        }
+        _ => {
+            // Catmull-Rom.
+            let (t2, t3) = (t * t, t * t * t);
+            taps[0] = (first - 1, (-t3 + 2.0 * t2 - t) / 2.0);
+            taps[1] = (first, (3.0 * t3 - 5.0 * t2 + 2.0) / 2.0);
+            taps[2] = (first + 1, (-3.0 * t3 + 4.0 * t2 + t) / 2.0);
+            taps[3] = (first + 2, (t3 - t2) / 2.0);
+            (taps, 4)
+        }
+    }
+}
+
+/// Turns a pixel index that may be outside of the image into one inside of it, or `None` if
+/// it should be transparent.
+fn resolve(index: i64, size: u32, tiles: u32, edge_mode: EdgeMode) -> Option<usize> {
+    let area = size as i64 * tiles as i64;
+
+    let index = if (0..area).contains(&index) {
+        index
+    } else {
+        match edge_mode {
+            EdgeMode::Wrap => index.rem_euclid(area),
+            EdgeMode::Clamp => index.clamp(0, area - 1),
+            EdgeMode::Mirror => {
+                let index = index.rem_euclid(area * 2);
+                if index < area {
+                    index
+                } else {
+                    area * 2 - 1 - index
+                }
+            }
+            EdgeMode::Transparent => return None,
+        }
+    };
+
+    Some(index.rem_euclid(size as i64) as usize)
+}
+
+/// Keeps a scale from being zero, which would divide by zero.
+fn nonzero(scale: f32) -> f32 {
+    if scale.abs() < f32::EPSILON {
+        f32::EPSILON.copysign(scale)
+    } else {
+        scale
+    }
+}
+
+/// Builds a new image from `slot_data` by sampling it at the position `position` returns for
+/// each output pixel. Positions are in pixels of the area that `sampler` treats as the image,
+/// where the center of the first pixel is at 0.5.
+pub(crate) fn resample<F: Fn(u32, u32) -> (f32, f32)>(
+    shutdown: &Arc<AtomicBool>,
+    node: &Node,
+    slot_data: &SlotData,
+    edge_mode: EdgeMode,
+    filter: ResizeFilter,
+    tiles: [u32; 2],
+    position: F,
+) -> Result<SlotData> {
+    process_channels(node, slot_data, |size, channels| {
+        let sampler = Sampler::new(
+            channels.to_vec(),
+            size.width,
+            size.height,
+            edge_mode,
+            filter,
+        )
+        .tiles(tiles);
+
+        let mut outputs = vec![Vec::with_capacity(size.pixel_count()); channels.len()];
+        let mut sample = vec![0.0; channels.len()];
+
+        for y in 0..size.height {
+            if cancelling(&node.cancel, shutdown) {
+                return Err(TexProError::Canceled);
+            }
+
+            for x in 0..size.width {
+                let (x, y) = position(x, y);
+
+                sampler.sample(x, y, &mut sample);
+                for (output, value) in outputs.iter_mut().zip(sample.iter()) {
+                    output.push(*value);
+                }
+            }
+        }
+
+        Ok(outputs)
+    })
+}
+
+pub(crate) fn process(
+    shutdown: Arc<AtomicBool>,
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    transform: Transform,
+) -> Result<Vec<Arc<SlotData>>> {
+    let slot_data = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data
+    } else {
+        return Ok(Vec::new());
+    };
+
+    let size = slot_data.size()?;
+    let (width, height) = (size.width as f32, size.height as f32);
+
+    let (sin, cos) = (-transform.rotation * TAU).sin_cos();
+    let scale = [nonzero(transform.scale[0]), nonzero(transform.scale[1])];
+    let pivot = [transform.pivot[0] * width, transform.pivot[1] * height];
+    let origin = [
+        pivot[0] + transform.offset[0] * width,
+        pivot[1] + transform.offset[1] * height,
+    ];
+    let tiles = [transform.tiles[0].max(1), transform.tiles[1].max(1)];
+
+    Ok(vec![Arc::new(resample(
+        &shutdown,
+        node,
+        &slot_data,
+        transform.edge_mode,
+        transform.filter,
+        tiles,
+        |x, y| {
+            // Goes backwards from the output pixel to the position in the input.
+            let (x, y) = (x as f32 + 0.5 - origin[0], y as f32 + 0.5 - origin[1]);
+            let (x, y) = (x * cos - y * sin, x * sin + y * cos);
+            let (x, y) = (x / scale[0] + pivot[0], y / scale[1] + pivot[1]);
+
+            (x * tiles[0] as f32, y * tiles[1] as f32)
+        },
+    )?)])
+}
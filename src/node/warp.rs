@@ -0,0 +1,204 @@
+use std::{
+    f32::consts::TAU,
+    fmt,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use crate::{
+    error::Result,
+    node::{
+        process_shared::{slot_data_with_name, Sampling},
+        transform::{resample, EdgeMode},
+    },
+    node_graph::SlotId,
+    slot_data::SlotData,
+};
+
+use super::{Node, ResizeFilter};
+
+use serde::{Deserialize, Serialize};
+
+/// The settings of a `NodeType::Warp` node, which moves the input along the slopes of a gray
+/// intensity map.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct Warp {
+    /// How far the input moves as a fraction of the image size, where a slope of 1 is the value
+    /// of the intensity map changing by 1 across the whole image. Positive values pull in the
+    /// input from uphill.
+    pub intensity: f32,
+}
+
+impl Default for Warp {
+    fn default() -> Self {
+        Self { intensity: 0.01 }
+    }
+}
+
+impl fmt::Display for Warp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Intensity {}", self.intensity)
+    }
+}
+
+impl Warp {
+    pub fn new(intensity: f32) -> Self {
+        Self { intensity }
+    }
+}
+
+/// The settings of a `NodeType::DirectionalWarp` node, which moves the input in one direction
+/// by a distance taken from a gray intensity map.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct DirectionalWarp {
+    /// How far the input moves where the intensity map is 1, as a fraction of the image size.
+    pub intensity: f32,
+    /// The direction the input is pulled in from, where 0 is to the right and 1 is a full turn
+    /// clockwise.
+    pub angle: f32,
+}
+
+impl Default for DirectionalWarp {
+    fn default() -> Self {
+        Self {
+            intensity: 0.1,
+            angle: 0.0,
+        }
+    }
+}
+
+impl fmt::Display for DirectionalWarp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Intensity {}, angle {}", self.intensity, self.angle)
+    }
+}
+
+impl DirectionalWarp {
+    pub fn new(intensity: f32, angle: f32) -> Self {
+        Self { intensity, angle }
+    }
+}
+
+/// The settings of a `NodeType::VectorWarp` node, which moves the input by the vectors in the
+/// red and green channels of an RGBA vector map. The channels go from 0 to 1 and are remapped
+/// to -1 to 1, like in normal maps.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+pub struct VectorWarp {
+    /// How far the input moves for a vector with a length of 1, as a fraction of the image
+    /// size.
+    pub intensity: f32,
+}
+
+impl Default for VectorWarp {
+    fn default() -> Self {
+        Self { intensity: 0.1 }
+    }
+}
+
+impl fmt::Display for VectorWarp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Intensity {}", self.intensity)
+    }
+}
+
+impl VectorWarp {
+    pub fn new(intensity: f32) -> Self {
+        Self { intensity }
+    }
+}
+
+/// The node types that are processed by this module.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum WarpKind {
+    Warp(Warp),
+    Directional(DirectionalWarp),
+    Vector(VectorWarp),
+}
+
+impl WarpKind {
+    fn map_name(self) -> &'static str {
+        match self {
+            Self::Warp(_) | Self::Directional(_) => "intensity",
+            Self::Vector(_) => "vector",
+        }
+    }
+}
+
+pub(crate) fn process(
+    shutdown: Arc<AtomicBool>,
+    slot_datas: &[Arc<SlotData>],
+    node: &Node,
+    warp_kind: WarpKind,
+) -> Result<Vec<Arc<SlotData>>> {
+    let slot_data = if let Some(slot_data) = slot_data_with_name(slot_datas, node, "input") {
+        slot_data
+    } else {
+        return Ok(Vec::new());
+    };
+
+    // Without a map there is nothing to move the input by.
+    let map = if let Some(map) = slot_data_with_name(slot_datas, node, warp_kind.map_name()) {
+        map.straight_image()
+    } else {
+        return Ok(vec![Arc::new(
+            SlotData::new(node.node_id, SlotId(0), slot_data.image.clone())
+                .alpha_mode(slot_data.alpha_mode),
+        )]);
+    };
+
+    let size = slot_data.size()?;
+    let (width, height) = (size.width as f32, size.height as f32);
+    let bufs = map.bufs();
+    let transient_buffers = bufs
+        .iter()
+        .map(|tbc| tbc.transient_buffer())
+        .collect::<Vec<_>>();
+    let map_value =
+        |channel: usize, x: u32, y: u32| transient_buffers[channel].buffer().get_pixel(x, y).0[0];
+
+    // The offset in pixels of the position in the input that ends up at each pixel.
+    let offset = |x: u32, y: u32| match warp_kind {
+        WarpKind::Warp(warp) => {
+            let slope_x = (map_value(0, x.wrapping_sample_add(1, size.width), y)
+                - map_value(0, x.wrapping_sample_subtract(1, size.width), y))
+                / 2.0
+                * width;
+            let slope_y = (map_value(0, x, y.wrapping_sample_add(1, size.height))
+                - map_value(0, x, y.wrapping_sample_subtract(1, size.height)))
+                / 2.0
+                * height;
+
+            (
+                slope_x * warp.intensity * width,
+                slope_y * warp.intensity * height,
+            )
+        }
+        WarpKind::Directional(directional_warp) => {
+            let (sin, cos) = (directional_warp.angle * TAU).sin_cos();
+            let distance = map_value(0, x, y) * directional_warp.intensity;
+
+            (cos * distance * width, sin * distance * height)
+        }
+        WarpKind::Vector(vector_warp) => {
+            let vector_x = map_value(0, x, y) * 2.0 - 1.0;
+            let vector_y = map_value(1, x, y) * 2.0 - 1.0;
+
+            (
+                vector_x * vector_warp.intensity * width,
+                vector_y * vector_warp.intensity * height,
+            )
+        }
+    };
+
+    Ok(vec![Arc::new(resample(
+        &shutdown,
+        node,
+        &slot_data,
+        EdgeMode::Wrap,
+        ResizeFilter::Triangle,
+        [1, 1],
+        |x, y| {
+            let (offset_x, offset_y) = offset(x, y);
+            (x as f32 + 0.5 + offset_x, y as f32 + 0.5 + offset_y)
+        },
+    )?)])
+}
@@ -1,15 +1,21 @@
 use crate::{
     edge::Edge,
     error::*,
-    node::{mix::MixType, node_type::NodeType, Node, Side, SlotInput, SlotOutput},
+    node::{
+        expression::Expression,
+        grayscale::GrayConversion,
+        mix::{Mix, MixType},
+        node_type::NodeType,
+        Node, Side, SlotInput, SlotOutput,
+    },
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    fmt,
+    env, fmt,
     fs::File,
     io::{self},
     mem,
-    path::PathBuf,
+    path::{Component, Path, PathBuf},
     sync::atomic::Ordering,
 };
 
@@ -17,6 +23,9 @@ use std::{
 pub struct NodeGraph {
     pub nodes: Vec<Node>,
     pub edges: Vec<Edge>,
+    /// How RGBA images are made gray when they are connected to gray input slots.
+    #[serde(default)]
+    pub gray_conversion: GrayConversion,
     #[serde(skip)]
     node_id_counter: NodeId,
 }
@@ -26,31 +35,49 @@ impl NodeGraph {
         Self {
             nodes: Vec::new(),
             edges: Vec::new(),
+            gray_conversion: GrayConversion::default(),
             node_id_counter: NodeId(0),
         }
     }
 
+    /// Loads a graph from a JSON file. Relative image paths that point to a file when
+    /// resolved against the directory of the graph file are made absolute, other paths are
+    /// left as they are.
     pub fn from_path(path: String) -> io::Result<Self> {
+        let graph_dir = Path::new(&path).parent().map(Path::to_path_buf);
         let mut graph = Self::import_json(path)?;
 
-        let node_id_counter =
-            if let Some(node_id) = graph.nodes.iter().map(|node| node.node_id).max() {
+        if let Some(graph_dir) = graph_dir {
+            for image_path in graph.image_paths_mut() {
+                let resolved = graph_dir.join(&image_path);
+                if image_path.is_relative() && resolved.is_file() {
+                    *image_path = absolute_path(&resolved)?;
+                }
+            }
+        }
+
+        graph.update_node_id_counter();
+
+        Ok(graph)
+    }
+
+    /// Sets the next `NodeId` to be handed out to one above the highest one in use, needed
+    /// after deserializing since the counter isn't serialized.
+    pub(crate) fn update_node_id_counter(&mut self) {
+        self.node_id_counter =
+            if let Some(node_id) = self.nodes.iter().map(|node| node.node_id).max() {
                 NodeId(node_id.0 + 1)
             } else {
                 NodeId(0)
             };
-
-        graph.node_id_counter = node_id_counter;
-
-        Ok(graph)
     }
 
     pub fn set_mix_type(&mut self, node_id: NodeId, mix_type: MixType) -> Result<()> {
         if let Some(node_index) = self.index_of_node(node_id) {
             match self.nodes[node_index].node_type {
-                NodeType::Mix(_) => {
+                NodeType::Mix(mix) => {
                     let mut node_clone: Node = (self.nodes[node_index]).clone();
-                    node_clone.node_type = NodeType::Mix(mix_type);
+                    node_clone.node_type = NodeType::Mix(Mix { mix_type, ..mix });
 
                     let _ = mem::replace(&mut self.nodes[node_index], node_clone);
                     Ok(())
@@ -62,6 +89,51 @@ impl NodeGraph {
         }
     }
 
+    /// Replaces the `Expression` of an expression node, if the new one is valid.
+    ///
+    /// `Edge`s to and from the node that no longer fit its slots are removed and returned. The
+    /// caller has to dirty the node, and the input nodes of the removed edges.
+    pub fn set_expression(&mut self, node_id: NodeId, expression: Expression) -> Result<Vec<Edge>> {
+        expression.check()?;
+
+        let node_index = self
+            .index_of_node(node_id)
+            .ok_or(TexProError::InvalidNodeId)?;
+        match self.nodes[node_index].node_type {
+            NodeType::Expression(_) => {
+                self.nodes[node_index].node_type = NodeType::Expression(expression);
+            }
+            _ => return Err(TexProError::InvalidNodeId),
+        }
+        self.nodes[node_index].cancel.store(true, Ordering::Relaxed);
+
+        let (kept_edges, removed_edges) =
+            mem::take(&mut self.edges).into_iter().partition(|edge| {
+                (edge.output_id != node_id && edge.input_id != node_id) || self.edge_fits(edge)
+            });
+        self.edges = kept_edges;
+
+        Ok(removed_edges)
+    }
+
+    /// Whether both slots of the `Edge` exist and their types fit together.
+    fn edge_fits(&self, edge: &Edge) -> bool {
+        let fits = || -> Result<()> {
+            let output_slot_type = self
+                .node(edge.output_id)?
+                .output_slot_with_id(edge.output_slot)?
+                .slot_type;
+            let input_slot_type = self
+                .node(edge.input_id)?
+                .input_slot_with_id(edge.input_slot)?
+                .slot_type;
+
+            output_slot_type.fits(input_slot_type)
+        };
+
+        fits().is_ok()
+    }
+
     pub fn set_image_node_path(&mut self, node_id: NodeId, path: PathBuf) -> Result<()> {
         if let Some(node_index) = self.index_of_node(node_id) {
             match self.nodes[node_index].node_type {
@@ -101,6 +173,22 @@ impl NodeGraph {
         Ok(())
     }
 
+    /// Like `export_json()`, but image paths are written relative to the directory of the
+    /// graph file, so the graph and its images can be moved together.
+    pub fn export_json_relative(&self, path: String) -> io::Result<()> {
+        let graph_dir = match Path::new(&path).parent() {
+            Some(graph_dir) => absolute_path(graph_dir)?,
+            None => return self.export_json(path),
+        };
+
+        let mut graph = self.clone();
+        for image_path in graph.image_paths_mut() {
+            *image_path = relative_path(&absolute_path(image_path)?, &graph_dir);
+        }
+
+        graph.export_json(path)
+    }
+
     fn import_json(path: String) -> io::Result<Self> {
         let file = File::open(path)?;
         Ok(serde_json::from_reader(file)?)
@@ -339,6 +427,68 @@ impl NodeGraph {
             .collect()
     }
 
+    /// Returns all files read by the nodes in the graph. Files read by nodes inside a
+    /// `NodeType::Graph` node are listed with the `NodeId` of the `Graph` node.
+    pub fn file_references(&self) -> Vec<FileReference> {
+        self.nodes
+            .iter()
+            .flat_map(|node| {
+                node.node_type
+                    .image_paths()
+                    .into_iter()
+                    .map(move |path| FileReference {
+                        node_id: node.node_id,
+                        path: path.to_path_buf(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the file references that don't point to an existing file.
+    pub fn unresolved_file_references(&self) -> Vec<FileReference> {
+        self.unresolved_file_references_in(&[])
+    }
+
+    /// Returns the file references that can't be found, neither where they point nor in
+    /// any of the given search paths. See `resolve_path()`.
+    pub fn unresolved_file_references_in(&self, search_paths: &[PathBuf]) -> Vec<FileReference> {
+        self.file_references()
+            .into_iter()
+            .filter(|file_reference| resolve_path(&file_reference.path, search_paths).is_none())
+            .collect()
+    }
+
+    /// Rewrites all image paths starting with `from` to instead start with `to`, for
+    /// instance when a folder of images has been moved. Returns the ids of the nodes that
+    /// were changed, `Graph` nodes included.
+    pub fn relink(&mut self, from: &Path, to: &Path) -> Vec<NodeId> {
+        let mut node_ids = Vec::new();
+
+        for node in &mut self.nodes {
+            let mut changed = false;
+
+            for image_path in node.node_type.image_paths_mut() {
+                if let Ok(rest) = image_path.strip_prefix(from) {
+                    *image_path = to.join(rest);
+                    changed = true;
+                }
+            }
+
+            if changed {
+                node_ids.push(node.node_id);
+            }
+        }
+
+        node_ids
+    }
+
+    fn image_paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.nodes
+            .iter_mut()
+            .flat_map(|node| node.node_type.image_paths_mut())
+            .collect()
+    }
+
     pub fn input_ids(&self) -> Vec<NodeId> {
         self.nodes
             .iter()
@@ -430,6 +580,12 @@ impl NodeGraph {
 
         output_slot_type.fits(input_slot_type)?;
 
+        for node in &[output_node, input_node] {
+            if let NodeType::Expression(ref expression) = node.node_type {
+                expression.check()?;
+            }
+        }
+
         // Discarding this result because we don't care if anything got disconnected.
         let _ = self.disconnect_slot(input_node_id, Side::Input, input_slot_id);
 
@@ -589,6 +745,76 @@ impl NodeGraph {
     }
 }
 
+/// Finds the file a path refers to. The path itself is tried first, then each search path
+/// joined with the path if it's relative, and finally each search path joined with the file
+/// name of the path.
+pub fn resolve_path(path: &Path, search_paths: &[PathBuf]) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.to_path_buf());
+    }
+
+    let relative = search_paths
+        .iter()
+        .filter(|_| path.is_relative())
+        .map(|search_path| search_path.join(path));
+    let file_name = path
+        .file_name()
+        .into_iter()
+        .flat_map(|file_name| search_paths.iter().map(move |dir| dir.join(file_name)));
+
+    relative
+        .chain(file_name)
+        .find(|candidate| candidate.is_file())
+}
+
+fn absolute_path(path: &Path) -> io::Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(env::current_dir()?.join(path))
+    }
+}
+
+/// Expresses the absolute `path` relative to the absolute directory `base`. If they don't
+/// share a root, `path` is returned unchanged.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path_components: Vec<Component> = path
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    let base_components: Vec<Component> = base
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+
+    let common = path_components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    if common == 0 {
+        return path.to_path_buf();
+    }
+
+    let mut output = PathBuf::new();
+    for _ in common..base_components.len() {
+        output.push("..");
+    }
+    for component in &path_components[common..] {
+        output.push(component);
+    }
+
+    output
+}
+
+/// A file read by a node in a `NodeGraph`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileReference {
+    pub node_id: NodeId,
+    pub path: PathBuf,
+}
+
 #[derive(
     Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
 )]
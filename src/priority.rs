@@ -260,7 +260,7 @@ mod tests {
         val: i8,
     ) -> NodeId {
         let node_id = node_graph
-            .add_node(Node::new(NodeType::Mix(MixType::default())))
+            .add_node(Node::new(NodeType::Mix(MixType::default().into())))
             .unwrap();
         let prio = node_graph.node(node_id).unwrap().priority;
         prio.set_priority(val);
@@ -32,7 +32,7 @@ impl ProcessPackManager {
     /// `max_count` limit.
     pub fn update(&mut self, mut process_packs: Vec<ProcessPack>) -> Result<Vec<ProcessPack>> {
         let mut output_packs = Vec::new();
-        self.remove_clean()?;
+        self.remove_not_processing()?;
         Self::sort_by_priority(&mut self.process_packs);
         self.process_packs.truncate(self.max_count);
 
@@ -95,7 +95,7 @@ impl ProcessPackManager {
         Ok(output_packs)
     }
 
-    fn remove_clean(&mut self) -> Result<()> {
+    fn remove_not_processing(&mut self) -> Result<()> {
         for i in (0..self.process_packs.len()).rev() {
             let node_state = self.process_packs[i]
                 .live_graph
@@ -104,7 +104,13 @@ impl ProcessPackManager {
 
             match node_state {
                 Ok(node_state) => {
-                    if node_state == NodeState::Clean {
+                    // Only nodes that are being processed hold on to their spot. Nodes that
+                    // went back to dirty, like when an input's data was missing, have to get a
+                    // new `ProcessPack` or they would block the spot forever.
+                    if !matches!(
+                        node_state,
+                        NodeState::Processing | NodeState::ProcessingDirty
+                    ) {
                         self.process_packs.remove(i);
                     }
                 }
@@ -5,17 +5,42 @@ use crate::{
     transient_buffer::{TransientBuffer, TransientBufferContainer},
 };
 use crate::{node::*, slot_data::*};
-use ::image::{imageops, DynamicImage, ImageBuffer};
+use ::image::{
+    codecs::hdr::HdrDecoder, imageops, io::Reader as ImageReader, DynamicImage, ImageBuffer,
+    ImageError, ImageFormat,
+};
 use std::{
     cmp::{max, min},
+    fs::File,
+    io::{BufRead, BufReader, Seek},
     path::Path,
     sync::{Arc, RwLock},
     u32,
 };
 
 pub fn deconstruct_image(image: &DynamicImage) -> Vec<BoxBuffer> {
-    let pixels = image.as_flat_samples_u8().unwrap().samples;
     let (width, height) = (image.width(), image.height());
+
+    // Samples are converted to `f32` without going through 8 bits, so 16 bit and floating point
+    // images keep their precision.
+    let pixels: Vec<ChannelPixel> = if let Some(samples) = image.as_flat_samples_u8() {
+        samples
+            .samples
+            .iter()
+            .map(|component| ChannelPixel::from(*component) / 255.)
+            .collect()
+    } else if let Some(samples) = image.as_flat_samples_u16() {
+        samples
+            .samples
+            .iter()
+            .map(|component| ChannelPixel::from(*component) / 65535.)
+            .collect()
+    } else if let Some(samples) = image.as_flat_samples_f32() {
+        samples.samples.to_vec()
+    } else {
+        image.to_rgba32f().into_raw()
+    };
+
     let pixel_count = (width * height) as usize;
     let channel_count = pixels.len() / pixel_count;
     let max_channel_count = 4;
@@ -28,7 +53,7 @@ pub fn deconstruct_image(image: &DynamicImage) -> Vec<BoxBuffer> {
     let mut current_channel = 0;
 
     for component in pixels {
-        pixel_vecs[current_channel].push(ChannelPixel::from(*component) / 255.);
+        pixel_vecs[current_channel].push(component);
         current_channel = (current_channel + 1) % channel_count;
     }
 
@@ -153,7 +178,17 @@ pub(crate) fn resize_buffers(
         .iter()
         .map(|slot_data| {
             if slot_data.size().unwrap() != size {
-                let resized_image = match &slot_data.image {
+                // Straight RGBA is filtered in premultiplied space, otherwise the colors of
+                // transparent pixels bleed into the visible ones.
+                let straight_rgba =
+                    slot_data.image.is_rgba() && slot_data.alpha_mode == AlphaMode::Straight;
+                let image = if straight_rgba {
+                    slot_data.image.premultiply()
+                } else {
+                    slot_data.image.clone()
+                };
+
+                let resized_image = match &image {
                     SlotImage::Gray(buf) => {
                         SlotImage::Gray(Arc::new(TransientBufferContainer::new(Arc::new(
                             RwLock::new(TransientBuffer::new(Box::new(imageops::resize(
@@ -200,11 +235,16 @@ pub(crate) fn resize_buffers(
                     ]),
                 };
 
-                Arc::new(SlotData::new(
-                    slot_data.node_id,
-                    slot_data.slot_id,
-                    resized_image,
-                ))
+                let resized_image = if straight_rgba {
+                    resized_image.unpremultiply()
+                } else {
+                    resized_image
+                };
+
+                Arc::new(
+                    SlotData::new(slot_data.node_id, slot_data.slot_id, resized_image)
+                        .alpha_mode(slot_data.alpha_mode),
+                )
             } else {
                 // Does not need to be resized
                 Arc::clone(slot_data)
@@ -216,6 +256,15 @@ pub(crate) fn resize_buffers(
 }
 
 pub fn read_slot_image<P: AsRef<Path>>(path: P) -> Result<SlotImage> {
+    slot_image_from_dynamic(&open_image(path)?)
+}
+
+/// Decodes a `SlotImage` from an encoded image, detecting the format from its contents.
+pub fn decode_slot_image<R: BufRead + Seek>(reader: R) -> Result<SlotImage> {
+    slot_image_from_dynamic(&decode_image(reader, None)?)
+}
+
+fn slot_image_from_dynamic(image: &DynamicImage) -> Result<SlotImage> {
     fn pop_vec_to_arc_buffer(
         width: u32,
         height: u32,
@@ -238,8 +287,7 @@ pub fn read_slot_image<P: AsRef<Path>>(path: P) -> Result<SlotImage> {
         ))))
     }
 
-    let image = ::image::open(path)?;
-    let mut buffers = deconstruct_image(&image);
+    let mut buffers = deconstruct_image(image);
     let width = buffers[0].width();
     let height = buffers[0].height();
 
@@ -259,3 +307,42 @@ pub fn read_slot_image<P: AsRef<Path>>(path: P) -> Result<SlotImage> {
         }
     }
 }
+
+/// Opens an image file without quantizing it, using the file extension to tell the format.
+fn open_image<P: AsRef<Path>>(path: P) -> Result<DynamicImage> {
+    let path = path.as_ref();
+    let format = ImageFormat::from_path(path)?;
+    let file = File::open(path).map_err(ImageError::IoError)?;
+    decode_image(BufReader::new(file), Some(format))
+}
+
+/// Decodes an image without quantizing it. If no format is given it's guessed from the
+/// contents.
+///
+/// Radiance HDR files are decoded separately because the `image` crate converts them to 8 bits
+/// when they are decoded the regular way.
+fn decode_image<R: BufRead + Seek>(reader: R, format: Option<ImageFormat>) -> Result<DynamicImage> {
+    let reader = match format {
+        Some(format) => ImageReader::with_format(reader, format),
+        None => ImageReader::new(reader).with_guessed_format()?,
+    };
+
+    if reader.format() == Some(ImageFormat::Hdr) {
+        let decoder = HdrDecoder::new(reader.into_inner())?;
+        let (width, height) = {
+            let metadata = decoder.metadata();
+            (metadata.width, metadata.height)
+        };
+        let pixels = decoder
+            .read_image_hdr()?
+            .into_iter()
+            .flat_map(|pixel| pixel.0)
+            .collect();
+
+        Ok(DynamicImage::ImageRgb32F(
+            ImageBuffer::from_raw(width, height, pixels).ok_or(TexProError::InvalidBufferCount)?,
+        ))
+    } else {
+        Ok(reader.decode()?)
+    }
+}
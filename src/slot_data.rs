@@ -31,11 +31,34 @@ impl Size {
 
 pub type ChannelPixel = f32;
 
+/// Whether the color channels of RGBA data have been multiplied by alpha.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AlphaMode {
+    Straight,
+    Premultiplied,
+}
+
+impl Default for AlphaMode {
+    fn default() -> Self {
+        Self::Straight
+    }
+}
+
+impl fmt::Display for AlphaMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Straight => write!(f, "Straight"),
+            Self::Premultiplied => write!(f, "Premultiplied"),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SlotData {
     pub node_id: NodeId,
     pub slot_id: SlotId,
     pub image: SlotImage,
+    pub alpha_mode: AlphaMode,
 }
 
 impl Display for SlotData {
@@ -56,11 +79,25 @@ impl SlotData {
             node_id,
             slot_id,
             image,
+            alpha_mode: AlphaMode::default(),
         }
     }
 
+    pub fn alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
     pub fn from_self(&self) -> Self {
-        Self::new(self.node_id, self.slot_id, self.image.from_self())
+        Self::new(self.node_id, self.slot_id, self.image.from_self()).alpha_mode(self.alpha_mode)
+    }
+
+    /// Returns the image with straight alpha, unpremultiplying it if needed.
+    pub fn straight_image(&self) -> SlotImage {
+        match self.alpha_mode {
+            AlphaMode::Straight => self.image.clone(),
+            AlphaMode::Premultiplied => self.image.unpremultiply(),
+        }
     }
 
     pub fn size(&self) -> Result<Size> {
@@ -1,11 +1,16 @@
 use crate::{
     error::*,
+    export::{self, ExportOptions},
+    node::grayscale::GrayConversion,
+    shared::decode_slot_image,
     slot_data::{ChannelPixel, Size, SrgbColorSpace},
     transient_buffer::{TransientBuffer, TransientBufferContainer},
 };
 use image::{ImageBuffer, Luma};
 use std::{
+    io::{BufReader, Cursor, Read, Seek, Write},
     mem,
+    path::Path,
     sync::{Arc, RwLock},
 };
 
@@ -101,6 +106,60 @@ impl SlotImage {
         Self::from_buffers_rgba(&mut buffers)
     }
 
+    /// Decodes an encoded image, like the contents of a PNG file. The format is detected from
+    /// the data.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_reader(Cursor::new(bytes))
+    }
+
+    /// Decodes an encoded image from a reader. The format is detected from the data.
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Self> {
+        decode_slot_image(BufReader::new(reader))
+    }
+
+    /// Creates an image from interleaved 8 bit RGBA values.
+    pub fn from_rgba8(size: Size, pixels: &[u8]) -> Result<Self> {
+        Self::from_interleaved(size, pixels, |value| value as f32 / 255.)
+    }
+
+    /// Creates an image from interleaved 16 bit RGBA values.
+    pub fn from_rgba16(size: Size, pixels: &[u16]) -> Result<Self> {
+        Self::from_interleaved(size, pixels, |value| value as f32 / 65535.)
+    }
+
+    /// Creates an image from interleaved `f32` RGBA values.
+    pub fn from_rgba_f32(size: Size, pixels: &[f32]) -> Result<Self> {
+        Self::from_interleaved(size, pixels, |value| value)
+    }
+
+    fn from_interleaved<T: Copy>(
+        size: Size,
+        pixels: &[T],
+        to_f32: impl Fn(T) -> ChannelPixel,
+    ) -> Result<Self> {
+        if pixels.len() != size.pixel_count() * 4 {
+            return Err(TexProError::InvalidBufferCount);
+        }
+
+        let mut buffers = (0..4)
+            .map(|channel| {
+                Buffer::from_raw(
+                    size.width,
+                    size.height,
+                    pixels
+                        .iter()
+                        .skip(channel)
+                        .step_by(4)
+                        .map(|value| to_f32(*value))
+                        .collect(),
+                )
+                .ok_or(TexProError::InvalidBufferCount)
+            })
+            .collect::<Result<Vec<Buffer>>>()?;
+
+        Self::from_buffers_rgba(&mut buffers)
+    }
+
     pub fn from_self(&self) -> Self {
         match self {
             Self::Gray(buf) => Self::Gray(Arc::new(buf.from_self())),
@@ -169,10 +228,43 @@ impl SlotImage {
         })
     }
 
+    #[inline]
+    pub(crate) fn f32_to_u16(value: f32) -> u16 {
+        (value.clamp(0.0, 1.0) * 65535.).round() as u16
+    }
+
+    /// Returns the image as interleaved RGBA values without any quantization.
+    pub fn to_f32(&self) -> Result<Vec<f32>> {
+        Ok(match self {
+            Self::Gray(buf) => buf
+                .transient_buffer()
+                .buffer()
+                .pixels()
+                .flat_map(|x| vec![x[0], x[0], x[0], 1.0])
+                .collect(),
+            Self::Rgba(bufs) => bufs[0]
+                .transient_buffer()
+                .buffer()
+                .pixels()
+                .zip(bufs[1].transient_buffer().buffer().pixels())
+                .zip(bufs[2].transient_buffer().buffer().pixels())
+                .zip(bufs[3].transient_buffer().buffer().pixels())
+                .flat_map(|(((r, g), b), a)| vec![r[0], g[0], b[0], a[0]])
+                .collect(),
+        })
+    }
+
+    /// Returns the image as interleaved 16 bit RGBA values.
+    pub fn to_u16(&self) -> Result<Vec<u16>> {
+        Ok(self.to_f32()?.into_iter().map(Self::f32_to_u16).collect())
+    }
+
+    /// Returns the image as interleaved 8 bit RGBA values, with the color channels encoded as
+    /// sRGB. Alpha is left linear.
     pub fn to_u8_srgb(&self) -> Result<Vec<u8>> {
         #[inline]
         fn f32_to_u8_srgb(value: f32) -> u8 {
-            ((value.clamp(0.0, 1.0).srgb_to_linear() * 255.).min(255.)) as u8
+            ((value.clamp(0.0, 1.0).linear_to_srgb() * 255.).min(255.)) as u8
         }
 
         Ok(match self {
@@ -206,7 +298,89 @@ impl SlotImage {
         })
     }
 
-    /// Converts to and from grayscale and rgba.
+    /// Converts the color channels from sRGB to linear, alpha is left as it is.
+    pub fn srgb_to_linear(&self) -> Result<Self> {
+        self.map_color(f32::srgb_to_linear)
+    }
+
+    /// Converts the color channels from linear to sRGB, alpha is left as it is.
+    pub fn linear_to_srgb(&self) -> Result<Self> {
+        self.map_color(f32::linear_to_srgb)
+    }
+
+    fn map_color(&self, f: fn(f32) -> f32) -> Result<Self> {
+        let map = |buf: &Arc<TransientBufferContainer>| {
+            let mut buffer = buf.transient_buffer().buffer().clone();
+            for value in buffer.iter_mut() {
+                *value = f(*value);
+            }
+            Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
+                TransientBuffer::new(Box::new(buffer)),
+            ))))
+        };
+
+        Ok(match self {
+            Self::Gray(buf) => Self::Gray(map(buf)),
+            Self::Rgba(bufs) => Self::Rgba([
+                map(&bufs[0]),
+                map(&bufs[1]),
+                map(&bufs[2]),
+                Arc::clone(&bufs[3]),
+            ]),
+        })
+    }
+
+    /// Multiplies the color channels by alpha. Gray images are returned as they are.
+    pub fn premultiply(&self) -> Self {
+        self.map_with_alpha(|value, alpha| value * alpha)
+    }
+
+    /// Divides the color channels by alpha, colors where alpha is zero or less become black.
+    /// Gray images are returned as they are.
+    pub fn unpremultiply(&self) -> Self {
+        self.map_with_alpha(|value, alpha| if alpha > 0.0 { value / alpha } else { 0.0 })
+    }
+
+    fn map_with_alpha(&self, f: fn(f32, f32) -> f32) -> Self {
+        match self {
+            Self::Gray(_) => self.clone(),
+            Self::Rgba(bufs) => {
+                let alpha = bufs[3].transient_buffer();
+                let alpha = alpha.buffer();
+
+                let map = |buf: &Arc<TransientBufferContainer>| {
+                    let mut buffer = buf.transient_buffer().buffer().clone();
+                    for (value, alpha) in buffer.iter_mut().zip(alpha.iter()) {
+                        *value = f(*value, *alpha);
+                    }
+                    Arc::new(TransientBufferContainer::new(Arc::new(RwLock::new(
+                        TransientBuffer::new(Box::new(buffer)),
+                    ))))
+                };
+
+                Self::Rgba([
+                    map(&bufs[0]),
+                    map(&bufs[1]),
+                    map(&bufs[2]),
+                    Arc::clone(&bufs[3]),
+                ])
+            }
+        }
+    }
+
+    /// Encodes the image into the given writer with the given bit depth, channels, color
+    /// encoding and format.
+    pub fn encode<W: Write + Seek>(&self, writer: &mut W, options: &ExportOptions) -> Result<()> {
+        export::encode(self, writer, options)
+    }
+
+    /// Writes the image to a file with the given bit depth, channels, color encoding and format.
+    pub fn export<P: AsRef<Path>>(&self, path: P, options: &ExportOptions) -> Result<()> {
+        export::export(self, path, options)
+    }
+
+    /// Converts to and from grayscale and rgba. RGBA is made gray by averaging the color
+    /// channels, use `to_gray()` for other conversions.
     ///
     /// Note: This should probably be replaced by From implementations.
     pub fn as_type(&self, rgba: bool) -> Result<Self> {
@@ -231,30 +405,43 @@ impl SlotImage {
                     )),
                 )))),
             ]),
-            Self::Rgba(bufs) => {
-                let (buf_r, buf_g, buf_b) = (
-                    bufs[0].transient_buffer(),
-                    bufs[1].transient_buffer(),
-                    bufs[2].transient_buffer(),
-                );
-                let (buf_r, buf_g, buf_b) = (buf_r.buffer(), buf_g.buffer(), buf_b.buffer());
-
-                Self::Gray(Arc::new(TransientBufferContainer::new(Arc::new(
-                    RwLock::new(TransientBuffer::new(Box::new(Buffer::from_fn(
-                        width,
-                        height,
-                        |x, y| {
-                            Luma([(buf_r.get_pixel(x, y).0[0]
-                                + buf_g.get_pixel(x, y).0[0]
-                                + buf_b.get_pixel(x, y).0[0])
-                                / 3.])
-                        },
-                    )))),
-                ))))
-            }
+            Self::Rgba(_) => self.to_gray(GrayConversion::default())?,
         })
     }
 
+    /// Converts the image to grayscale with the given conversion. Gray images are returned as
+    /// they are.
+    pub fn to_gray(&self, gray_conversion: GrayConversion) -> Result<Self> {
+        let bufs = match self {
+            Self::Gray(_) => return Ok(self.clone()),
+            Self::Rgba(bufs) => bufs,
+        };
+
+        let (width, height) = {
+            let size = self.size()?;
+            (size.width, size.height)
+        };
+
+        let (buf_r, buf_g, buf_b) = (
+            bufs[0].transient_buffer(),
+            bufs[1].transient_buffer(),
+            bufs[2].transient_buffer(),
+        );
+        let (buf_r, buf_g, buf_b) = (buf_r.buffer(), buf_g.buffer(), buf_b.buffer());
+
+        Ok(Self::Gray(Arc::new(TransientBufferContainer::new(
+            Arc::new(RwLock::new(TransientBuffer::new(Box::new(
+                Buffer::from_fn(width, height, |x, y| {
+                    Luma([gray_conversion.gray([
+                        buf_r.get_pixel(x, y).0[0],
+                        buf_g.get_pixel(x, y).0[0],
+                        buf_b.get_pixel(x, y).0[0],
+                    ])])
+                }),
+            )))),
+        ))))
+    }
+
     pub fn bufs(&self) -> Vec<Arc<TransientBufferContainer>> {
         match self {
             Self::Gray(buf) => vec![Arc::clone(buf)],
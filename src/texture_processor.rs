@@ -1,13 +1,19 @@
 use crate::{
     engine,
-    error::Result,
+    error::{Result, TexProError},
     live_graph::*,
+    node::{
+        custom::{CustomNode, NodeProcessor},
+        node_type::NodeType,
+    },
     node_graph::*,
     process_pack::ProcessPackManager,
     slot_data::*,
     transient_buffer::{TransientBufferContainer, TransientBufferQueue},
 };
 use std::{
+    collections::BTreeMap,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, RwLock,
@@ -22,6 +28,8 @@ pub struct TextureProcessor {
     pub memory_threshold: Arc<AtomicUsize>,
     pub(crate) process_pack_manager: RwLock<ProcessPackManager>,
     pub transient_buffer_queue: Arc<RwLock<TransientBufferQueue>>,
+    search_paths: RwLock<Vec<PathBuf>>,
+    node_processors: RwLock<BTreeMap<String, Arc<dyn NodeProcessor>>>,
 }
 
 impl Drop for TextureProcessor {
@@ -46,6 +54,8 @@ impl TextureProcessor {
             add_buffer_queue,
             process_pack_manager: RwLock::new(ProcessPackManager::new()),
             transient_buffer_queue: Arc::clone(&transient_buffer_queue),
+            search_paths: RwLock::new(Vec::new()),
+            node_processors: RwLock::new(BTreeMap::new()),
         });
         let output_send = Arc::clone(&output);
 
@@ -99,11 +109,106 @@ impl TextureProcessor {
             if let Ok(live_graph) = live_graph.try_read() {
                 if let Ok(size) = live_graph.slot_data_size(node_id, slot_id) {
                     return Ok(size);
+                } else if live_graph.node_state(node_id)? == NodeState::Failed {
+                    return Err(TexProError::NodeFailed);
                 }
             }
         }
     }
 
+    /// Directories that are searched for images that can't be found at their given path,
+    /// in order. See `node_graph::resolve_path()`.
+    pub fn search_paths(&self) -> Result<Vec<PathBuf>> {
+        Ok(self.search_paths.read()?.clone())
+    }
+
+    /// Replaces the search paths. Nodes that read images are processed again, since their
+    /// images may now be found somewhere else.
+    ///
+    /// Don't hold a lock on any `LiveGraph` when calling this.
+    pub fn set_search_paths(&self, search_paths: Vec<PathBuf>) -> Result<()> {
+        *self.search_paths.write()? = search_paths;
+        self.dirty_image_nodes()
+    }
+
+    /// Adds a search path after the existing ones, see `set_search_paths()`.
+    ///
+    /// Don't hold a lock on any `LiveGraph` when calling this.
+    pub fn add_search_path(&self, search_path: PathBuf) -> Result<()> {
+        self.search_paths.write()?.push(search_path);
+        self.dirty_image_nodes()
+    }
+
+    fn dirty_image_nodes(&self) -> Result<()> {
+        for live_graph in self.live_graphs.read()?.iter() {
+            let mut live_graph = live_graph.write()?;
+            let node_ids = live_graph
+                .node_graph
+                .nodes()
+                .iter()
+                .filter(|node| !node.node_type.image_paths().is_empty())
+                .map(|node| node.node_id)
+                .collect::<Vec<NodeId>>();
+
+            for node_id in node_ids {
+                live_graph.set_state(node_id, NodeState::Dirty)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Registers a custom node type, replacing any node type with the same id. Nodes of the
+    /// type that failed because it was not registered are processed again.
+    ///
+    /// Don't hold a lock on any `LiveGraph` when calling this.
+    pub fn register_node_processor(&self, node_processor: Arc<dyn NodeProcessor>) -> Result<()> {
+        let type_id = node_processor.id().to_string();
+        self.node_processors
+            .write()?
+            .insert(type_id.clone(), node_processor);
+
+        for live_graph in self.live_graphs.read()?.iter() {
+            let mut live_graph = live_graph.write()?;
+            let node_ids = live_graph
+                .node_graph
+                .nodes()
+                .iter()
+                .filter(|node| match &node.node_type {
+                    NodeType::Custom(custom_node) => custom_node.type_id == type_id,
+                    _ => false,
+                })
+                .map(|node| node.node_id)
+                .collect::<Vec<NodeId>>();
+
+            for node_id in node_ids {
+                if live_graph.node_state(node_id)? == NodeState::Failed {
+                    live_graph.set_state(node_id, NodeState::Dirty)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn node_processor(&self, type_id: &str) -> Result<Option<Arc<dyn NodeProcessor>>> {
+        Ok(self.node_processors.read()?.get(type_id).map(Arc::clone))
+    }
+
+    /// The ids of all registered custom node types.
+    pub fn node_processor_ids(&self) -> Result<Vec<String>> {
+        Ok(self.node_processors.read()?.keys().cloned().collect())
+    }
+
+    /// Creates a `NodeType` for a registered custom node type, with default parameters.
+    pub fn custom_node_type(&self, type_id: &str) -> Result<NodeType> {
+        let node_processor = self
+            .node_processor(type_id)?
+            .ok_or_else(|| TexProError::UnknownNodeType(type_id.into()))?;
+
+        Ok(NodeType::Custom(CustomNode::new(&*node_processor)))
+    }
+
     pub fn processing_node_count(&self) -> Result<usize> {
         Ok(self.process_pack_manager.read()?.process_packs().len())
     }
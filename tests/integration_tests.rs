@@ -1,12 +1,40 @@
 use vismut_core::{
+    bundle::Bundle,
+    error::TexProError,
+    export::{
+        bcn::BcFormat,
+        dds::{self, DdsOptions},
+        ktx2::{Ktx2Format, Ktx2Options},
+        pack::{Channel, ChannelPack, PackPreset, PackedChannel},
+        BitDepth, ChannelLayout, ColorEncoding, ExportFormat, ExportOptions, TextureUsage,
+    },
     live_graph::{LiveGraph, NodeState},
+    mipmap::{self, MipFilter, MipOptions},
     node::{
-        embed::EmbeddedSlotDataId, mix::MixType, node_type::NodeType, Node, ResizeFilter,
-        ResizePolicy, Side,
+        ambient_occlusion::AmbientOcclusion,
+        convert_color::{ColorModel, ConvertColor},
+        curvature::Curvature,
+        curves::{Curve, CurvePoint, Curves},
+        custom::{
+            CustomNode, CustomSlot, NodeProcessor, Parameter, ParameterValue, ProcessContext,
+        },
+        embed::EmbeddedSlotDataId,
+        expression::Expression,
+        gradient_map::{GradientInterpolation, GradientMap, GradientStop},
+        grayscale::GrayConversion,
+        height_to_normal::{HeightToNormal, NormalFilter, NormalFormat},
+        hsl_adjust::HslAdjust,
+        levels::{AutoLevels, Level, Levels, ToneChannels},
+        make_tile::{MakeTile, TileMethod},
+        mix::{Mix, MixAlpha, MixType},
+        node_type::NodeType,
+        transform::{EdgeMode, Transform},
+        warp::{DirectionalWarp, VectorWarp, Warp},
+        ColorSpace, Node, ResizeFilter, ResizePolicy, Side, SlotType,
     },
-    node_graph::{NodeGraph, NodeId, SlotId},
-    slot_data::Size,
-    slot_image::SlotImage,
+    node_graph::{FileReference, NodeGraph, NodeId, SlotId},
+    slot_data::{AlphaMode, Size, SlotData, SrgbColorSpace},
+    slot_image::{Buffer, SlotImage},
     texture_processor::TextureProcessor,
 };
 use ntest::timeout;
@@ -118,7 +146,7 @@ fn deadlock() {
             .add_node(Node::new(NodeType::Value(0.0)))
             .unwrap();
         let mix_node_1 = live_graph
-            .add_node(Node::new(NodeType::Mix(MixType::Add)))
+            .add_node(Node::new(NodeType::Mix(MixType::Add.into())))
             .unwrap();
 
         live_graph
@@ -169,10 +197,10 @@ fn drive_cache() {
 
         // 2 mix nodes should be 2 nodes * 4 channels * 4 bytes = 32 bytes
         let mix_node_1 = live_graph
-            .add_node(Node::new(NodeType::Mix(MixType::Add)))
+            .add_node(Node::new(NodeType::Mix(MixType::Add.into())))
             .unwrap();
         let mix_node_2 = live_graph
-            .add_node(Node::new(NodeType::Mix(MixType::Add)))
+            .add_node(Node::new(NodeType::Mix(MixType::Add.into())))
             .unwrap();
 
         live_graph
@@ -314,7 +342,7 @@ fn request_empty_buffer() {
         let mut live_graph = live_graph.write().unwrap();
 
         let mix_node = live_graph
-            .add_node(Node::new(NodeType::Mix(MixType::default())))
+            .add_node(Node::new(NodeType::Mix(MixType::default().into())))
             .unwrap();
         let output_node = live_graph
             .add_node(Node::new(NodeType::OutputRgba("out".into())))
@@ -352,7 +380,7 @@ fn input_output_intercept() {
             .unwrap();
         let resize_node_1 = live_graph
             .add_node(
-                Node::new(NodeType::Mix(MixType::default()))
+                Node::new(NodeType::Mix(MixType::default().into()))
                     .resize_filter(ResizeFilter::Lanczos3)
                     .resize_policy(ResizePolicy::SpecificSize(Size::new(
                         SIZE_SMALL, SIZE_SMALL,
@@ -361,7 +389,7 @@ fn input_output_intercept() {
             .unwrap();
         let resize_node_2 = live_graph
             .add_node(
-                Node::new(NodeType::Mix(MixType::default()))
+                Node::new(NodeType::Mix(MixType::default().into()))
                     .resize_filter(ResizeFilter::Lanczos3)
                     .resize_policy(ResizePolicy::SpecificSize(Size::new(
                         SIZE_LARGE, SIZE_LARGE,
@@ -370,7 +398,7 @@ fn input_output_intercept() {
             .unwrap();
         let resize_node_3 = live_graph
             .add_node(
-                Node::new(NodeType::Mix(MixType::default()))
+                Node::new(NodeType::Mix(MixType::default().into()))
                     .resize_filter(ResizeFilter::Lanczos3)
                     .resize_policy(ResizePolicy::SpecificSize(Size::new(SIZE, SIZE))),
             )
@@ -434,7 +462,7 @@ fn priority_internal(max_processing: usize, large_priority: i8) -> bool {
             .unwrap();
         let resize_small_1 = live_graph
             .add_node(
-                Node::new(NodeType::Mix(MixType::default()))
+                Node::new(NodeType::Mix(MixType::default().into()))
                     .resize_filter(ResizeFilter::Nearest)
                     .resize_policy(ResizePolicy::SpecificSize(Size::new(
                         SIZE_SMALL, SIZE_SMALL,
@@ -443,7 +471,7 @@ fn priority_internal(max_processing: usize, large_priority: i8) -> bool {
             .unwrap();
         let resize_small_2 = live_graph
             .add_node(
-                Node::new(NodeType::Mix(MixType::default()))
+                Node::new(NodeType::Mix(MixType::default().into()))
                     .resize_filter(ResizeFilter::Nearest)
                     .resize_policy(ResizePolicy::SpecificSize(Size::new(
                         SIZE_SMALL, SIZE_SMALL,
@@ -452,7 +480,7 @@ fn priority_internal(max_processing: usize, large_priority: i8) -> bool {
             .unwrap();
         let resize_large = live_graph
             .add_node(
-                Node::new(NodeType::Mix(MixType::default()))
+                Node::new(NodeType::Mix(MixType::default().into()))
                     .resize_filter(ResizeFilter::Nearest)
                     .resize_policy(ResizePolicy::SpecificSize(Size::new(
                         SIZE_LARGE, SIZE_LARGE,
@@ -491,6 +519,49 @@ fn priority_internal(max_processing: usize, large_priority: i8) -> bool {
     }
 }
 
+#[test]
+#[timeout(20_000)]
+fn process_spot_released_when_dirty() {
+    // Reconnecting dirties the mix node and the output node below it. When a node goes back to
+    // dirty instead of being processed, like when its input is not processed yet, it must not
+    // keep the only processing spot.
+    let tex_pro = tex_pro_new();
+    tex_pro.set_max_processing_nodes(1).unwrap();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let (value_node, mix_node, output_node) = {
+        let mut live_graph = live_graph.write().unwrap();
+        let value_node = live_graph
+            .add_node(Node::new(NodeType::Value(0.25)))
+            .unwrap();
+        let mix_node = live_graph
+            .add_node(Node::new(NodeType::Mix(Mix::new(MixType::Add))))
+            .unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputGray("out".into())))
+            .unwrap();
+        live_graph
+            .connect(value_node, mix_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(mix_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        (value_node, mix_node, output_node)
+    };
+    LiveGraph::await_clean_read(&live_graph, output_node).unwrap();
+
+    live_graph
+        .write()
+        .unwrap()
+        .connect(value_node, mix_node, SlotId(0), SlotId(1))
+        .unwrap();
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    assert_pixels_eq(&pixels, &[0.5, 0.5, 0.5, 1.0]);
+}
+
 #[test]
 #[timeout(20_000)]
 fn mix_node_single_input() {
@@ -504,7 +575,7 @@ fn mix_node_single_input() {
             .add_node(Node::new(NodeType::Image(IMAGE_2.into())))
             .unwrap();
         let mix_node = live_graph
-            .add_node(Node::new(NodeType::Mix(MixType::Add)))
+            .add_node(Node::new(NodeType::Mix(MixType::Add.into())))
             .unwrap();
         let output_node = live_graph
             .add_node(Node::new(NodeType::OutputGray("out".into())))
@@ -534,7 +605,7 @@ fn mix_node_single_input_2() {
             .add_node(Node::new(NodeType::Image(IMAGE_2.into())))
             .unwrap();
         let mix_node = live_graph
-            .add_node(Node::new(NodeType::Mix(MixType::Subtract)))
+            .add_node(Node::new(NodeType::Mix(MixType::Subtract.into())))
             .unwrap();
         let output_node = live_graph
             .add_node(Node::new(NodeType::OutputGray("out".into())))
@@ -688,7 +759,7 @@ fn irregular_sizes() {
             .add_node(Node::new(NodeType::Image(HEART_110.into())))
             .unwrap();
         let mix = live_graph
-            .add_node(Node::new(NodeType::Mix(MixType::default())))
+            .add_node(Node::new(NodeType::Mix(MixType::default().into())))
             .unwrap();
         let output_node = live_graph
             .add_node(Node::new(NodeType::OutputRgba("out".into())))
@@ -794,7 +865,7 @@ fn connect_invalid_slot() {
         let value_node = live_graph.add_node(Node::new(NodeType::Value(0.))).unwrap();
 
         let output_node = live_graph
-            .add_node(Node::new(NodeType::Mix(MixType::default())))
+            .add_node(Node::new(NodeType::Mix(MixType::default().into())))
             .unwrap();
 
         assert!(live_graph
@@ -805,6 +876,9 @@ fn connect_invalid_slot() {
             .is_ok());
         assert!(live_graph
             .connect(value_node, output_node, SlotId(0), SlotId(2))
+            .is_ok());
+        assert!(live_graph
+            .connect(value_node, output_node, SlotId(0), SlotId(3))
             .is_err());
     }
 }
@@ -864,7 +938,7 @@ fn resize_policy_test(
             .unwrap();
 
         let mix_node = {
-            let mut mix_node = Node::new(NodeType::Mix(MixType::default()));
+            let mut mix_node = Node::new(NodeType::Mix(MixType::default().into()));
             mix_node.resize_policy = resize_policy;
             live_graph.add_node(mix_node).unwrap()
         };
@@ -1002,7 +1076,7 @@ fn invert_graph_node() {
             .add_node(Node::new(NodeType::InputGray("in".into())))
             .unwrap();
         let subtract_node = invert_graph
-            .add_node(Node::new(NodeType::Mix(MixType::Subtract)))
+            .add_node(Node::new(NodeType::Mix(MixType::Subtract.into())))
             .unwrap();
         let nested_output_node = invert_graph
             .add_node(Node::new(NodeType::OutputGray("out".into())))
@@ -1083,7 +1157,7 @@ fn invert_graph_node_export() {
         .add_node(Node::new(NodeType::InputGray("in".into())))
         .unwrap();
     let subtract_node = invert_graph
-        .add_node(Node::new(NodeType::Mix(MixType::Subtract)))
+        .add_node(Node::new(NodeType::Mix(MixType::Subtract.into())))
         .unwrap();
     let nested_output_node = invert_graph
         .add_node(Node::new(NodeType::OutputGray("out".into())))
@@ -1361,7 +1435,9 @@ fn height_to_normal_node() {
             .add_node(Node::new(NodeType::SeparateRgba))
             .unwrap();
         let h2n_node = live_graph
-            .add_node(Node::new(NodeType::HeightToNormal))
+            .add_node(Node::new(NodeType::HeightToNormal(
+                HeightToNormal::default(),
+            )))
             .unwrap();
         let output_node = live_graph
             .add_node(Node::new(NodeType::OutputRgba("out".into())))
@@ -1449,7 +1525,7 @@ fn mix_node_test_gray(mix_type: MixType, name: &str) {
             .add_node(Node::new(NodeType::SeparateRgba))
             .unwrap();
         let input_node = live_graph
-            .add_node(Node::new(NodeType::Mix(mix_type)))
+            .add_node(Node::new(NodeType::Mix(mix_type.into())))
             .unwrap();
         let output_node = live_graph
             .add_node(Node::new(NodeType::OutputGray("out".into())))
@@ -1487,7 +1563,7 @@ fn mix_node_test_rgba(mix_type: MixType, name: &str) {
             .add_node(Node::new(NodeType::Image(IMAGE_2.into())))
             .unwrap();
         let multiply_node = live_graph
-            .add_node(Node::new(NodeType::Mix(mix_type)))
+            .add_node(Node::new(NodeType::Mix(mix_type.into())))
             .unwrap();
         let output_node = live_graph
             .add_node(Node::new(NodeType::OutputRgba("out".into())))
@@ -1566,3 +1642,3673 @@ fn pow_node_gray() {
 fn pow_node_rgba() {
     mix_node_test_rgba(MixType::Pow, "pow_node_rgba.png");
 }
+
+#[test]
+#[timeout(20_000)]
+fn screen_node_gray() {
+    mix_node_test_gray(MixType::Screen, "screen_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn screen_node_rgba() {
+    mix_node_test_rgba(MixType::Screen, "screen_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn overlay_node_gray() {
+    mix_node_test_gray(MixType::Overlay, "overlay_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn overlay_node_rgba() {
+    mix_node_test_rgba(MixType::Overlay, "overlay_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn soft_light_node_gray() {
+    mix_node_test_gray(MixType::SoftLight, "soft_light_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn soft_light_node_rgba() {
+    mix_node_test_rgba(MixType::SoftLight, "soft_light_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn hard_light_node_gray() {
+    mix_node_test_gray(MixType::HardLight, "hard_light_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn hard_light_node_rgba() {
+    mix_node_test_rgba(MixType::HardLight, "hard_light_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn linear_dodge_node_gray() {
+    mix_node_test_gray(MixType::LinearDodge, "linear_dodge_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn linear_dodge_node_rgba() {
+    mix_node_test_rgba(MixType::LinearDodge, "linear_dodge_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn color_dodge_node_gray() {
+    mix_node_test_gray(MixType::ColorDodge, "color_dodge_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn color_dodge_node_rgba() {
+    mix_node_test_rgba(MixType::ColorDodge, "color_dodge_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn linear_burn_node_gray() {
+    mix_node_test_gray(MixType::LinearBurn, "linear_burn_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn linear_burn_node_rgba() {
+    mix_node_test_rgba(MixType::LinearBurn, "linear_burn_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn color_burn_node_gray() {
+    mix_node_test_gray(MixType::ColorBurn, "color_burn_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn color_burn_node_rgba() {
+    mix_node_test_rgba(MixType::ColorBurn, "color_burn_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn difference_node_gray() {
+    mix_node_test_gray(MixType::Difference, "difference_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn difference_node_rgba() {
+    mix_node_test_rgba(MixType::Difference, "difference_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn exclusion_node_gray() {
+    mix_node_test_gray(MixType::Exclusion, "exclusion_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn exclusion_node_rgba() {
+    mix_node_test_rgba(MixType::Exclusion, "exclusion_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn min_node_gray() {
+    mix_node_test_gray(MixType::Min, "min_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn min_node_rgba() {
+    mix_node_test_rgba(MixType::Min, "min_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn max_node_gray() {
+    mix_node_test_gray(MixType::Max, "max_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn max_node_rgba() {
+    mix_node_test_rgba(MixType::Max, "max_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn lerp_node_gray() {
+    mix_node_test_gray(MixType::Lerp, "lerp_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn lerp_node_rgba() {
+    mix_node_test_rgba(MixType::Lerp, "lerp_node_rgba.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn switch_node_gray() {
+    mix_node_test_gray(MixType::Switch, "switch_node_gray.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn switch_node_rgba() {
+    mix_node_test_rgba(MixType::Switch, "switch_node_rgba.png");
+}
+
+fn mix_node_test_mask(mix: Mix, name: &str) {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let image_node_1 = live_graph
+            .add_node(Node::new(NodeType::Image(IMAGE_1.into())))
+            .unwrap();
+        let image_node_2 = live_graph
+            .add_node(Node::new(NodeType::Image(IMAGE_2.into())))
+            .unwrap();
+        let mask_image_node = live_graph
+            .add_node(Node::new(NodeType::Image(CLOUDS.into())))
+            .unwrap();
+        let separate_node = live_graph
+            .add_node(Node::new(NodeType::SeparateRgba))
+            .unwrap();
+        let mix_node = live_graph.add_node(Node::new(NodeType::Mix(mix))).unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputRgba("out".into())))
+            .unwrap();
+
+        live_graph
+            .connect(image_node_1, mix_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(image_node_2, mix_node, SlotId(0), SlotId(1))
+            .unwrap();
+        live_graph
+            .connect(mask_image_node, separate_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(separate_node, mix_node, SlotId(0), SlotId(2))
+            .unwrap();
+        live_graph
+            .connect(mix_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    save_and_compare(&live_graph, output_node, name);
+}
+
+#[test]
+#[timeout(20_000)]
+fn mix_node_mask() {
+    mix_node_test_mask(Mix::new(MixType::Overlay), "mix_node_mask.png");
+}
+
+#[test]
+#[timeout(20_000)]
+fn mix_node_mask_opacity() {
+    mix_node_test_mask(
+        Mix::new(MixType::Screen).opacity(0.5),
+        "mix_node_mask_opacity.png",
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn mix_node_mask_composite() {
+    mix_node_test_mask(
+        Mix::new(MixType::Multiply).alpha(MixAlpha::Composite),
+        "mix_node_mask_composite.png",
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn mix_blend_modes() {
+    let cases = [
+        (MixType::Screen, [0.52, 0.94, 0.5, 0.72, 1.0, 1.0]),
+        (MixType::Overlay, [0.16, 0.88, 0.0, 0.44, 1.0, 1.0]),
+        (MixType::HardLight, [0.16, 0.88, 0.0, 0.36, 0.0, 1.0]),
+        (MixType::SoftLight, [0.168, 0.8378, 0.0, 0.504, 1.0, 0.7071]),
+        (MixType::ColorDodge, [0.3333, 1.0, 0.0, 0.8571, 1.0, 1.0]),
+        (MixType::ColorBurn, [0.0, 0.7143, 0.0, 0.0, 1.0, 0.5]),
+        (MixType::LinearBurn, [-0.4, 0.5, -0.5, -0.1, 0.0, 0.5]),
+        (MixType::Difference, [0.2, 0.1, 0.5, 0.3, 1.0, 0.5]),
+        (MixType::Exclusion, [0.44, 0.38, 0.5, 0.54, 1.0, 0.5]),
+        (MixType::Min, [0.2, 0.7, 0.0, 0.3, 0.0, 0.5]),
+        (MixType::Max, [0.4, 0.8, 0.5, 0.6, 1.0, 1.0]),
+    ];
+
+    for (mix_type, expected) in cases {
+        let pixels = mix_pixels(
+            mix_type.into(),
+            Size::new(2, 1),
+            &[0.2, 0.8, 0.0, 1.0, 0.6, 1.0, 0.5, 1.0],
+            &[0.4, 0.7, 0.5, 1.0, 0.3, 0.0, 1.0, 1.0],
+        );
+        let colors = pixels
+            .chunks(4)
+            .flat_map(|pixel| pixel[..3].to_vec())
+            .collect::<Vec<f32>>();
+        for (actual, expected) in colors.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 0.001,
+                "{}: {:?} != {:?}",
+                mix_type,
+                colors,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn mix_opacity() {
+    let cases = [
+        (Mix::new(MixType::Lerp).opacity(0.25), [0.4, 0.3, 0.575]),
+        (Mix::new(MixType::Add).opacity(0.5), [0.7, 0.4, 0.85]),
+        (Mix::new(MixType::Switch).opacity(0.49), [0.2, 0.4, 0.6]),
+        (Mix::new(MixType::Switch).opacity(0.5), [1.0, 0.0, 0.5]),
+    ];
+
+    for (mix, expected) in cases {
+        let pixels = mix_pixels(
+            mix,
+            Size::new(1, 1),
+            &[0.2, 0.4, 0.6, 1.0],
+            &[1.0, 0.0, 0.5, 1.0],
+        );
+        assert_pixels_eq(&pixels[..3], &expected);
+    }
+}
+
+fn read_full_precision_test(
+    path: &str,
+    image: image::DynamicImage,
+    expected: &[f32],
+    epsilon: f32,
+) {
+    ensure_out_dir();
+    image.save(path).unwrap();
+
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let image_node = live_graph
+            .add_node(Node::new(NodeType::Image(path.into())))
+            .unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputRgba("out".into())))
+            .unwrap();
+
+        live_graph
+            .connect(image_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    let actual = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+
+    assert_eq!(actual.len(), expected.len());
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!(
+            (actual - expected).abs() <= epsilon,
+            "Actual: {:?}, Expected: {:?}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn read_exr_full_precision() {
+    const PIXEL: [f32; 4] = [0.123_456, 2.5, -0.25, 0.5];
+
+    read_full_precision_test(
+        "out/read_exr_full_precision.exr",
+        image::DynamicImage::ImageRgba32F(
+            image::ImageBuffer::from_raw(1, 1, PIXEL.to_vec()).unwrap(),
+        ),
+        &PIXEL,
+        0.0,
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn read_png_16_bit() {
+    const PIXEL: [u16; 4] = [1, 32_769, 65_534, 65_535];
+
+    read_full_precision_test(
+        "out/read_png_16_bit.png",
+        image::DynamicImage::ImageRgba16(
+            image::ImageBuffer::from_raw(1, 1, PIXEL.to_vec()).unwrap(),
+        ),
+        &PIXEL.map(|value| value as f32 / 65535.),
+        0.000_001,
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_16_bit_gray() {
+    const PATH: &str = "out/export_16_bit_gray.png";
+    const VALUE: f32 = 0.3;
+
+    ensure_out_dir();
+    SlotImage::from_value(Size::new(2, 2), VALUE, false)
+        .export(
+            PATH,
+            &ExportOptions::new(ExportFormat::Png)
+                .bit_depth(BitDepth::Sixteen)
+                .channels(ChannelLayout::Gray),
+        )
+        .unwrap();
+
+    let image = image::open(PATH).unwrap();
+    assert_eq!(image.color(), image::ColorType::L16);
+    assert!(image
+        .to_luma16()
+        .pixels()
+        .all(|pixel| pixel.0[0] == (VALUE * 65535.).round() as u16));
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_16_bit_png_round_trip() {
+    let pixels: Vec<u16> = vec![0, 1, 32_768, 65_535, 1_000, 2_000, 3_000, 4_000];
+    let slot_image = SlotImage::from_rgba16(Size::new(2, 1), &pixels).unwrap();
+
+    let mut bytes = Vec::new();
+    slot_image
+        .encode(
+            &mut std::io::Cursor::new(&mut bytes),
+            &ExportOptions::new(ExportFormat::Png).bit_depth(BitDepth::Sixteen),
+        )
+        .unwrap();
+
+    let image = image::load_from_memory(&bytes).unwrap();
+    assert_eq!(image.color(), image::ColorType::Rgba16);
+    assert_eq!(image.to_rgba16().into_raw(), pixels);
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_srgb() {
+    const PATH: &str = "out/export_srgb.png";
+
+    ensure_out_dir();
+    SlotImage::from_value(Size::new(1, 1), 0.5, true)
+        .export(
+            PATH,
+            &ExportOptions::new(ExportFormat::Png).encoding(ColorEncoding::Srgb),
+        )
+        .unwrap();
+
+    // Linear 0.5 is 0.7354 in sRGB, which rounds to 188, alpha is left linear.
+    assert_eq!(
+        image::open(PATH).unwrap().to_rgba8().into_raw(),
+        vec![188, 188, 188, 255]
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_dither() {
+    const PATH: &str = "out/export_dither.png";
+    // Halfway between two 8 bit values.
+    const VALUE: f32 = 100.5 / 255.;
+
+    ensure_out_dir();
+    SlotImage::from_value(Size::new(4, 4), VALUE, false)
+        .export(
+            PATH,
+            &ExportOptions::new(ExportFormat::Png)
+                .channels(ChannelLayout::Gray)
+                .dither(true),
+        )
+        .unwrap();
+
+    let pixels = image::open(PATH).unwrap().to_luma8().into_raw();
+    assert_eq!(pixels.iter().filter(|value| **value == 100).count(), 8);
+    assert_eq!(pixels.iter().filter(|value| **value == 101).count(), 8);
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_unsupported() {
+    let mut buffer = std::io::Cursor::new(Vec::new());
+
+    assert!(SlotImage::from_value(Size::new(1, 1), 0.5, false)
+        .encode(
+            &mut buffer,
+            &ExportOptions::new(ExportFormat::OpenExr)
+                .bit_depth(BitDepth::Float32)
+                .channels(ChannelLayout::Gray),
+        )
+        .is_err());
+}
+
+fn dds_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_dds_color_mipmaps() {
+    const PATH: &str = "out/export_dds_color_mipmaps.dds";
+
+    ensure_out_dir();
+    dds::export(
+        &SlotImage::from_value(Size::new(8, 6), 0.5, true),
+        PATH,
+        &DdsOptions::from_usage(TextureUsage::Color),
+    )
+    .unwrap();
+
+    let bytes = std::fs::read(PATH).unwrap();
+    assert_eq!(&bytes[0..4], b"DDS ");
+    assert_eq!(dds_u32(&bytes, 12), 6);
+    assert_eq!(dds_u32(&bytes, 16), 8);
+    assert_eq!(dds_u32(&bytes, 28), 4);
+    assert_eq!(&bytes[84..88], b"DX10");
+    assert_eq!(dds_u32(&bytes, 128), 99);
+
+    // 8x6, 4x3, 2x1 and 1x1 pixels take 4, 1, 1 and 1 BC7 blocks.
+    assert_eq!(bytes.len(), 148 + 7 * 16);
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_dds_mask() {
+    let mut buffer = Vec::new();
+    dds::encode(
+        &SlotImage::from_value(Size::new(5, 5), 0.5, false),
+        &mut buffer,
+        &DdsOptions::from_usage(TextureUsage::Mask).mipmaps(false),
+    )
+    .unwrap();
+
+    assert_eq!(dds_u32(&buffer, 28), 1);
+    assert_eq!(dds_u32(&buffer, 128), 80);
+    assert_eq!(buffer.len(), 148 + 4 * 8);
+
+    // A solid block has both endpoints at the value.
+    for block in buffer[148..].chunks_exact(8) {
+        assert_eq!(block, &[128, 128, 0, 0, 0, 0, 0, 0]);
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_dds_normal() {
+    let options = DdsOptions::from_usage(TextureUsage::Normal);
+    assert_eq!(options.format, BcFormat::Bc5);
+    assert_eq!(options.encoding, ColorEncoding::Linear);
+
+    let mut buffer = Vec::new();
+    dds::encode(
+        &SlotImage::from_value(Size::new(4, 4), 0.5, true),
+        &mut buffer,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(dds_u32(&buffer, 128), 83);
+
+    // BC5 can't store sRGB.
+    assert!(dds::encode(
+        &SlotImage::from_value(Size::new(4, 4), 0.5, true),
+        &mut Vec::new(),
+        &options.encoding(ColorEncoding::Srgb),
+    )
+    .is_err());
+}
+
+#[test]
+#[timeout(20_000)]
+fn watch_image_file() {
+    const PATH: &str = "out/watch_image_file.png";
+
+    ensure_out_dir();
+    image::RgbaImage::from_raw(1, 1, vec![255, 0, 0, 255])
+        .unwrap()
+        .save(PATH)
+        .unwrap();
+
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        live_graph.watch_files = true;
+        live_graph.set_file_watch_interval(Duration::from_millis(1));
+
+        let image_node = live_graph
+            .add_node(Node::new(NodeType::Image(PATH.into())))
+            .unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputRgba("out".into())))
+            .unwrap();
+        live_graph
+            .connect(image_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+
+        output_node
+    };
+
+    assert_eq!(
+        LiveGraph::await_clean_read(&live_graph, output_node)
+            .unwrap()
+            .buffer_rgba(output_node, SlotId(0))
+            .unwrap(),
+        vec![255, 0, 0, 255]
+    );
+
+    image::RgbaImage::from_raw(2, 1, vec![0, 0, 255, 255, 0, 0, 255, 255])
+        .unwrap()
+        .save(PATH)
+        .unwrap();
+
+    loop {
+        let buffer = LiveGraph::await_clean_read(&live_graph, output_node)
+            .unwrap()
+            .buffer_rgba(output_node, SlotId(0))
+            .unwrap();
+
+        if buffer == vec![0, 0, 255, 255, 0, 0, 255, 255] {
+            break;
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn watch_image_file_in_search_path() {
+    const DIR: &str = "out/watch_image_file_in_search_path";
+    const FILE_NAME: &str = "image.png";
+
+    ensure_out_dir();
+    let _ = create_dir(DIR);
+    let path = Path::new(DIR).join(FILE_NAME);
+    image::RgbaImage::from_raw(1, 1, vec![255, 0, 0, 255])
+        .unwrap()
+        .save(&path)
+        .unwrap();
+
+    let tex_pro = tex_pro_new();
+    tex_pro.add_search_path(DIR.into()).unwrap();
+    let (live_graph, _, output_node) = missing_image_graph(&tex_pro, FILE_NAME);
+    {
+        let mut live_graph = live_graph.write().unwrap();
+        live_graph.watch_files = true;
+        live_graph.set_file_watch_interval(Duration::from_millis(1));
+    }
+
+    assert_eq!(
+        LiveGraph::await_clean_read(&live_graph, output_node)
+            .unwrap()
+            .buffer_rgba(output_node, SlotId(0))
+            .unwrap(),
+        vec![255, 0, 0, 255]
+    );
+
+    image::RgbaImage::from_raw(1, 1, vec![0, 0, 255, 255])
+        .unwrap()
+        .save(&path)
+        .unwrap();
+
+    loop {
+        let buffer = LiveGraph::await_clean_read(&live_graph, output_node)
+            .unwrap()
+            .buffer_rgba(output_node, SlotId(0))
+            .unwrap();
+
+        if buffer == vec![0, 0, 255, 255] {
+            break;
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn missing_image_graph(
+    tex_pro: &Arc<TextureProcessor>,
+    path: &str,
+) -> (Arc<RwLock<LiveGraph>>, NodeId, NodeId) {
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let (image_node, output_node) = {
+        let mut live_graph = live_graph.write().unwrap();
+
+        let image_node = live_graph
+            .add_node(Node::new(NodeType::Image(path.into())))
+            .unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputRgba("out".into())))
+            .unwrap();
+        live_graph
+            .connect(image_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+
+        (image_node, output_node)
+    };
+
+    (live_graph, image_node, output_node)
+}
+
+#[test]
+#[timeout(20_000)]
+fn missing_image_error() {
+    const PATH: &str = "data/does_not_exist.png";
+
+    let tex_pro = tex_pro_new();
+    let (live_graph, image_node, output_node) = missing_image_graph(&tex_pro, PATH);
+
+    assert!(matches!(
+        LiveGraph::await_clean_read(&live_graph, output_node),
+        Err(TexProError::NodeFailed)
+    ));
+
+    {
+        let live_graph = live_graph.read().unwrap();
+        assert_eq!(live_graph.node_state(output_node), Ok(NodeState::Failed));
+
+        // The output node reports the error of the image node it failed because of.
+        for node_id in [image_node, output_node] {
+            match live_graph.node_error(node_id) {
+                Some(TexProError::ReadImage(path, cause)) => {
+                    assert_eq!(path, Path::new(PATH));
+                    assert!(matches!(**cause, TexProError::Image(_)));
+                }
+                error => panic!("Unexpected error: {:?}", error),
+            }
+        }
+    }
+
+    // Fixing the path makes the node process again.
+    live_graph
+        .write()
+        .unwrap()
+        .node_mut(image_node)
+        .unwrap()
+        .node_type = NodeType::Image(IMAGE_1.into());
+    assert!(LiveGraph::await_clean_read(&live_graph, output_node).is_ok());
+    assert!(live_graph.read().unwrap().node_error(output_node).is_none());
+}
+
+#[test]
+#[timeout(20_000)]
+fn missing_image_placeholder() {
+    let tex_pro = tex_pro_new();
+    let (live_graph, _, output_node) = missing_image_graph(&tex_pro, "data/does_not_exist.png");
+    live_graph.write().unwrap().missing_image_placeholder = true;
+
+    assert_eq!(
+        LiveGraph::await_clean_read(&live_graph, output_node)
+            .unwrap()
+            .buffer_rgba(output_node, SlotId(0))
+            .unwrap(),
+        vec![255, 0, 255, 255]
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn file_references() {
+    let mut node_graph = NodeGraph::new();
+    let existing = node_graph
+        .add_node(Node::new(NodeType::Image(IMAGE_1.into())))
+        .unwrap();
+    let missing = node_graph
+        .add_node(Node::new(NodeType::Image("data/does_not_exist.png".into())))
+        .unwrap();
+
+    let mut nested_graph = NodeGraph::new();
+    nested_graph
+        .add_node(Node::new(NodeType::Image(IMAGE_2.into())))
+        .unwrap();
+    let graph = node_graph
+        .add_node(Node::new(NodeType::Graph(nested_graph)))
+        .unwrap();
+
+    assert_eq!(
+        node_graph.file_references(),
+        vec![
+            FileReference {
+                node_id: existing,
+                path: IMAGE_1.into()
+            },
+            FileReference {
+                node_id: missing,
+                path: "data/does_not_exist.png".into()
+            },
+            FileReference {
+                node_id: graph,
+                path: IMAGE_2.into()
+            },
+        ]
+    );
+    assert_eq!(
+        node_graph.unresolved_file_references(),
+        vec![FileReference {
+            node_id: missing,
+            path: "data/does_not_exist.png".into()
+        }]
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn relative_image_paths() {
+    const PATH: &str = "out/relative_image_paths.json";
+
+    ensure_out_dir();
+    let mut node_graph = NodeGraph::new();
+    let image_node = node_graph
+        .add_node(Node::new(NodeType::Image(IMAGE_1.into())))
+        .unwrap();
+    node_graph.export_json_relative(PATH.into()).unwrap();
+
+    let json = std::fs::read_to_string(PATH).unwrap();
+    assert!(json.contains(&format!("\"../{}\"", IMAGE_1)));
+
+    let node_graph = NodeGraph::from_path(PATH.into()).unwrap();
+    let path = &node_graph.file_references()[0].path;
+    assert_eq!(node_graph.file_references()[0].node_id, image_node);
+    assert_eq!(
+        path,
+        &std::env::current_dir()
+            .unwrap()
+            .join("out")
+            .join("..")
+            .join(IMAGE_1)
+    );
+    assert!(path.is_file());
+}
+
+#[test]
+#[timeout(20_000)]
+fn image_search_paths() {
+    const PATH: &str = "moved/image_1.png";
+
+    let tex_pro = tex_pro_new();
+    tex_pro.add_search_path("data".into()).unwrap();
+    let (live_graph, _, output_node) = missing_image_graph(&tex_pro, PATH);
+
+    assert!(live_graph
+        .read()
+        .unwrap()
+        .unresolved_file_references_in(&tex_pro.search_paths().unwrap())
+        .is_empty());
+    assert_eq!(
+        live_graph
+            .read()
+            .unwrap()
+            .unresolved_file_references()
+            .len(),
+        1
+    );
+
+    let expected = image::open(IMAGE_1).unwrap().to_rgba8().into_raw();
+    assert_eq!(
+        LiveGraph::await_clean_read(&live_graph, output_node)
+            .unwrap()
+            .buffer_rgba(output_node, SlotId(0))
+            .unwrap(),
+        expected
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn image_search_paths_added_later() {
+    const PATH: &str = "moved/image_1.png";
+
+    let tex_pro = tex_pro_new();
+    let (live_graph, image_node, output_node) = missing_image_graph(&tex_pro, PATH);
+    assert!(matches!(
+        LiveGraph::await_clean_read(&live_graph, output_node),
+        Err(TexProError::NodeFailed)
+    ));
+
+    // Adding the search path that resolves the image processes the failed node again.
+    tex_pro.add_search_path("data".into()).unwrap();
+    assert_ne!(
+        live_graph.read().unwrap().node_state(image_node).unwrap(),
+        NodeState::Failed
+    );
+
+    let expected = image::open(IMAGE_1).unwrap().to_rgba8().into_raw();
+    assert_eq!(
+        LiveGraph::await_clean_read(&live_graph, output_node)
+            .unwrap()
+            .buffer_rgba(output_node, SlotId(0))
+            .unwrap(),
+        expected
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn relink_image_paths() {
+    let mut node_graph = NodeGraph::new();
+    let moved = node_graph
+        .add_node(Node::new(NodeType::Image("old/image_1.png".into())))
+        .unwrap();
+    node_graph
+        .add_node(Node::new(NodeType::Image("other/image_1.png".into())))
+        .unwrap();
+
+    let mut nested_graph = NodeGraph::new();
+    nested_graph
+        .add_node(Node::new(NodeType::Image("old/sub/image_2.png".into())))
+        .unwrap();
+    let graph = node_graph
+        .add_node(Node::new(NodeType::Graph(nested_graph)))
+        .unwrap();
+
+    assert_eq!(
+        node_graph.relink(Path::new("old"), Path::new("data")),
+        vec![moved, graph]
+    );
+    assert_eq!(
+        node_graph
+            .file_references()
+            .into_iter()
+            .map(|file_reference| file_reference.path)
+            .collect::<Vec<_>>(),
+        vec![
+            Path::new("data/image_1.png").to_path_buf(),
+            Path::new("other/image_1.png").to_path_buf(),
+            Path::new("data/sub/image_2.png").to_path_buf(),
+        ]
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn bundle_round_trip() {
+    const PATH: &str = "out/bundle_round_trip.vmb";
+    const EXTRACT_DIR: &str = "out/bundle_round_trip";
+
+    ensure_out_dir();
+    let embedded_values = vec![0.1, 0.2, 0.3, 1.0, 1.0 / 3.0, 2.5, -0.5, 0.0];
+    let mut buffers = [0, 1, 2, 3].map(|channel| {
+        Buffer::from_raw(
+            2,
+            1,
+            vec![embedded_values[channel], embedded_values[4 + channel]],
+        )
+        .unwrap()
+    });
+    let slot_image = SlotImage::from_buffers_rgba(&mut buffers).unwrap();
+
+    let tex_pro = tex_pro_new();
+    tex_pro.add_search_path("data".into()).unwrap();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let (embed_node, embed_output, image_output) = {
+        let mut live_graph = live_graph.write().unwrap();
+        let esd_id = live_graph
+            .embed_slot_data_with_id(
+                Arc::new(
+                    SlotData::new(NodeId(0), SlotId(0), slot_image)
+                        .alpha_mode(AlphaMode::Premultiplied),
+                ),
+                EmbeddedSlotDataId(3),
+            )
+            .unwrap();
+
+        let embed_node = live_graph
+            .add_node(Node::new(NodeType::Embed(esd_id)).color_space(ColorSpace::Linear))
+            .unwrap();
+        let embed_output = live_graph
+            .add_node(Node::new(NodeType::OutputRgba("embed".into())))
+            .unwrap();
+        // Only found through the search path.
+        let image_node = live_graph
+            .add_node(Node::new(NodeType::Image("image_1.png".into())))
+            .unwrap();
+        let image_output = live_graph
+            .add_node(Node::new(NodeType::OutputRgba("image".into())))
+            .unwrap();
+        live_graph
+            .connect(embed_node, embed_output, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(image_node, image_output, SlotId(0), SlotId(0))
+            .unwrap();
+
+        live_graph
+            .save_bundle(PATH, &tex_pro.search_paths().unwrap())
+            .unwrap();
+        (embed_node, embed_output, image_output)
+    };
+
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    live_graph
+        .write()
+        .unwrap()
+        .load_bundle(PATH, EXTRACT_DIR)
+        .unwrap();
+
+    let file_references = live_graph.read().unwrap().file_references();
+    assert_eq!(file_references.len(), 1);
+    assert!(file_references[0].path.starts_with(EXTRACT_DIR));
+    assert_eq!(
+        std::fs::read(&file_references[0].path).unwrap(),
+        std::fs::read(IMAGE_1).unwrap()
+    );
+
+    assert_eq!(
+        live_graph
+            .read()
+            .unwrap()
+            .node(embed_node)
+            .unwrap()
+            .color_space,
+        ColorSpace::Linear
+    );
+    let live_graph_read = LiveGraph::await_clean_read(&live_graph, embed_output).unwrap();
+    let slot_data = live_graph_read.slot_data(embed_output, SlotId(0)).unwrap();
+    assert_eq!(slot_data.alpha_mode, AlphaMode::Premultiplied);
+    assert_eq!(slot_data.image.to_f32().unwrap(), embedded_values);
+    drop(live_graph_read);
+    assert_eq!(
+        LiveGraph::await_clean_read(&live_graph, image_output)
+            .unwrap()
+            .buffer_rgba(image_output, SlotId(0))
+            .unwrap(),
+        image::open(IMAGE_1).unwrap().to_rgba8().into_raw()
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn bundle_invalid() {
+    assert!(matches!(
+        Bundle::read(&mut &b"VISMUTBX\x01\0\0\0"[..]),
+        Err(TexProError::InvalidBundle)
+    ));
+}
+
+fn bundle_bytes(embedded: serde_json::Value, files: serde_json::Value, data: &[u8]) -> Vec<u8> {
+    let manifest = serde_json::to_vec(&serde_json::json!({
+        "node_graph": NodeGraph::new(),
+        "embedded": embedded,
+        "files": files,
+    }))
+    .unwrap();
+
+    let mut bytes = b"VISMUTBN".to_vec();
+    bytes.extend_from_slice(&1_u32.to_le_bytes());
+    bytes.extend_from_slice(&(manifest.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&manifest);
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+#[test]
+#[timeout(20_000)]
+fn bundle_unsafe_file_name() {
+    for name in [
+        "../escape.png",
+        "images/../../escape.png",
+        "/escape.png",
+        "",
+    ] {
+        let bytes = bundle_bytes(
+            serde_json::json!([]),
+            serde_json::json!([{ "name": name, "len": 3 }]),
+            &[1, 2, 3],
+        );
+
+        assert!(
+            matches!(
+                Bundle::read(&mut &bytes[..]),
+                Err(TexProError::InvalidBundle)
+            ),
+            "{:?} was accepted",
+            name
+        );
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn bundle_embedded_size_mismatch() {
+    // The data holds a 2x1 RGBA image.
+    let bytes = |width: u32, height: u32| {
+        bundle_bytes(
+            serde_json::json!([{
+                "slot_data_id": 0,
+                "slot_id": 0,
+                "width": width,
+                "height": height,
+                "rgba": true,
+            }]),
+            serde_json::json!([]),
+            &[0; 2 * 4 * 4],
+        )
+    };
+
+    assert!(Bundle::read(&mut &bytes(2, 1)[..]).is_ok());
+    for (width, height) in [(4_000_000_000, 4_000_000_000), (2, 2), (0, 1)] {
+        assert!(matches!(
+            Bundle::read(&mut &bytes(width, height)[..]),
+            Err(TexProError::InvalidBundle)
+        ));
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn slot_image_from_bytes() {
+    let bytes = std::fs::read(IMAGE_1).unwrap();
+    let expected = image::open(IMAGE_1).unwrap().to_rgba8().into_raw();
+
+    assert_eq!(
+        SlotImage::from_bytes(&bytes).unwrap().to_u8().unwrap(),
+        expected
+    );
+    assert_eq!(
+        SlotImage::from_reader(std::io::Cursor::new(&bytes))
+            .unwrap()
+            .to_u8()
+            .unwrap(),
+        expected
+    );
+    assert!(SlotImage::from_bytes(&[0, 1, 2, 3]).is_err());
+}
+
+#[test]
+#[timeout(20_000)]
+fn slot_image_from_bytes_16_bit() {
+    use image::ImageEncoder;
+
+    let pixels: Vec<u16> = vec![0, 1, 32768, 65535, 1000, 2000, 3000, 4000];
+    let image = image::DynamicImage::ImageRgba16(
+        image::ImageBuffer::from_raw(2, 1, pixels.clone()).unwrap(),
+    );
+    let mut bytes = Vec::new();
+    // `PngEncoder` is used since `write_to` writes 16 bit PNG samples in native endian in some
+    // `image` 0.24 releases.
+    image::codecs::png::PngEncoder::new(&mut bytes)
+        .write_image(image.as_bytes(), 2, 1, image.color())
+        .unwrap();
+
+    assert_eq!(
+        SlotImage::from_bytes(&bytes).unwrap().to_u16().unwrap(),
+        pixels
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn slot_image_from_raw() {
+    let size = Size::new(2, 1);
+
+    let rgba8 = vec![0, 64, 128, 255, 1, 2, 3, 4];
+    assert_eq!(
+        SlotImage::from_rgba8(size, &rgba8)
+            .unwrap()
+            .to_u8()
+            .unwrap(),
+        rgba8
+    );
+
+    let rgba16 = vec![0, 1, 32768, 65535, 1000, 2000, 3000, 4000];
+    assert_eq!(
+        SlotImage::from_rgba16(size, &rgba16)
+            .unwrap()
+            .to_u16()
+            .unwrap(),
+        rgba16
+    );
+
+    let rgba_f32 = vec![0.0, 0.25, -1.0, 1.0, 2.5, 0.1, 0.2, 0.3];
+    let slot_image = SlotImage::from_rgba_f32(size, &rgba_f32).unwrap();
+    assert!(slot_image.is_rgba());
+    assert_eq!(slot_image.size().unwrap(), size);
+    assert_eq!(slot_image.to_f32().unwrap(), rgba_f32);
+
+    assert!(matches!(
+        SlotImage::from_rgba8(size, &rgba8[..4]),
+        Err(TexProError::InvalidBufferCount)
+    ));
+}
+
+fn color_space_graph(
+    tex_pro: &Arc<TextureProcessor>,
+    path: &str,
+    input: ColorSpace,
+    output: ColorSpace,
+) -> (Arc<RwLock<LiveGraph>>, NodeId) {
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let image_node = live_graph
+            .add_node(Node::new(NodeType::Image(path.into())).color_space(input))
+            .unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputRgba("out".into())).color_space(output))
+            .unwrap();
+        live_graph
+            .connect(image_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    (live_graph, output_node)
+}
+
+#[test]
+#[timeout(20_000)]
+fn color_space_round_trip() {
+    const PATH_IN: &str = "out/color_space_round_trip_in.png";
+    const PATH_OUT: &str = "out/color_space_round_trip_out.png";
+
+    ensure_out_dir();
+    let pixels: Vec<u8> = (0..=255).flat_map(|v| [v, 255 - v, v / 2, v]).collect();
+    image::RgbaImage::from_raw(256, 1, pixels.clone())
+        .unwrap()
+        .save(PATH_IN)
+        .unwrap();
+
+    let tex_pro = tex_pro_new();
+    let (live_graph, output_node) =
+        color_space_graph(&tex_pro, PATH_IN, ColorSpace::Srgb, ColorSpace::Srgb);
+    let live_graph = LiveGraph::await_clean_read(&live_graph, output_node).unwrap();
+
+    // The working space is linear, alpha is never converted.
+    let values = live_graph.buffer_rgba_f32(output_node, SlotId(0)).unwrap();
+    for (i, (value, pixel)) in values.iter().zip(&pixels).enumerate() {
+        let expected = if i % 4 == 3 {
+            *pixel as f32 / 255.
+        } else {
+            (*pixel as f32 / 255.).srgb_to_linear()
+        };
+        assert!((value - expected).abs() < 0.000_01);
+    }
+
+    live_graph
+        .export_output(
+            output_node,
+            PATH_OUT,
+            &ExportOptions::new(ExportFormat::Png).bit_depth(BitDepth::Sixteen),
+        )
+        .unwrap();
+    let exported = image::open(PATH_OUT).unwrap().to_rgba16().into_raw();
+    for (value, pixel) in exported.iter().zip(&pixels) {
+        assert!((*value as i32 - *pixel as i32 * 257).abs() <= 1);
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn color_space_raw_and_linear() {
+    const PATH_IN: &str = "out/color_space_raw_and_linear.png";
+    const PATH_OUT: &str = "out/color_space_raw_and_linear_out.png";
+
+    ensure_out_dir();
+    image::RgbaImage::from_raw(1, 1, vec![128, 128, 128, 128])
+        .unwrap()
+        .save(PATH_IN)
+        .unwrap();
+
+    let tex_pro = tex_pro_new();
+
+    // Raw data is passed through untouched.
+    {
+        let (live_graph, output_node) =
+            color_space_graph(&tex_pro, PATH_IN, ColorSpace::Raw, ColorSpace::Raw);
+        let live_graph = LiveGraph::await_clean_read(&live_graph, output_node).unwrap();
+        assert_eq!(
+            live_graph.buffer_rgba_f32(output_node, SlotId(0)).unwrap(),
+            vec![128. / 255.; 4]
+        );
+        live_graph
+            .export_output(output_node, PATH_OUT, &ExportOptions::default())
+            .unwrap();
+        assert_eq!(
+            image::open(PATH_OUT).unwrap().to_rgba8().into_raw(),
+            vec![128, 128, 128, 128]
+        );
+        assert!(matches!(
+            live_graph.export_output(NodeId(0), PATH_OUT, &ExportOptions::default()),
+            Err(TexProError::InvalidNodeType)
+        ));
+    }
+
+    // Linear input declared as a color output gets encoded as sRGB on export.
+    let (live_graph, output_node) = color_space_graph(
+        &tex_pro,
+        PATH_IN,
+        ColorSpace::Linear,
+        ColorSpace::from(TextureUsage::Color),
+    );
+    LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .export_output(output_node, PATH_OUT, &ExportOptions::default())
+        .unwrap();
+    let expected = ((128. / 255_f32).linear_to_srgb() * 255.).round() as u8;
+    assert_eq!(
+        image::open(PATH_OUT).unwrap().to_rgba8().into_raw(),
+        vec![expected, expected, expected, 128]
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn slot_image_to_u8_srgb() {
+    // Linear 0.5 is 0.735 in sRGB, alpha is left linear.
+    assert_eq!(
+        SlotImage::from_value(Size::new(1, 1), 0.5, true)
+            .to_u8_srgb()
+            .unwrap(),
+        vec![187, 187, 187, 255]
+    );
+}
+
+fn embed_rgba(live_graph: &mut LiveGraph, id: u32, size: Size, pixels: &[f32]) -> NodeId {
+    let slot_image = SlotImage::from_rgba_f32(size, pixels).unwrap();
+    let esd_id = live_graph
+        .embed_slot_data_with_id(
+            Arc::new(SlotData::new(NodeId(0), SlotId(0), slot_image)),
+            EmbeddedSlotDataId(id),
+        )
+        .unwrap();
+    live_graph
+        .add_node(Node::new(NodeType::Embed(esd_id)))
+        .unwrap()
+}
+
+fn add_output(live_graph: &mut LiveGraph, input: NodeId) -> NodeId {
+    let output_node = live_graph
+        .add_node(Node::new(NodeType::OutputRgba("out".into())))
+        .unwrap();
+    live_graph
+        .connect(input, output_node, SlotId(0), SlotId(0))
+        .unwrap();
+    output_node
+}
+
+fn assert_pixels_eq(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len());
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!(
+            (actual - expected).abs() < 0.000_1,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn premultiply_nodes() {
+    const PATH: &str = "out/premultiply_nodes.png";
+
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let (premultiplied, twice, unpremultiplied) = {
+        let mut live_graph = live_graph.write().unwrap();
+        let embed_node = embed_rgba(
+            &mut live_graph,
+            0,
+            Size::new(2, 1),
+            &[1.0, 0.5, 0.25, 0.5, 0.2, 0.4, 0.6, 0.0],
+        );
+        let premultiply_node = live_graph
+            .add_node(Node::new(NodeType::Premultiply))
+            .unwrap();
+        let premultiply_node_2 = live_graph
+            .add_node(Node::new(NodeType::Premultiply))
+            .unwrap();
+        let unpremultiply_node = live_graph
+            .add_node(Node::new(NodeType::Unpremultiply))
+            .unwrap();
+        live_graph
+            .connect(embed_node, premultiply_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(premultiply_node, premultiply_node_2, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(premultiply_node, unpremultiply_node, SlotId(0), SlotId(0))
+            .unwrap();
+
+        (
+            add_output(&mut live_graph, premultiply_node),
+            add_output(&mut live_graph, premultiply_node_2),
+            add_output(&mut live_graph, unpremultiply_node),
+        )
+    };
+
+    let expected = [0.5, 0.25, 0.125, 0.5, 0.0, 0.0, 0.0, 0.0];
+    for output_node in [premultiplied, twice] {
+        let live_graph = LiveGraph::await_clean_read(&live_graph, output_node).unwrap();
+        let slot_data = live_graph.slot_data(output_node, SlotId(0)).unwrap();
+        assert_eq!(slot_data.alpha_mode, AlphaMode::Premultiplied);
+        assert_pixels_eq(&slot_data.image.to_f32().unwrap(), &expected);
+    }
+
+    {
+        let live_graph = LiveGraph::await_clean_read(&live_graph, unpremultiplied).unwrap();
+        let slot_data = live_graph.slot_data(unpremultiplied, SlotId(0)).unwrap();
+        assert_eq!(slot_data.alpha_mode, AlphaMode::Straight);
+        // The color of fully transparent pixels is lost.
+        assert_pixels_eq(
+            &slot_data.image.to_f32().unwrap(),
+            &[1.0, 0.5, 0.25, 0.5, 0.0, 0.0, 0.0, 0.0],
+        );
+    }
+
+    // Image files get straight alpha.
+    ensure_out_dir();
+    LiveGraph::await_clean_read(&live_graph, premultiplied)
+        .unwrap()
+        .export(premultiplied, SlotId(0), PATH, &ExportOptions::default())
+        .unwrap();
+    assert_eq!(
+        image::open(PATH).unwrap().to_rgba8().into_raw(),
+        vec![255, 128, 64, 128, 0, 0, 0, 0]
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn resize_premultiplied() {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        // An opaque red pixel next to a transparent green one.
+        let embed_node = embed_rgba(
+            &mut live_graph,
+            0,
+            Size::new(2, 1),
+            &[1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0],
+        );
+        let output_node = live_graph
+            .add_node(
+                Node::new(NodeType::OutputRgba("out".into()))
+                    .resize_policy(ResizePolicy::SpecificSize(Size::new(1, 1))),
+            )
+            .unwrap();
+        live_graph
+            .connect(embed_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    // The transparent green doesn't bleed into the result.
+    let pixel = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    assert_pixels_eq(&pixel, &[1.0, 0.0, 0.0, 0.5]);
+}
+
+fn mix_pixels(mix: Mix, size: Size, left: &[f32], right: &[f32]) -> Vec<f32> {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let left = embed_rgba(&mut live_graph, 0, size, left);
+        let right = embed_rgba(&mut live_graph, 1, size, right);
+        let mix_node = live_graph.add_node(Node::new(NodeType::Mix(mix))).unwrap();
+        live_graph
+            .connect(left, mix_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(right, mix_node, SlotId(0), SlotId(1))
+            .unwrap();
+        add_output(&mut live_graph, mix_node)
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    pixels
+}
+
+fn mix_alpha_test(mix: Mix) -> Vec<f32> {
+    mix_pixels(
+        mix,
+        Size::new(2, 1),
+        &[1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0],
+        &[0.0, 1.0, 0.0, 0.5, 0.0, 1.0, 0.0, 0.5],
+    )
+}
+
+#[test]
+#[timeout(20_000)]
+fn mix_alpha_opaque() {
+    assert_pixels_eq(
+        &mix_alpha_test(Mix::new(MixType::Add)),
+        &[1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 0.0, 1.0],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn mix_alpha_composite() {
+    // Where the left input is opaque the blended color shows through the right input's
+    // alpha, where it's transparent only the right input is visible.
+    assert_pixels_eq(
+        &mix_alpha_test(Mix::new(MixType::Add).alpha(MixAlpha::Composite)),
+        &[1.0, 0.5, 0.0, 1.0, 0.0, 1.0, 0.0, 0.5],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn mix_legacy_json() {
+    // Graphs saved before `Mix` had settings only store the `MixType`.
+    let node_graph = NodeGraph::from_path("data/invert_graph.json".into()).unwrap();
+    let mix = node_graph
+        .nodes()
+        .iter()
+        .find_map(|node| match node.node_type {
+            NodeType::Mix(mix) => Some(mix),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(mix, Mix::new(MixType::Subtract));
+    assert_eq!(mix.alpha, MixAlpha::Opaque);
+    assert_eq!(mix.opacity, 1.0);
+}
+
+/// Evaluates an `Expression` with a 2x1 RGBA input named "a" and a gray input named "b" that
+/// is 0.25.
+fn expression_pixels(expression: &str) -> Vec<f32> {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let a = embed_rgba(
+            &mut live_graph,
+            0,
+            Size::new(2, 1),
+            &[0.2, 0.4, 0.6, 1.0, 0.8, 0.1, 0.3, 0.5],
+        );
+        let b = live_graph
+            .add_node(Node::new(NodeType::Value(0.25)))
+            .unwrap();
+        let expression = Expression::new(expression)
+            .input("a", SlotType::Rgba)
+            .input("b", SlotType::Gray);
+        let output_node = live_graph
+            .add_node(Node::new(match expression.check().unwrap() {
+                SlotType::Gray => NodeType::OutputGray("out".into()),
+                _ => NodeType::OutputRgba("out".into()),
+            }))
+            .unwrap();
+        let expression_node = live_graph
+            .add_node(Node::new(NodeType::Expression(expression)))
+            .unwrap();
+        live_graph
+            .connect(a, expression_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(b, expression_node, SlotId(0), SlotId(1))
+            .unwrap();
+        live_graph
+            .connect(expression_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    pixels
+}
+
+#[test]
+#[timeout(20_000)]
+fn expression_node_gray() {
+    assert_pixels_eq(
+        &expression_pixels("a.r * 2 + b - u"),
+        &[0.65, 0.65, 0.65, 1.0, 1.35, 1.35, 1.35, 1.0],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn expression_node_rgba() {
+    assert_pixels_eq(
+        &expression_pixels("lerp(a, rgba(1, 0, 0, 1), 0.5) * (v < 1)"),
+        &[0.6, 0.2, 0.3, 1.0, 0.9, 0.05, 0.15, 0.75],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn expression_node_functions() {
+    let expressions = [
+        ("smoothstep(0, 1, 0.25) + sin(pi / 2)", 1.156_25),
+        ("(2 > 1) + (2 <= 1) * 10 + (3 == 3) * 100 - (1 != 1)", 101.0),
+        (
+            "clamp(-b * 8, -1, 1) + abs(-2) + pow(2, 3) + max(1, 4)",
+            13.0,
+        ),
+        (
+            "fract(1.75) + floor(1.5) + ceil(0.2) + step(0.5, 0.4)",
+            2.75,
+        ),
+        ("1e1 + .5 - -1", 11.5),
+    ];
+
+    for (expression, expected) in expressions {
+        let pixels = expression_pixels(expression);
+        assert!(
+            (pixels[0] - expected).abs() < 0.000_1,
+            "{}: {} != {}",
+            expression,
+            pixels[0],
+            expected
+        );
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn expression_node_size() {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let expression_node = live_graph
+            .add_node(
+                Node::new(NodeType::Expression(Expression::new(
+                    "u + v * 10 + width * 100 + height * 1000",
+                )))
+                .resize_policy(ResizePolicy::SpecificSize(Size::new(2, 2))),
+            )
+            .unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputGray("out".into())))
+            .unwrap();
+        live_graph
+            .connect(expression_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    let pixels = pixels.chunks(4).map(|pixel| pixel[0]).collect::<Vec<f32>>();
+    assert_pixels_eq(&pixels, &[2200.0, 2200.5, 2205.0, 2205.5]);
+}
+
+#[test]
+fn expression_invalid() {
+    let invalid = [
+        "a +",
+        "(a",
+        "a b",
+        "a $ b",
+        "c",
+        "b.r",
+        "a.x",
+        "clamp(a)",
+        "noise(a)",
+        "rgba(a, b, b, b)",
+    ];
+    for expression in invalid {
+        let expression = Expression::new(expression)
+            .input("a", SlotType::Rgba)
+            .input("b", SlotType::Gray);
+        assert_eq!(
+            expression.check(),
+            Err(TexProError::InvalidExpression(String::new())),
+            "{}",
+            expression
+        );
+    }
+
+    for (name, slot_type) in [
+        ("sin", SlotType::Gray),
+        ("2a", SlotType::Gray),
+        ("a", SlotType::GrayOrRgba),
+    ] {
+        assert!(Expression::new("1").input(name, slot_type).check().is_err());
+    }
+    assert!(Expression::new("1")
+        .input("a", SlotType::Gray)
+        .input("a", SlotType::Gray)
+        .check()
+        .is_err());
+
+    assert_eq!(
+        Expression::new("a.r + b")
+            .input("a", SlotType::Rgba)
+            .input("b", SlotType::Gray)
+            .check(),
+        Ok(SlotType::Gray)
+    );
+    assert_eq!(
+        Expression::new("a * b")
+            .input("a", SlotType::Rgba)
+            .input("b", SlotType::Gray)
+            .check(),
+        Ok(SlotType::Rgba)
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn expression_nesting_depth() {
+    let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+
+    assert_eq!(Expression::new(nested(100)).check(), Ok(SlotType::Gray));
+    for expression in [nested(100_000), format!("{}1", "-".repeat(100_000))] {
+        assert_eq!(
+            Expression::new(expression).check(),
+            Err(TexProError::InvalidExpression(String::new()))
+        );
+    }
+}
+
+#[test]
+fn expression_invalid_connect() {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let mut live_graph = live_graph.write().unwrap();
+
+    let value_node = live_graph
+        .add_node(Node::new(NodeType::Value(0.5)))
+        .unwrap();
+    let expression_node = live_graph
+        .add_node(Node::new(NodeType::Expression(
+            Expression::new("a.r").input("a", SlotType::Gray),
+        )))
+        .unwrap();
+
+    assert_eq!(
+        live_graph.connect(value_node, expression_node, SlotId(0), SlotId(0)),
+        Err(TexProError::InvalidExpression(String::new()))
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn expression_set_removes_unfitting_edges() {
+    let mut node_graph = NodeGraph::new();
+
+    let value_node = node_graph
+        .add_node(Node::new(NodeType::Value(0.5)))
+        .unwrap();
+    let expression_node = node_graph
+        .add_node(Node::new(NodeType::Expression(
+            Expression::new("a + b")
+                .input("a", SlotType::Gray)
+                .input("b", SlotType::Gray),
+        )))
+        .unwrap();
+    let output_node = node_graph
+        .add_node(Node::new(NodeType::OutputGray("out".into())))
+        .unwrap();
+
+    let kept_edge = *node_graph
+        .connect(value_node, expression_node, SlotId(0), SlotId(0))
+        .unwrap();
+    node_graph
+        .connect(value_node, expression_node, SlotId(0), SlotId(1))
+        .unwrap();
+    node_graph
+        .connect(expression_node, output_node, SlotId(0), SlotId(0))
+        .unwrap();
+
+    // Drops the "b" input and makes the output RGBA, which doesn't fit the gray output node.
+    let removed_edges = node_graph
+        .set_expression(
+            expression_node,
+            Expression::new("rgba(a, a, a, 1)").input("a", SlotType::Gray),
+        )
+        .unwrap();
+
+    assert_eq!(removed_edges.len(), 2);
+    assert_eq!(node_graph.edges, vec![kept_edge]);
+    assert_eq!(
+        node_graph
+            .node(expression_node)
+            .unwrap()
+            .output_slot_with_id(SlotId(0))
+            .unwrap()
+            .slot_type,
+        SlotType::Rgba
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn expression_json() {
+    const PATH: &str = "out/expression_json.json";
+    ensure_out_dir();
+
+    let expression = Expression::new("lerp(a, b, 0.5)")
+        .input("a", SlotType::Rgba)
+        .input("b", SlotType::Gray);
+    let mut node_graph = NodeGraph::new();
+    node_graph
+        .add_node(Node::new(NodeType::Expression(expression.clone())))
+        .unwrap();
+    node_graph.export_json(PATH.into()).unwrap();
+
+    let node_graph = NodeGraph::from_path(PATH.into()).unwrap();
+    match &node_graph.nodes()[0].node_type {
+        NodeType::Expression(loaded) => assert_eq!(*loaded, expression),
+        _ => panic!("the node is not an expression node"),
+    }
+}
+
+/// Multiplies the color of the input by the "gain" parameter.
+struct Gain;
+
+impl NodeProcessor for Gain {
+    fn id(&self) -> &str {
+        "studio.gain"
+    }
+
+    fn input_slots(&self) -> Vec<CustomSlot> {
+        vec![CustomSlot::new("input", SlotType::GrayOrRgba)]
+    }
+
+    fn output_slots(&self) -> Vec<CustomSlot> {
+        vec![CustomSlot::new("output", SlotType::Rgba)]
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![Parameter::new("gain", ParameterValue::Float(2.0))]
+    }
+
+    fn process(&self, context: &ProcessContext) -> Result<Vec<SlotImage>, TexProError> {
+        let gain = context
+            .parameter("gain")
+            .and_then(ParameterValue::as_float)
+            .unwrap();
+        let pixels = match context.input("input") {
+            Some(input) => input.to_f32()?,
+            None => vec![0.0; context.size().pixel_count() * 4],
+        };
+        let pixels = pixels
+            .chunks(4)
+            .flat_map(|pixel| vec![pixel[0] * gain, pixel[1] * gain, pixel[2] * gain, pixel[3]])
+            .collect::<Vec<f32>>();
+
+        Ok(vec![SlotImage::from_rgba_f32(context.size(), &pixels)?])
+    }
+}
+
+/// Connects a `Value` node of 0.25 to a node of the given type and returns its output.
+fn custom_node_pixels(tex_pro: &Arc<TextureProcessor>, node_type: NodeType) -> Vec<f32> {
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let value_node = live_graph
+            .add_node(Node::new(NodeType::Value(0.25)))
+            .unwrap();
+        let custom_node = live_graph.add_node(Node::new(node_type)).unwrap();
+        live_graph
+            .connect(value_node, custom_node, SlotId(0), SlotId(0))
+            .unwrap();
+        add_output(&mut live_graph, custom_node)
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    pixels
+}
+
+#[test]
+#[timeout(20_000)]
+fn custom_node() {
+    let tex_pro = tex_pro_new();
+    tex_pro.register_node_processor(Arc::new(Gain)).unwrap();
+    assert_eq!(
+        tex_pro.node_processor_ids().unwrap(),
+        vec!["studio.gain".to_string()]
+    );
+
+    let node_type = tex_pro.custom_node_type("studio.gain").unwrap();
+    assert_pixels_eq(
+        &custom_node_pixels(&tex_pro, node_type.clone()),
+        &[0.5, 0.5, 0.5, 1.0],
+    );
+
+    let node_type = match node_type {
+        NodeType::Custom(custom_node) => {
+            NodeType::Custom(custom_node.parameter("gain", ParameterValue::Float(3.0)))
+        }
+        _ => unreachable!(),
+    };
+    assert_pixels_eq(
+        &custom_node_pixels(&tex_pro, node_type),
+        &[0.75, 0.75, 0.75, 1.0],
+    );
+
+    assert_eq!(
+        tex_pro.custom_node_type("studio.missing"),
+        Err(TexProError::UnknownNodeType(String::new()))
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn custom_node_unregistered() {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let value_node = live_graph
+            .add_node(Node::new(NodeType::Value(0.25)))
+            .unwrap();
+        let custom_node = live_graph
+            .add_node(Node::new(NodeType::Custom(CustomNode {
+                type_id: "studio.gain".into(),
+                parameters: Default::default(),
+                input_slots: Gain.input_slots(),
+                output_slots: Gain.output_slots(),
+            })))
+            .unwrap();
+        live_graph
+            .connect(value_node, custom_node, SlotId(0), SlotId(0))
+            .unwrap();
+        add_output(&mut live_graph, custom_node)
+    };
+
+    assert_eq!(
+        LiveGraph::await_clean_read(&live_graph, output_node).map(|_| ()),
+        Err(TexProError::NodeFailed)
+    );
+    assert!(matches!(
+        live_graph.read().unwrap().node_error(output_node),
+        Some(TexProError::UnknownNodeType(type_id)) if type_id == "studio.gain"
+    ));
+
+    // Registering the node type makes the node process again.
+    tex_pro.register_node_processor(Arc::new(Gain)).unwrap();
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    assert_pixels_eq(&pixels, &[0.5, 0.5, 0.5, 1.0]);
+}
+
+/// Returns the images of the function instead of one RGBA image of the input size.
+struct BadOutput(fn(Size) -> Result<Vec<SlotImage>, TexProError>);
+
+impl NodeProcessor for BadOutput {
+    fn id(&self) -> &str {
+        "studio.bad_output"
+    }
+
+    fn input_slots(&self) -> Vec<CustomSlot> {
+        vec![CustomSlot::new("input", SlotType::GrayOrRgba)]
+    }
+
+    fn output_slots(&self) -> Vec<CustomSlot> {
+        vec![CustomSlot::new("output", SlotType::Rgba)]
+    }
+
+    fn process(&self, context: &ProcessContext) -> Result<Vec<SlotImage>, TexProError> {
+        (self.0)(context.size())
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn custom_node_bad_output() {
+    fn rgba(size: Size) -> Result<SlotImage, TexProError> {
+        SlotImage::from_rgba_f32(size, &vec![0.0; size.pixel_count() * 4])
+    }
+
+    let cases = vec![
+        (
+            BadOutput(|_| Ok(Vec::new())),
+            TexProError::InvalidBufferCount,
+        ),
+        (
+            BadOutput(|size| Ok(vec![rgba(size)?, rgba(size)?])),
+            TexProError::InvalidBufferCount,
+        ),
+        (
+            BadOutput(|size| Ok(vec![rgba(size)?.as_type(false)?])),
+            TexProError::InvalidSlotType,
+        ),
+        (
+            BadOutput(|size| Ok(vec![rgba(Size::new(size.width + 1, size.height))?])),
+            TexProError::InvalidBufferCount,
+        ),
+    ];
+
+    for (node_processor, error) in cases {
+        let tex_pro = tex_pro_new();
+        tex_pro
+            .register_node_processor(Arc::new(node_processor))
+            .unwrap();
+        let node_type = tex_pro.custom_node_type("studio.bad_output").unwrap();
+
+        let live_graph = tex_pro.new_live_graph().unwrap();
+        let output_node = {
+            let mut live_graph = live_graph.write().unwrap();
+            let value_node = live_graph
+                .add_node(Node::new(NodeType::Value(0.25)))
+                .unwrap();
+            let custom_node = live_graph.add_node(Node::new(node_type)).unwrap();
+            live_graph
+                .connect(value_node, custom_node, SlotId(0), SlotId(0))
+                .unwrap();
+            add_output(&mut live_graph, custom_node)
+        };
+
+        assert_eq!(
+            LiveGraph::await_clean_read(&live_graph, output_node).map(|_| ()),
+            Err(TexProError::NodeFailed)
+        );
+        assert_eq!(
+            live_graph.read().unwrap().node_error(output_node),
+            Some(&error)
+        );
+    }
+}
+
+#[test]
+fn custom_node_json() {
+    const PATH: &str = "out/custom_node_json.json";
+    ensure_out_dir();
+
+    let tex_pro = tex_pro_new();
+    tex_pro.register_node_processor(Arc::new(Gain)).unwrap();
+    let node_type = match tex_pro.custom_node_type("studio.gain").unwrap() {
+        NodeType::Custom(custom_node) => custom_node.parameter("gain", ParameterValue::Float(0.5)),
+        _ => unreachable!(),
+    };
+
+    let mut node_graph = NodeGraph::new();
+    node_graph
+        .add_node(Node::new(NodeType::Custom(node_type.clone())))
+        .unwrap();
+    node_graph.export_json(PATH.into()).unwrap();
+
+    let node_graph = NodeGraph::from_path(PATH.into()).unwrap();
+    match &node_graph.nodes()[0].node_type {
+        NodeType::Custom(loaded) => assert_eq!(*loaded, node_type),
+        _ => panic!("the node is not a custom node"),
+    }
+}
+
+/// Processes embedded RGBA pixels with a node that has a single input, and returns the output.
+fn node_pixels(node_type: NodeType, size: Size, pixels: &[f32]) -> Vec<f32> {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let input_node = embed_rgba(&mut live_graph, 0, size, pixels);
+        let node = live_graph.add_node(Node::new(node_type)).unwrap();
+        live_graph
+            .connect(input_node, node, SlotId(0), SlotId(0))
+            .unwrap();
+        add_output(&mut live_graph, node)
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    pixels
+}
+
+fn single_input_test(node_type: NodeType, name: &str) {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let image_node = live_graph
+            .add_node(Node::new(NodeType::Image(IMAGE_1.into())))
+            .unwrap();
+        let node = live_graph.add_node(Node::new(node_type)).unwrap();
+        live_graph
+            .connect(image_node, node, SlotId(0), SlotId(0))
+            .unwrap();
+        add_output(&mut live_graph, node)
+    };
+
+    save_and_compare(&live_graph, output_node, name);
+}
+
+const TONE_PIXELS: [f32; 8] = [0.2, 0.5, 0.8, 1.0, 0.0, 1.0, 0.35, 0.5];
+
+#[test]
+#[timeout(20_000)]
+fn levels_node() {
+    let cases = [
+        (
+            Level::default().input(0.2, 0.8),
+            [0.0, 0.5, 1.0, 1.0, 0.0, 1.0, 0.25, 0.5],
+        ),
+        (
+            Level::default().gamma(2.0),
+            [0.447_2, 0.707_1, 0.894_4, 1.0, 0.0, 1.0, 0.591_6, 0.5],
+        ),
+        (
+            Level::default().output(0.1, 0.6),
+            [0.2, 0.35, 0.5, 1.0, 0.1, 0.6, 0.275, 0.5],
+        ),
+    ];
+
+    for (level, expected) in cases {
+        assert_pixels_eq(
+            &node_pixels(
+                NodeType::Levels(Levels::new(ToneChannels::Combined(level))),
+                Size::new(2, 1),
+                &TONE_PIXELS,
+            ),
+            &expected,
+        );
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn levels_node_per_channel() {
+    let levels = Levels::new(ToneChannels::Separate([
+        Level::default().output(0.0, 0.5),
+        Level::default(),
+        Level::default().input(0.5, 1.0),
+    ]));
+
+    assert_pixels_eq(
+        &node_pixels(NodeType::Levels(levels), Size::new(2, 1), &TONE_PIXELS),
+        &[0.1, 0.5, 0.6, 1.0, 0.0, 1.0, 0.0, 0.5],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn auto_levels_node() {
+    const PIXELS: [f32; 8] = [0.25, 0.5, 0.75, 1.0, 0.5, 0.5, 0.5, 1.0];
+    let auto_levels = AutoLevels { clip: 0.0 };
+
+    assert_pixels_eq(
+        &node_pixels(
+            NodeType::Levels(Levels::default().auto_levels(auto_levels)),
+            Size::new(2, 1),
+            &PIXELS,
+        ),
+        &[0.0, 0.5, 1.0, 1.0, 0.5, 0.5, 0.5, 1.0],
+    );
+
+    // The green channel has a single value, so it's left alone.
+    assert_pixels_eq(
+        &node_pixels(
+            NodeType::Levels(
+                Levels::new(ToneChannels::Separate([Level::default(); 3])).auto_levels(auto_levels),
+            ),
+            Size::new(2, 1),
+            &PIXELS,
+        ),
+        &[0.0, 0.5, 1.0, 1.0, 1.0, 0.5, 0.0, 1.0],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn levels_node_image() {
+    single_input_test(
+        NodeType::Levels(Levels::new(ToneChannels::Combined(
+            Level::default()
+                .input(0.1, 0.9)
+                .gamma(1.5)
+                .output(0.05, 0.95),
+        ))),
+        "levels_node_image.png",
+    );
+}
+
+#[test]
+fn curve_interpolation() {
+    let curve = Curve::new(vec![
+        CurvePoint::new(1.0, 1.0),
+        CurvePoint::new(0.0, 0.0),
+        CurvePoint::new(0.5, 0.25),
+    ]);
+
+    // The points are sorted and the curve goes through them.
+    assert_eq!(
+        curve.points(),
+        &[
+            CurvePoint::new(0.0, 0.0),
+            CurvePoint::new(0.5, 0.25),
+            CurvePoint::new(1.0, 1.0),
+        ]
+    );
+    for point in curve.points() {
+        assert!((curve.apply(point.x) - point.y).abs() < 0.000_1);
+    }
+
+    // It's flat outside of the points, and increases between them without overshooting.
+    assert_eq!(curve.apply(-1.0), 0.0);
+    assert_eq!(curve.apply(2.0), 1.0);
+    let values = (0..=100)
+        .map(|i| curve.apply(i as f32 / 100.0))
+        .collect::<Vec<f32>>();
+    assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    assert_eq!(Curve::new(Vec::new()).apply(0.3), 0.3);
+    assert_eq!(Curve::new(vec![CurvePoint::new(0.5, 0.7)]).apply(0.3), 0.7);
+}
+
+#[test]
+#[timeout(20_000)]
+fn curves_node() {
+    let curves = Curves::new(ToneChannels::Separate([
+        Curve::default(),
+        Curve::new(vec![CurvePoint::new(0.0, 1.0), CurvePoint::new(1.0, 0.0)]),
+        Curve::new(vec![CurvePoint::new(0.0, 0.3)]),
+    ]));
+
+    assert_pixels_eq(
+        &node_pixels(NodeType::Curves(curves), Size::new(2, 1), &TONE_PIXELS),
+        &[0.2, 0.5, 0.3, 1.0, 0.0, 0.0, 0.3, 0.5],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn curves_node_image() {
+    single_input_test(
+        NodeType::Curves(Curves::new(ToneChannels::Combined(Curve::new(vec![
+            CurvePoint::new(0.0, 0.0),
+            CurvePoint::new(0.25, 0.15),
+            CurvePoint::new(0.75, 0.85),
+            CurvePoint::new(1.0, 1.0),
+        ])))),
+        "curves_node_image.png",
+    );
+}
+
+#[test]
+fn curves_json() {
+    let curves = Curves::new(ToneChannels::Combined(Curve::new(vec![
+        CurvePoint::new(0.0, 0.1),
+        CurvePoint::new(1.0, 0.9),
+    ])));
+
+    // Only the points are stored.
+    let json = serde_json::to_string(&NodeType::Curves(curves.clone())).unwrap();
+    assert_eq!(
+        json,
+        r#"{"Curves":{"curves":{"Combined":[{"x":0.0,"y":0.1},{"x":1.0,"y":0.9}]}}}"#
+    );
+
+    match serde_json::from_str(&json).unwrap() {
+        NodeType::Curves(loaded) => assert_eq!(loaded, curves),
+        _ => panic!("the node is not a curves node"),
+    }
+}
+
+/// Maps a horizontal ramp of 0, 0.2, 0.4, 0.6 and 0.8 through a `GradientMap` from red at 0
+/// to transparent blue at 0.8.
+fn gradient_map_pixels(interpolation: GradientInterpolation, alpha: bool) -> Vec<f32> {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let ramp_node = live_graph
+            .add_node(
+                Node::new(NodeType::Expression(Expression::new("u")))
+                    .resize_policy(ResizePolicy::SpecificSize(Size::new(5, 1))),
+            )
+            .unwrap();
+        let gradient_map_node = live_graph
+            .add_node(Node::new(NodeType::GradientMap(
+                GradientMap::new(vec![
+                    GradientStop::new(0.8, [0.0, 0.0, 1.0, 0.0]),
+                    GradientStop::new(0.0, [1.0, 0.0, 0.0, 1.0]),
+                ])
+                .interpolation(interpolation)
+                .alpha(alpha),
+            )))
+            .unwrap();
+        live_graph
+            .connect(ramp_node, gradient_map_node, SlotId(0), SlotId(0))
+            .unwrap();
+        add_output(&mut live_graph, gradient_map_node)
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    pixels
+}
+
+#[test]
+#[timeout(20_000)]
+fn gradient_map_node() {
+    let cases = [
+        (GradientInterpolation::Linear, [1.0, 0.75, 0.5, 0.25, 0.0]),
+        (
+            GradientInterpolation::Smooth,
+            [1.0, 0.843_75, 0.5, 0.156_25, 0.0],
+        ),
+        (GradientInterpolation::Constant, [1.0, 1.0, 1.0, 1.0, 0.0]),
+    ];
+
+    for (interpolation, red) in cases {
+        let expected = red
+            .iter()
+            .flat_map(|red| vec![*red, 0.0, 1.0 - red, 1.0])
+            .collect::<Vec<f32>>();
+        assert_pixels_eq(&gradient_map_pixels(interpolation, false), &expected);
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn gradient_map_node_alpha() {
+    let alpha = gradient_map_pixels(GradientInterpolation::Linear, true)
+        .chunks(4)
+        .map(|pixel| pixel[3])
+        .collect::<Vec<f32>>();
+    assert_pixels_eq(&alpha, &[1.0, 0.75, 0.5, 0.25, 0.0]);
+}
+
+#[test]
+fn gradient_map_color() {
+    assert_eq!(
+        GradientMap::new(Vec::new()).color(0.5),
+        [0.0, 0.0, 0.0, 1.0]
+    );
+    assert_eq!(GradientMap::default().color(0.25), [0.25, 0.25, 0.25, 1.0]);
+    assert_eq!(GradientMap::default().color(2.0), [1.0, 1.0, 1.0, 1.0]);
+}
+
+#[test]
+#[timeout(20_000)]
+fn gradient_map_node_image() {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let image_node = live_graph
+            .add_node(Node::new(NodeType::Image(CLOUDS.into())))
+            .unwrap();
+        let separate_node = live_graph
+            .add_node(Node::new(NodeType::SeparateRgba))
+            .unwrap();
+        let gradient_map_node = live_graph
+            .add_node(Node::new(NodeType::GradientMap(
+                GradientMap::new(vec![
+                    GradientStop::new(0.0, [0.05, 0.0, 0.2, 1.0]),
+                    GradientStop::new(0.4, [0.8, 0.1, 0.1, 1.0]),
+                    GradientStop::new(0.7, [1.0, 0.8, 0.2, 1.0]),
+                    GradientStop::new(1.0, [1.0, 1.0, 1.0, 1.0]),
+                ])
+                .interpolation(GradientInterpolation::Smooth),
+            )))
+            .unwrap();
+        live_graph
+            .connect(image_node, separate_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(separate_node, gradient_map_node, SlotId(0), SlotId(0))
+            .unwrap();
+        add_output(&mut live_graph, gradient_map_node)
+    };
+
+    save_and_compare(&live_graph, output_node, "gradient_map_node_image.png");
+}
+
+#[test]
+fn gradient_map_json() {
+    let gradient_map = GradientMap::new(vec![
+        GradientStop::new(0.0, [0.0, 0.0, 0.0, 1.0]),
+        GradientStop::new(1.0, [1.0, 0.5, 0.0, 0.5]),
+    ])
+    .interpolation(GradientInterpolation::Constant)
+    .alpha(true);
+
+    let json = serde_json::to_string(&NodeType::GradientMap(gradient_map.clone())).unwrap();
+    match serde_json::from_str(&json).unwrap() {
+        NodeType::GradientMap(loaded) => assert_eq!(loaded, gradient_map),
+        _ => panic!("the node is not a gradient map node"),
+    }
+}
+
+const COLOR_PIXELS: [f32; 8] = [1.0, 0.0, 0.0, 1.0, 0.2, 0.4, 0.8, 0.5];
+
+#[test]
+#[timeout(20_000)]
+fn convert_color_node() {
+    let cases = [
+        (
+            ColorModel::Hsv,
+            [0.0, 1.0, 1.0, 1.0, 0.611_1, 0.75, 0.8, 0.5],
+        ),
+        (
+            ColorModel::Hsl,
+            [0.0, 1.0, 0.5, 1.0, 0.611_1, 0.6, 0.5, 0.5],
+        ),
+        (
+            ColorModel::Ycbcr,
+            [0.299, 0.331_3, 1.0, 1.0, 0.385_8, 0.733_7, 0.367_5, 0.5],
+        ),
+    ];
+
+    for (color_model, expected) in cases {
+        let converted = node_pixels(
+            NodeType::ConvertColor(ConvertColor::new(ColorModel::Rgb, color_model)),
+            Size::new(2, 1),
+            &COLOR_PIXELS,
+        );
+        assert_pixels_eq(&converted, &expected);
+
+        assert_pixels_eq(
+            &node_pixels(
+                NodeType::ConvertColor(ConvertColor::new(color_model, ColorModel::Rgb)),
+                Size::new(2, 1),
+                &converted,
+            ),
+            &COLOR_PIXELS,
+        );
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn convert_color_between_models() {
+    assert_pixels_eq(
+        &node_pixels(
+            NodeType::ConvertColor(ConvertColor::new(ColorModel::Hsv, ColorModel::Hsl)),
+            Size::new(2, 1),
+            &[0.0, 1.0, 1.0, 1.0, 0.611_111, 0.75, 0.8, 0.5],
+        ),
+        &[0.0, 1.0, 0.5, 1.0, 0.611_1, 0.6, 0.5, 0.5],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn convert_color_separate() {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let input_node = embed_rgba(&mut live_graph, 0, Size::new(2, 1), &COLOR_PIXELS);
+        let convert_node = live_graph
+            .add_node(Node::new(NodeType::ConvertColor(ConvertColor::new(
+                ColorModel::Rgb,
+                ColorModel::Hsv,
+            ))))
+            .unwrap();
+        let separate_node = live_graph
+            .add_node(Node::new(NodeType::SeparateRgba))
+            .unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputGray("out".into())))
+            .unwrap();
+        live_graph
+            .connect(input_node, convert_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(convert_node, separate_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(separate_node, output_node, SlotId(1), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    assert_pixels_eq(&pixels, &[1.0, 1.0, 1.0, 1.0, 0.75, 0.75, 0.75, 1.0]);
+}
+
+#[test]
+#[timeout(20_000)]
+fn hsl_adjust_node() {
+    let cases = [
+        (
+            HslAdjust::default().hue_offset(1.0 / 3.0),
+            [0.0, 1.0, 0.0, 1.0, 0.8, 0.2, 0.4, 0.5],
+        ),
+        (
+            HslAdjust::default().saturation(-1.0),
+            [0.5, 0.5, 0.5, 1.0, 0.5, 0.5, 0.5, 0.5],
+        ),
+        (
+            HslAdjust::default().lightness(0.25),
+            [1.0, 0.5, 0.5, 1.0, 0.6, 0.7, 0.9, 0.5],
+        ),
+        (HslAdjust::default(), COLOR_PIXELS),
+    ];
+
+    for (hsl_adjust, expected) in cases {
+        assert_pixels_eq(
+            &node_pixels(
+                NodeType::HslAdjust(hsl_adjust),
+                Size::new(2, 1),
+                &COLOR_PIXELS,
+            ),
+            &expected,
+        );
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn hsl_adjust_node_image() {
+    single_input_test(
+        NodeType::HslAdjust(HslAdjust::default().hue_offset(0.5).saturation(0.2)),
+        "hsl_adjust_node_image.png",
+    );
+}
+
+fn gray_pixels(node_type: NodeType, gray_conversion: GrayConversion) -> Vec<f32> {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        live_graph.set_gray_conversion(gray_conversion).unwrap();
+        let input_node = embed_rgba(&mut live_graph, 0, Size::new(2, 1), &COLOR_PIXELS);
+        let node = live_graph.add_node(Node::new(node_type)).unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputGray("out".into())))
+            .unwrap();
+        live_graph
+            .connect(input_node, node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    pixels.chunks(4).map(|pixel| pixel[0]).collect::<Vec<f32>>()
+}
+
+#[test]
+#[timeout(20_000)]
+fn grayscale_node() {
+    let cases = [
+        (GrayConversion::Rec709, [0.212_6, 0.386_4]),
+        (GrayConversion::Rec601, [0.299, 0.385_8]),
+        (GrayConversion::Average, [0.333_3, 0.466_7]),
+        (GrayConversion::Max, [1.0, 0.8]),
+        (GrayConversion::Red, [1.0, 0.2]),
+        (GrayConversion::Green, [0.0, 0.4]),
+        (GrayConversion::Blue, [0.0, 0.8]),
+    ];
+
+    for (gray_conversion, expected) in cases {
+        assert_pixels_eq(
+            &gray_pixels(
+                NodeType::Grayscale(gray_conversion),
+                GrayConversion::default(),
+            ),
+            &expected,
+        );
+    }
+}
+
+/// Feeds a gray 0.0 into `SlotId(0)` and `COLOR_PIXELS` into `SlotId(1)` of the node, so the
+/// node has to convert the RGBA input to gray. The RGBA input goes through `Levels`, since its
+/// `SlotType::GrayOrRgba` output can be connected to gray inputs.
+fn implicit_gray_pixels(node_type: NodeType, gray_conversion: GrayConversion) -> Vec<f32> {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        live_graph.set_gray_conversion(gray_conversion).unwrap();
+        let gray_node = live_graph
+            .add_node(Node::new(NodeType::Value(0.0)))
+            .unwrap();
+        let rgba_node = embed_rgba(&mut live_graph, 0, Size::new(2, 1), &COLOR_PIXELS);
+        let levels_node = live_graph
+            .add_node(Node::new(NodeType::Levels(Levels::default())))
+            .unwrap();
+        let node = live_graph.add_node(Node::new(node_type)).unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputGray("out".into())))
+            .unwrap();
+        live_graph
+            .connect(gray_node, node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(rgba_node, levels_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(levels_node, node, SlotId(0), SlotId(1))
+            .unwrap();
+        live_graph
+            .connect(node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    pixels.chunks(4).map(|pixel| pixel[0]).collect::<Vec<f32>>()
+}
+
+#[test]
+#[timeout(20_000)]
+fn gray_conversion_implicit() {
+    // Mixing an RGBA image into a gray one, or reading it from a gray expression input,
+    // converts it with the graph's `GrayConversion`.
+    let node_types = [
+        NodeType::Mix(Mix::new(MixType::Add)),
+        NodeType::Expression(
+            Expression::new("b")
+                .input("a", SlotType::Gray)
+                .input("b", SlotType::Gray),
+        ),
+    ];
+
+    for node_type in node_types {
+        assert_pixels_eq(
+            &implicit_gray_pixels(node_type.clone(), GrayConversion::default()),
+            &[0.333_3, 0.466_7],
+        );
+        assert_pixels_eq(
+            &implicit_gray_pixels(node_type, GrayConversion::Rec709),
+            &[0.212_6, 0.386_4],
+        );
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn gray_conversion_dirties_nodes() {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let gray_node = live_graph
+            .add_node(Node::new(NodeType::Value(0.0)))
+            .unwrap();
+        let rgba_node = embed_rgba(&mut live_graph, 0, Size::new(2, 1), &COLOR_PIXELS);
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputGray("out".into())))
+            .unwrap();
+        let mix_node = live_graph
+            .add_node(Node::new(NodeType::Mix(Mix::new(MixType::Add))))
+            .unwrap();
+        live_graph
+            .connect(gray_node, mix_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(rgba_node, mix_node, SlotId(0), SlotId(1))
+            .unwrap();
+        live_graph
+            .connect(mix_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+    LiveGraph::await_clean_read(&live_graph, output_node).unwrap();
+
+    live_graph
+        .write()
+        .unwrap()
+        .set_gray_conversion(GrayConversion::Max)
+        .unwrap();
+    assert_eq!(
+        live_graph.read().unwrap().node_state(output_node).unwrap(),
+        NodeState::Dirty
+    );
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    assert_pixels_eq(&pixels, &[1.0, 1.0, 1.0, 1.0, 0.8, 0.8, 0.8, 1.0]);
+}
+
+#[test]
+fn gray_conversion_json() {
+    let mut node_graph = NodeGraph::new();
+    node_graph.gray_conversion = GrayConversion::Rec709;
+    let json = serde_json::to_string(&node_graph).unwrap();
+    let loaded: NodeGraph = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.gray_conversion, GrayConversion::Rec709);
+
+    // Graphs saved before the setting existed keep averaging.
+    let loaded: NodeGraph = serde_json::from_str(r#"{"nodes":[],"edges":[]}"#).unwrap();
+    assert_eq!(loaded.gray_conversion, GrayConversion::Average);
+}
+
+/// A row of four opaque gray pixels, going from 0.1 to 0.4.
+const RAMP_PIXELS: [f32; 16] = [
+    0.1, 0.1, 0.1, 1.0, 0.2, 0.2, 0.2, 1.0, 0.3, 0.3, 0.3, 1.0, 0.4, 0.4, 0.4, 1.0,
+];
+
+/// Returns the red channel of the ramp after a node.
+fn ramp_red(node_type: NodeType) -> Vec<f32> {
+    node_pixels(node_type, Size::new(4, 1), &RAMP_PIXELS)
+        .chunks(4)
+        .map(|pixel| pixel[0])
+        .collect()
+}
+
+#[test]
+#[timeout(20_000)]
+fn transform_offset() {
+    assert_pixels_eq(
+        &ramp_red(NodeType::Transform(
+            Transform::default()
+                .offset(0.25, 0.0)
+                .filter(ResizeFilter::Nearest),
+        )),
+        &[0.4, 0.1, 0.2, 0.3],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn transform_edge_modes() {
+    let cases = [
+        (EdgeMode::Wrap, [0.3, 0.4, 0.1, 0.2]),
+        (EdgeMode::Clamp, [0.1, 0.1, 0.1, 0.2]),
+        (EdgeMode::Mirror, [0.2, 0.1, 0.1, 0.2]),
+    ];
+
+    for (edge_mode, expected) in cases {
+        assert_pixels_eq(
+            &ramp_red(NodeType::Transform(
+                Transform::default()
+                    .offset(0.5, 0.0)
+                    .edge_mode(edge_mode)
+                    .filter(ResizeFilter::Nearest),
+            )),
+            &expected,
+        );
+    }
+
+    assert_pixels_eq(
+        &node_pixels(
+            NodeType::Transform(
+                Transform::default()
+                    .offset(0.5, 0.0)
+                    .edge_mode(EdgeMode::Transparent)
+                    .filter(ResizeFilter::Nearest),
+            ),
+            Size::new(4, 1),
+            &RAMP_PIXELS,
+        ),
+        &[
+            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.1, 0.1, 0.1, 1.0, 0.2, 0.2, 0.2, 1.0,
+        ],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn transform_filters() {
+    let cases = [
+        (ResizeFilter::Triangle, [0.25, 0.15, 0.25, 0.35]),
+        (ResizeFilter::CatmullRom, [0.25, 0.125, 0.25, 0.375]),
+    ];
+
+    for (filter, expected) in cases {
+        assert_pixels_eq(
+            &ramp_red(NodeType::Transform(
+                Transform::default().offset(0.125, 0.0).filter(filter),
+            )),
+            &expected,
+        );
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn transform_rotation() {
+    let pixels = node_pixels(
+        NodeType::Transform(
+            Transform::default()
+                .rotation(0.25)
+                .filter(ResizeFilter::Nearest),
+        ),
+        Size::new(2, 2),
+        &RAMP_PIXELS,
+    );
+
+    // A quarter turn clockwise.
+    assert_pixels_eq(
+        &pixels.chunks(4).map(|pixel| pixel[0]).collect::<Vec<f32>>(),
+        &[0.3, 0.1, 0.4, 0.2],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn transform_tiles() {
+    assert_pixels_eq(
+        &ramp_red(NodeType::Transform(
+            Transform::default()
+                .tiles(2, 1)
+                .filter(ResizeFilter::Nearest),
+        )),
+        &[0.2, 0.4, 0.2, 0.4],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn transform_node_image() {
+    single_input_test(
+        NodeType::Transform(
+            Transform::default()
+                .rotation(0.125)
+                .scale(0.75, 0.75)
+                .tiles(2, 2)
+                .edge_mode(EdgeMode::Mirror)
+                .filter(ResizeFilter::CatmullRom),
+        ),
+        "transform_node_image.png",
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn make_tile_seamless() {
+    const SIZE: u32 = 32;
+    // A diagonal ramp, which jumps from bright to dark at the edges.
+    let pixels = (0..SIZE * SIZE)
+        .flat_map(|i| {
+            let value = ((i % SIZE) + (i / SIZE)) as f32 / (SIZE * 2) as f32;
+            vec![value, value, value, 1.0]
+        })
+        .collect::<Vec<f32>>();
+    let value = |pixels: &[f32], x: u32, y: u32| pixels[((y * SIZE + x) * 4) as usize];
+
+    for method in [TileMethod::Offset, TileMethod::Mirror, TileMethod::Patch] {
+        let tiling = node_pixels(
+            NodeType::MakeTile(MakeTile::new(method)),
+            Size::new(SIZE, SIZE),
+            &pixels,
+        );
+
+        // Going across the edges isn't a bigger step than going between any other pixels.
+        let largest_step = (0..SIZE)
+            .flat_map(|i| (1..SIZE).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                (value(&tiling, j, i) - value(&tiling, j - 1, i))
+                    .abs()
+                    .max((value(&tiling, i, j) - value(&tiling, i, j - 1)).abs())
+            })
+            .fold(0.0, f32::max);
+        for i in 0..SIZE {
+            let seam_x = (value(&tiling, 0, i) - value(&tiling, SIZE - 1, i)).abs();
+            let seam_y = (value(&tiling, i, 0) - value(&tiling, i, SIZE - 1)).abs();
+            assert!(
+                seam_x <= largest_step + 1e-4 && seam_y <= largest_step + 1e-4,
+                "{} has a seam at {}: {}, {}",
+                method,
+                i,
+                seam_x,
+                seam_y
+            );
+        }
+
+        // The edge blending leaves the middle alone.
+        if method != TileMethod::Patch {
+            assert_pixels_eq(
+                &[value(&tiling, SIZE / 2, SIZE / 2)],
+                &[value(&pixels, SIZE / 2, SIZE / 2)],
+            );
+        }
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn make_tile_offset_image() {
+    single_input_test(
+        NodeType::MakeTile(MakeTile::new(TileMethod::Offset).blend_width(0.3)),
+        "make_tile_offset_image.png",
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn make_tile_patch_image() {
+    single_input_test(
+        NodeType::MakeTile(MakeTile::new(TileMethod::Patch).seed(3)),
+        "make_tile_patch_image.png",
+    );
+}
+
+/// Warps `RAMP_PIXELS` by the map `add_map` adds, and returns the red channel.
+fn warp_red(node_type: NodeType, add_map: impl FnOnce(&mut LiveGraph) -> NodeId) -> Vec<f32> {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let input_node = embed_rgba(&mut live_graph, 0, Size::new(4, 1), &RAMP_PIXELS);
+        let map_node = add_map(&mut live_graph);
+        let warp_node = live_graph.add_node(Node::new(node_type)).unwrap();
+        live_graph
+            .connect(input_node, warp_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(map_node, warp_node, SlotId(0), SlotId(1))
+            .unwrap();
+        add_output(&mut live_graph, warp_node)
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    pixels.chunks(4).map(|pixel| pixel[0]).collect()
+}
+
+fn add_value(live_graph: &mut LiveGraph, value: f32) -> NodeId {
+    live_graph
+        .add_node(Node::new(NodeType::Value(value)))
+        .unwrap()
+}
+
+#[test]
+#[timeout(20_000)]
+fn warp_node() {
+    // A flat map has no slopes, so nothing moves.
+    assert_pixels_eq(
+        &warp_red(NodeType::Warp(Warp::new(0.25)), |live_graph| {
+            add_value(live_graph, 0.7)
+        }),
+        &[0.1, 0.2, 0.3, 0.4],
+    );
+
+    // The ramp goes uphill to the right, except where it wraps around.
+    assert_pixels_eq(
+        &warp_red(NodeType::Warp(Warp::new(0.25)), |live_graph| {
+            live_graph
+                .add_node(
+                    Node::new(NodeType::Expression(Expression::new("u")))
+                        .resize_policy(ResizePolicy::SpecificSize(Size::new(4, 1))),
+                )
+                .unwrap()
+        }),
+        &[0.4, 0.3, 0.4, 0.3],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn directional_warp_node() {
+    let cases = [(0.0, [0.2, 0.3, 0.4, 0.1]), (0.5, [0.4, 0.1, 0.2, 0.3])];
+
+    for (angle, expected) in cases {
+        assert_pixels_eq(
+            &warp_red(
+                NodeType::DirectionalWarp(DirectionalWarp::new(0.25, angle)),
+                |live_graph| add_value(live_graph, 1.0),
+            ),
+            &expected,
+        );
+    }
+
+    // Nothing moves where the intensity map is black.
+    assert_pixels_eq(
+        &warp_red(
+            NodeType::DirectionalWarp(DirectionalWarp::new(0.25, 0.0)),
+            |live_graph| add_value(live_graph, 0.0),
+        ),
+        &[0.1, 0.2, 0.3, 0.4],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn vector_warp_node() {
+    let cases = [
+        ([0.75, 0.5], [0.2, 0.3, 0.4, 0.1]),
+        ([0.25, 0.5], [0.4, 0.1, 0.2, 0.3]),
+        ([0.5, 0.5], [0.1, 0.2, 0.3, 0.4]),
+    ];
+
+    for ([x, y], expected) in cases {
+        assert_pixels_eq(
+            &warp_red(NodeType::VectorWarp(VectorWarp::new(0.5)), |live_graph| {
+                embed_rgba(live_graph, 1, Size::new(1, 1), &[x, y, 1.0, 1.0])
+            }),
+            &expected,
+        );
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn warp_node_image() {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let image_node = live_graph
+            .add_node(Node::new(NodeType::Image(IMAGE_1.into())))
+            .unwrap();
+        let clouds_node = live_graph
+            .add_node(Node::new(NodeType::Image(CLOUDS.into())))
+            .unwrap();
+        let separate_node = live_graph
+            .add_node(Node::new(NodeType::SeparateRgba))
+            .unwrap();
+        let warp_node = live_graph
+            .add_node(Node::new(NodeType::Warp(Warp::new(0.002))))
+            .unwrap();
+        live_graph
+            .connect(clouds_node, separate_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(image_node, warp_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(separate_node, warp_node, SlotId(0), SlotId(1))
+            .unwrap();
+        add_output(&mut live_graph, warp_node)
+    };
+
+    save_and_compare(&live_graph, output_node, "warp_node_image.png");
+}
+
+/// Runs a node on a 5 by 5 height map where the pixel at `peak_index` is `peak` and the rest
+/// are `base`, and returns the gray output.
+fn height_map_gray(node_type: NodeType, base: f32, peak: f32, peak_index: usize) -> Vec<f32> {
+    let mut heights = [base; 25];
+    heights[peak_index] = peak;
+    let pixels = heights
+        .iter()
+        .flat_map(|&height| [height, 0.0, 0.0, 1.0])
+        .collect::<Vec<f32>>();
+
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let input_node = embed_rgba(&mut live_graph, 0, Size::new(5, 5), &pixels);
+        let grayscale_node = live_graph
+            .add_node(Node::new(NodeType::Grayscale(GrayConversion::Red)))
+            .unwrap();
+        let node = live_graph.add_node(Node::new(node_type)).unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputGray("out".into())))
+            .unwrap();
+        live_graph
+            .connect(input_node, grayscale_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(grayscale_node, node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    pixels.chunks(4).map(|pixel| pixel[0]).collect()
+}
+
+/// Moves the pixels of a 5 by 5 image so the center pixel ends up in the top left corner.
+fn center_to_corner(pixels: &[f32]) -> Vec<f32> {
+    (0..25)
+        .map(|i| pixels[((i / 5 + 2) % 5) * 5 + (i % 5 + 2) % 5])
+        .collect()
+}
+
+fn height_map_image(node_type: NodeType, name: &str) {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let image_node = live_graph
+            .add_node(Node::new(NodeType::Image(CLOUDS.into())))
+            .unwrap();
+        let separate_node = live_graph
+            .add_node(Node::new(NodeType::SeparateRgba))
+            .unwrap();
+        let node = live_graph.add_node(Node::new(node_type)).unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputGray("out".into())))
+            .unwrap();
+        live_graph
+            .connect(image_node, separate_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(separate_node, node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    save_and_compare(&live_graph, output_node, name);
+}
+
+#[test]
+#[timeout(20_000)]
+fn ambient_occlusion_node() {
+    let ambient_occlusion = AmbientOcclusion::default().radius(0.4).quality(4);
+    let node_type = NodeType::AmbientOcclusion(ambient_occlusion);
+
+    // Nothing is occluded on a flat height map.
+    assert_pixels_eq(
+        &height_map_gray(node_type.clone(), 0.5, 0.5, 12),
+        &[1.0; 25],
+    );
+
+    // The bottom of a pit is occluded, while the corners far away from it are not.
+    let pit = height_map_gray(node_type.clone(), 1.0, 0.0, 12);
+    assert!(pit[12] < 0.5, "{:?}", pit);
+    assert_pixels_eq(&[pit[0]], &[1.0]);
+
+    // The height map wraps around, so a pit in the corner is occluded by the other sides.
+    assert_pixels_eq(
+        &height_map_gray(node_type, 1.0, 0.0, 0),
+        &center_to_corner(&pit),
+    );
+
+    assert_pixels_eq(
+        &height_map_gray(
+            NodeType::AmbientOcclusion(ambient_occlusion.intensity(0.0)),
+            1.0,
+            0.0,
+            12,
+        ),
+        &[1.0; 25],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn curvature_node() {
+    let node_type = NodeType::Curvature(Curvature::new(1.0));
+
+    assert_pixels_eq(
+        &height_map_gray(node_type.clone(), 0.5, 0.5, 12),
+        &[0.5; 25],
+    );
+
+    // A bump is convex and a pit is concave.
+    let bump = height_map_gray(node_type.clone(), 0.0, 0.01, 12);
+    let pit = height_map_gray(node_type.clone(), 0.01, 0.0, 12);
+    assert!(bump[12] > 0.5, "{:?}", bump);
+    assert!(pit[12] < 0.5, "{:?}", pit);
+
+    assert_pixels_eq(
+        &height_map_gray(node_type, 0.0, 0.01, 0),
+        &center_to_corner(&bump),
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn ambient_occlusion_node_image() {
+    height_map_image(
+        NodeType::AmbientOcclusion(AmbientOcclusion::default()),
+        "ambient_occlusion_node_image.png",
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn curvature_node_image() {
+    height_map_image(
+        NodeType::Curvature(Curvature::default()),
+        "curvature_node_image.png",
+    );
+}
+
+/// Makes normals from a 5 by 5 height map with the heights `height` returns for each x and y,
+/// and returns them as RGBA.
+fn normal_pixels(
+    height_to_normal: HeightToNormal,
+    height: impl Fn(usize, usize) -> f32,
+) -> Vec<f32> {
+    let pixels = (0..25)
+        .flat_map(|i| [height(i % 5, i / 5), 0.0, 0.0, 1.0])
+        .collect::<Vec<f32>>();
+
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        let input_node = embed_rgba(&mut live_graph, 0, Size::new(5, 5), &pixels);
+        let grayscale_node = live_graph
+            .add_node(Node::new(NodeType::Grayscale(GrayConversion::Red)))
+            .unwrap();
+        let normal_node = live_graph
+            .add_node(Node::new(NodeType::HeightToNormal(height_to_normal)))
+            .unwrap();
+        live_graph
+            .connect(input_node, grayscale_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(grayscale_node, normal_node, SlotId(0), SlotId(0))
+            .unwrap();
+        add_output(&mut live_graph, normal_node)
+    };
+
+    let pixels = LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .buffer_rgba_f32(output_node, SlotId(0))
+        .unwrap();
+    pixels
+}
+
+/// The RGB of the pixel at `x` and `y` in a 5 by 5 image.
+fn rgb_at(pixels: &[f32], x: usize, y: usize) -> [f32; 3] {
+    let i = (y * 5 + x) * 4;
+    [pixels[i], pixels[i + 1], pixels[i + 2]]
+}
+
+#[test]
+#[timeout(20_000)]
+fn height_to_normal_intensity() {
+    // A slope of 0.01 per pixel rises by 0.05 across the image, so an intensity of 20 makes it
+    // a 45 degree slope.
+    let ramp = |x: usize, _| x as f32 * 0.01;
+
+    for filter in [
+        NormalFilter::Backward,
+        NormalFilter::Central,
+        NormalFilter::Sobel,
+        NormalFilter::Scharr,
+    ] {
+        let height_to_normal = HeightToNormal::default().intensity(20.0).filter(filter);
+        assert_pixels_eq(
+            &rgb_at(&normal_pixels(height_to_normal, ramp), 2, 2),
+            &[0.146_4, 0.5, 0.853_6],
+        );
+    }
+
+    assert_pixels_eq(
+        &rgb_at(
+            &normal_pixels(HeightToNormal::default().intensity(0.0), ramp),
+            2,
+            2,
+        ),
+        &[0.5, 0.5, 1.0],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn height_to_normal_format() {
+    let ramp = |_, y: usize| y as f32 * 0.01;
+    let height_to_normal = HeightToNormal::default().intensity(20.0);
+
+    assert_pixels_eq(
+        &rgb_at(&normal_pixels(height_to_normal, ramp), 2, 2),
+        &[0.5, 0.853_6, 0.853_6],
+    );
+    assert_pixels_eq(
+        &rgb_at(
+            &normal_pixels(height_to_normal.format(NormalFormat::DirectX), ramp),
+            2,
+            2,
+        ),
+        &[0.5, 0.146_4, 0.853_6],
+    );
+    assert_pixels_eq(
+        &rgb_at(&normal_pixels(height_to_normal.signed(true), ramp), 2, 2),
+        &[0.0, 0.707_1, 0.707_1],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn height_to_normal_filters() {
+    let spike = |x, y| if (x, y) == (2, 2) { 0.1 } else { 0.0 };
+    let red_at_diagonal = |filter| {
+        rgb_at(
+            &normal_pixels(HeightToNormal::default().filter(filter), spike),
+            1,
+            1,
+        )[0]
+    };
+
+    // Only the filters that smooth across the slope see the spike from the diagonal.
+    assert_pixels_eq(&[red_at_diagonal(NormalFilter::Backward)], &[0.5]);
+    assert_pixels_eq(&[red_at_diagonal(NormalFilter::Central)], &[0.5]);
+    let sobel = red_at_diagonal(NormalFilter::Sobel);
+    let scharr = red_at_diagonal(NormalFilter::Scharr);
+    assert!(sobel < 0.5 && scharr < 0.5, "{}, {}", sobel, scharr);
+    assert!(sobel < scharr, "{}, {}", sobel, scharr);
+}
+
+#[test]
+#[timeout(20_000)]
+fn height_to_normal_legacy_json() {
+    // Graphs saved before `HeightToNormal` had settings store it as a unit variant.
+    match serde_json::from_str(r#""HeightToNormal""#).unwrap() {
+        NodeType::HeightToNormal(height_to_normal) => {
+            assert_eq!(height_to_normal, HeightToNormal::default())
+        }
+        _ => panic!("expected a `HeightToNormal` node"),
+    }
+
+    let height_to_normal = HeightToNormal::default()
+        .format(NormalFormat::DirectX)
+        .filter(NormalFilter::Sobel);
+    let json = serde_json::to_string(&NodeType::HeightToNormal(height_to_normal)).unwrap();
+    match serde_json::from_str(&json).unwrap() {
+        NodeType::HeightToNormal(deserialized) => assert_eq!(deserialized, height_to_normal),
+        _ => panic!("expected a `HeightToNormal` node"),
+    }
+
+    assert!(serde_json::from_str::<NodeType>(r#""NotANodeType""#).is_err());
+
+    // Other node types report what is wrong with them.
+    let error = serde_json::from_str::<NodeType>(
+        r#"{"Expression": {"expression": "a", "inputs": [{"name": "a", "slot_type": "Grey"}]}}"#,
+    )
+    .unwrap_err();
+    assert!(
+        error.to_string().contains("unknown variant `Grey`"),
+        "{}",
+        error
+    );
+}
+
+fn ktx2_encode(slot_image: &SlotImage, options: &Ktx2Options) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    vismut_core::export::ktx2::encode(slot_image, &mut buffer, options).unwrap();
+    buffer
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_ktx2_color_mipmaps() {
+    let buffer = ktx2_encode(
+        &SlotImage::from_value(Size::new(4, 2), 0.5, true),
+        &Ktx2Options::from_usage(TextureUsage::Color),
+    );
+    let reader = ktx2::Reader::new(&buffer).unwrap();
+
+    let header = reader.header();
+    assert_eq!(header.format, Some(ktx2::Format::R8G8B8A8_SRGB));
+    assert_eq!((header.pixel_width, header.pixel_height), (4, 2));
+    assert_eq!(header.level_count, 3);
+
+    let levels = reader.levels().collect::<Vec<&[u8]>>();
+    assert_eq!(
+        levels
+            .iter()
+            .map(|level| level.len())
+            .collect::<Vec<usize>>(),
+        vec![32, 8, 4]
+    );
+    for level in levels {
+        assert!(level
+            .chunks_exact(4)
+            .all(|pixel| pixel == [188, 188, 188, 255]));
+    }
+
+    let dfd = reader.data_format_descriptors().next().unwrap();
+    let basic = ktx2::BasicDataFormatDescriptor::parse(dfd.data).unwrap();
+    assert_eq!(basic.transfer_function, Some(ktx2::TransferFunction::SRGB));
+
+    // Alpha is always stored linearly.
+    let samples = basic.sample_information().collect::<Vec<_>>();
+    assert_eq!(samples.len(), 4);
+    assert_eq!(samples[3].channel_type, 15);
+    assert_eq!(
+        samples[3].channel_type_qualifiers,
+        ktx2::ChannelTypeQualifiers::LINEAR
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_ktx2_float() {
+    let buffer = ktx2_encode(
+        &SlotImage::from_value(Size::new(2, 2), 0.5, true),
+        &Ktx2Options::new(Ktx2Format::Rgba16Float)
+            .encoding(ColorEncoding::Linear)
+            .mipmaps(false),
+    );
+    let reader = ktx2::Reader::new(&buffer).unwrap();
+
+    let header = reader.header();
+    assert_eq!(header.format, Some(ktx2::Format::R16G16B16A16_SFLOAT));
+    assert_eq!(header.type_size, 2);
+    assert_eq!(header.level_count, 1);
+
+    // 0.5 and 1.0 as half precision floats.
+    let level = reader.levels().next().unwrap();
+    assert!(level
+        .chunks_exact(8)
+        .all(|pixel| pixel == [0x00, 0x38, 0x00, 0x38, 0x00, 0x38, 0x00, 0x3c]));
+
+    let dfd = reader.data_format_descriptors().next().unwrap();
+    let basic = ktx2::BasicDataFormatDescriptor::parse(dfd.data).unwrap();
+    assert!(basic.sample_information().all(|sample| sample
+        .channel_type_qualifiers
+        .contains(ktx2::ChannelTypeQualifiers::FLOAT)));
+
+    assert!(vismut_core::export::ktx2::encode(
+        &SlotImage::from_value(Size::new(2, 2), 0.5, true),
+        &mut Vec::new(),
+        &Ktx2Options::new(Ktx2Format::Rgba16Float).encoding(ColorEncoding::Srgb),
+    )
+    .is_err());
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_ktx2_normal_and_mask() {
+    let slot_image = slot_image_rgba(Size::new(1, 1), &[[0.2, 0.4, 0.6, 1.0]]);
+
+    let buffer = ktx2_encode(&slot_image, &Ktx2Options::from_usage(TextureUsage::Normal));
+    let reader = ktx2::Reader::new(&buffer).unwrap();
+    assert_eq!(reader.header().format, Some(ktx2::Format::R8G8_UNORM));
+    assert_eq!(reader.levels().next().unwrap(), &[51, 102]);
+
+    let buffer = ktx2_encode(&slot_image, &Ktx2Options::from_usage(TextureUsage::Mask));
+    let reader = ktx2::Reader::new(&buffer).unwrap();
+    assert_eq!(reader.header().format, Some(ktx2::Format::R8_UNORM));
+    assert_eq!(reader.levels().next().unwrap(), &[102]);
+}
+
+fn slot_image_rgba(size: Size, pixels: &[[f32; 4]]) -> SlotImage {
+    let mut buffers = (0..4)
+        .map(|c| {
+            Buffer::from_raw(
+                size.width,
+                size.height,
+                pixels.iter().map(|pixel| pixel[c]).collect(),
+            )
+            .unwrap()
+        })
+        .collect::<Vec<Buffer>>();
+
+    SlotImage::from_buffers_rgba(&mut buffers).unwrap()
+}
+
+fn smallest_mip(slot_image: &SlotImage, options: &MipOptions) -> Vec<f32> {
+    mipmap::mip_chain(slot_image, options)
+        .unwrap()
+        .last()
+        .unwrap()
+        .to_f32()
+        .unwrap()
+}
+
+#[test]
+#[timeout(20_000)]
+fn mip_chain_sizes() {
+    let mip_chain = mipmap::mip_chain(
+        &SlotImage::from_value(Size::new(8, 3), 0.5, false),
+        &MipOptions::default(),
+    )
+    .unwrap();
+
+    let sizes = mip_chain
+        .iter()
+        .map(|level| level.size().unwrap())
+        .collect::<Vec<Size>>();
+    assert_eq!(
+        sizes,
+        vec![
+            Size::new(8, 3),
+            Size::new(4, 1),
+            Size::new(2, 1),
+            Size::new(1, 1)
+        ]
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn mip_chain_filters_keep_value() {
+    for filter in [
+        MipFilter::Box,
+        MipFilter::Kaiser,
+        MipFilter::Resize(ResizeFilter::Triangle),
+        MipFilter::Resize(ResizeFilter::Lanczos3),
+    ] {
+        for wrap in [false, true] {
+            let mip_chain = mipmap::mip_chain(
+                &SlotImage::from_value(Size::new(16, 8), 0.25, true),
+                &MipOptions::new(filter).wrap(wrap),
+            )
+            .unwrap();
+
+            for level in mip_chain {
+                assert!(level
+                    .to_f32()
+                    .unwrap()
+                    .chunks_exact(4)
+                    .all(
+                        |pixel| (pixel[0] - 0.25).abs() < 0.0001 && (pixel[3] - 1.0).abs() < 0.0001
+                    ));
+            }
+        }
+    }
+}
+
+#[test]
+#[timeout(20_000)]
+fn mip_chain_srgb() {
+    let slot_image = slot_image_rgba(
+        Size::new(2, 1),
+        &[[0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0]],
+    );
+
+    let linear = smallest_mip(&slot_image, &MipOptions::default());
+    assert_eq!(linear, vec![0.5, 0.5, 0.5, 1.0]);
+
+    // Half the light is 0.735 in sRGB.
+    let srgb = smallest_mip(
+        &slot_image,
+        &MipOptions::default().encoding(ColorEncoding::Srgb),
+    );
+    assert!((srgb[0] - 0.735).abs() < 0.001);
+    assert_eq!(srgb[3], 1.0);
+}
+
+#[test]
+#[timeout(20_000)]
+fn mip_chain_normalize() {
+    // Two normals leaning 0.6 left and right.
+    let slot_image = slot_image_rgba(
+        Size::new(2, 1),
+        &[[0.8, 0.5, 0.9, 1.0], [0.2, 0.5, 0.9, 1.0]],
+    );
+
+    let averaged = smallest_mip(&slot_image, &MipOptions::default());
+    assert!((averaged[2] - 0.9).abs() < 0.0001);
+
+    let normalized = smallest_mip(&slot_image, &MipOptions::from_usage(TextureUsage::Normal));
+    assert!((normalized[0] - 0.5).abs() < 0.0001);
+    assert!((normalized[2] - 1.0).abs() < 0.0001);
+}
+
+#[test]
+#[timeout(20_000)]
+fn mip_chain_alpha_coverage() {
+    let slot_image = slot_image_rgba(
+        Size::new(2, 2),
+        &[
+            [1.0, 1.0, 1.0, 0.6],
+            [1.0, 1.0, 1.0, 0.6],
+            [1.0, 1.0, 1.0, 0.6],
+            [1.0, 1.0, 1.0, 0.2],
+        ],
+    );
+
+    let alpha = smallest_mip(&slot_image, &MipOptions::default())[3];
+    assert!(alpha <= 0.5);
+
+    // Three quarters of the pixels are above the cutoff, so the smallest level should be too.
+    let alpha = smallest_mip(
+        &slot_image,
+        &MipOptions::default().alpha_coverage(Some(0.5)),
+    )[3];
+    assert!(alpha > 0.5);
+}
+
+#[test]
+#[timeout(20_000)]
+fn mip_preview() {
+    let preview = mipmap::preview(
+        &SlotImage::from_value(Size::new(256, 64), 0.5, false),
+        100,
+        &MipOptions::new(MipFilter::Kaiser),
+    )
+    .unwrap();
+
+    assert_eq!(preview.size().unwrap(), Size::new(64, 16));
+}
+
+fn channel_pack_test(channel_pack: ChannelPack, expected: [f32; 4]) {
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+
+    {
+        let mut live_graph = live_graph.write().unwrap();
+
+        for (name, value) in [("roughness", 0.25), ("metallic", 1.0)] {
+            let value_node = live_graph
+                .add_node(Node::new(NodeType::Value(value)))
+                .unwrap();
+            let output_node = live_graph
+                .add_node(Node::new(NodeType::OutputGray(name.into())))
+                .unwrap();
+
+            live_graph
+                .connect(value_node, output_node, SlotId(0), SlotId(0))
+                .unwrap();
+        }
+    }
+
+    let slot_image = channel_pack.await_pack(&live_graph).unwrap();
+
+    assert_eq!(slot_image.size().unwrap(), Size::new(1, 1));
+    assert_eq!(slot_image.to_f32().unwrap(), expected);
+}
+
+#[test]
+#[timeout(20_000)]
+fn channel_pack_unreal_orm() {
+    channel_pack_test(PackPreset::UnrealOrm.into(), [1.0, 0.25, 1.0, 1.0]);
+}
+
+#[test]
+#[timeout(20_000)]
+fn channel_pack_unity_mask_map() {
+    channel_pack_test(PackPreset::UnityMaskMap.into(), [1.0, 1.0, 1.0, 0.75]);
+}
+
+#[test]
+#[timeout(20_000)]
+fn channel_pack_custom() {
+    channel_pack_test(
+        ChannelPack::new()
+            .channel(Channel::Red, PackedChannel::new("metallic").invert(true))
+            .channel(Channel::Green, PackedChannel::new("missing").default(0.5))
+            .channel(Channel::Blue, PackedChannel::new("roughness")),
+        [0.0, 0.5, 0.25, 1.0],
+    );
+}
+
+#[test]
+#[timeout(20_000)]
+fn export_gray_conversion() {
+    const PATH: &str = "out/export_gray_conversion.png";
+    ensure_out_dir();
+
+    // A red pixel, which is 1/3 when averaged and 1 with `GrayConversion::Max`.
+    let tex_pro = tex_pro_new();
+    let live_graph = tex_pro.new_live_graph().unwrap();
+    let output_node = {
+        let mut live_graph = live_graph.write().unwrap();
+        live_graph.set_gray_conversion(GrayConversion::Max).unwrap();
+        let rgba_node = embed_rgba(&mut live_graph, 0, Size::new(1, 1), &COLOR_PIXELS[..4]);
+        let levels_node = live_graph
+            .add_node(Node::new(NodeType::Levels(Levels::default())))
+            .unwrap();
+        let output_node = live_graph
+            .add_node(Node::new(NodeType::OutputGray("roughness".into())))
+            .unwrap();
+        live_graph
+            .connect(rgba_node, levels_node, SlotId(0), SlotId(0))
+            .unwrap();
+        live_graph
+            .connect(levels_node, output_node, SlotId(0), SlotId(0))
+            .unwrap();
+        output_node
+    };
+
+    let slot_image = ChannelPack::new()
+        .channel(Channel::Red, PackedChannel::new("roughness"))
+        .await_pack(&live_graph)
+        .unwrap();
+    assert_eq!(slot_image.to_f32().unwrap(), [1.0, 0.0, 0.0, 1.0]);
+
+    LiveGraph::await_clean_read(&live_graph, output_node)
+        .unwrap()
+        .export(
+            output_node,
+            SlotId(0),
+            PATH,
+            &ExportOptions::new(ExportFormat::Png).channels(ChannelLayout::Gray),
+        )
+        .unwrap();
+    assert_eq!(image::open(PATH).unwrap().to_luma8().into_raw(), [255]);
+}